@@ -186,11 +186,50 @@ where
 pub enum Literal {
     Byte(u8),
     Int(i64),
+    FixedInt(FixedInt),
     Float(f64),
     String(String),
     Char(char),
 }
 
+/// A literal of one of the fixed-width integer types, written with a suffix (`1i32`, `1u64`, `1w`)
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FixedInt {
+    I32(i32),
+    U64(u64),
+    /// An unsigned integer with the same width as a pointer
+    Word(u64),
+}
+
+impl FixedInt {
+    /// Returns the name of the type of this literal
+    pub fn type_name(self) -> &'static str {
+        match self {
+            FixedInt::I32(_) => "I32",
+            FixedInt::U64(_) => "U64",
+            FixedInt::Word(_) => "Word",
+        }
+    }
+
+    /// Returns the bit pattern of this literal
+    pub fn to_bits(self) -> i64 {
+        match self {
+            FixedInt::I32(i) => i as i64,
+            FixedInt::U64(i) | FixedInt::Word(i) => i as i64,
+        }
+    }
+}
+
+impl fmt::Display for FixedInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FixedInt::I32(i) => write!(f, "{}i32", i),
+            FixedInt::U64(i) => write!(f, "{}u64", i),
+            FixedInt::Word(i) => write!(f, "{}w", i),
+        }
+    }
+}
+
 /// Pattern which contains a location
 pub type SpannedPattern<Id> = Spanned<Pattern<Id>, BytePos>;
 
@@ -725,6 +764,7 @@ impl Typed for Literal {
     fn env_type_of(&self, _: &TypeEnv) -> ArcType {
         match *self {
             Literal::Int(_) => Type::int(),
+            Literal::FixedInt(i) => Type::ident(Symbol::from(i.type_name())),
             Literal::Float(_) => Type::float(),
             Literal::Byte(_) => Type::byte(),
             Literal::String(_) => Type::string(),
@@ -20,6 +20,26 @@ pub struct Metadata {
 }
 
 impl Metadata {
+    /// Creates metadata with the documentation comment `comment`
+    pub fn with_comment<S>(comment: S) -> Metadata
+    where
+        S: Into<String>,
+    {
+        Metadata {
+            comment: Some(comment.into()),
+            module: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the metadata of the field `name`
+    pub fn field<S>(mut self, name: S, metadata: Metadata) -> Metadata
+    where
+        S: Into<String>,
+    {
+        self.module.insert(name.into(), metadata);
+        self
+    }
+
     pub fn has_data(&self) -> bool {
         self.comment.is_some() || !self.module.is_empty()
     }
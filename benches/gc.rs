@@ -0,0 +1,65 @@
+#[macro_use]
+extern crate bencher;
+
+extern crate gluon;
+
+use bencher::{black_box, Bencher};
+
+use gluon::{new_vm, Compiler, RootedThread};
+use gluon::vm::api::FunctionRef;
+
+// Creates a vm which keeps a large list alive in a global so that every collection has a large
+// heap to consider
+fn vm_with_large_heap() -> RootedThread {
+    let vm = new_vm();
+    let text = r#"
+    let list = import! std.list
+    let make n acc =
+        if n #Int== 0 then acc
+        else make (n #Int- 1) (list.Cons n acc)
+    make 100000 list.Nil
+    "#;
+    Compiler::new().load_script(&vm, "large_heap", text).unwrap();
+    vm.collect();
+    vm
+}
+
+// Pause time of a full collection grows with the size of the heap
+fn full_collection(b: &mut Bencher) {
+    let vm = vm_with_large_heap();
+    b.iter(|| vm.collect())
+}
+
+// A minor collection only considers the values allocated since the last collection
+fn minor_collection(b: &mut Bencher) {
+    let vm = vm_with_large_heap();
+    b.iter(|| vm.collect_minor())
+}
+
+// Allocation heavy code which only keeps a few values alive at a time
+fn short_lived_allocations(b: &mut Bencher) {
+    let vm = vm_with_large_heap();
+    let text = r#"
+    let list = import! std.list
+    let loop n acc =
+        if n #Int== 0 then acc
+        else
+            let l = list.Cons n (list.Cons n list.Nil)
+            loop (n #Int- 1) (acc #Int+ 1)
+    loop
+    "#;
+    Compiler::new().load_script(&vm, "allocate", text).unwrap();
+    let mut allocate: FunctionRef<fn(i32, i32) -> i32> = vm.get_global("allocate").unwrap();
+    b.iter(|| {
+        let result = allocate.call(10000, 0).unwrap();
+        black_box(result)
+    })
+}
+
+benchmark_group!(
+    gc,
+    full_collection,
+    minor_collection,
+    short_lived_allocations
+);
+benchmark_main!(gc);
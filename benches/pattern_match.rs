@@ -0,0 +1,85 @@
+#[macro_use]
+extern crate bencher;
+
+extern crate gluon;
+
+use bencher::{black_box, Bencher};
+
+use gluon::{new_vm, Compiler};
+use gluon::vm::api::FunctionRef;
+
+// Benchmarks code which spends most of its time in pattern matching and field accesses
+fn match_list(b: &mut Bencher) {
+    let vm = new_vm();
+    let text = r#"
+    type List a = | Cons a (List a) | Nil
+
+    let range n acc =
+        if n #Int== 0 then acc
+        else range (n #Int- 1) (Cons n acc)
+
+    let sum xs acc =
+        match xs with
+        | Cons x rest -> sum rest (acc #Int+ x)
+        | Nil -> acc
+
+    \n -> sum (range n Nil) 0
+    "#;
+    Compiler::new().load_script(&vm, "sum", text).unwrap();
+    let mut sum: FunctionRef<fn(i32) -> i32> = vm.get_global("sum").unwrap();
+    b.iter(|| {
+        let result = sum.call(1000).unwrap();
+        black_box(result)
+    })
+}
+
+fn match_enum(b: &mut Bencher) {
+    let vm = new_vm();
+    let text = r#"
+    type Op = | Add | Sub | Mul | Neg | Nop
+
+    let apply op x =
+        match op with
+        | Add -> x #Int+ 1
+        | Sub -> x #Int- 1
+        | Mul -> x #Int* 2
+        | Neg -> 0 #Int- x
+        | Nop -> x
+
+    let loop n x =
+        if n #Int== 0 then x
+        else
+            let x = apply Nop (apply Sub (apply Add (apply Nop x)))
+            loop (n #Int- 1) x
+
+    \n -> loop n 0
+    "#;
+    Compiler::new().load_script(&vm, "match_enum", text).unwrap();
+    let mut test: FunctionRef<fn(i32) -> i32> = vm.get_global("match_enum").unwrap();
+    b.iter(|| {
+        let result = test.call(1000).unwrap();
+        black_box(result)
+    })
+}
+
+fn nested_fields(b: &mut Bencher) {
+    let vm = new_vm();
+    let text = r#"
+    let record = { a = { b = { c = 1 } } }
+
+    let loop n acc =
+        if n #Int== 0 then acc
+        else loop (n #Int- 1) (acc #Int+ record.a.b.c)
+
+    \n -> loop n 0
+    "#;
+    Compiler::new().load_script(&vm, "nested_fields", text).unwrap();
+    let mut test: FunctionRef<fn(i32) -> i32> = vm.get_global("nested_fields").unwrap();
+    b.iter(|| {
+        let result = test.call(1000).unwrap();
+        black_box(result)
+    })
+}
+
+benchmark_group!(pattern_match, match_list, match_enum, nested_fields);
+benchmark_main!(pattern_match);
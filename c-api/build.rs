@@ -0,0 +1,24 @@
+#[cfg(feature = "generate-header")]
+extern crate cbindgen;
+
+/// Generates `gluon.h` into `OUT_DIR`. The header in `include` is checked in so that the crate
+/// can be built without `cbindgen`, `tests/c.rs` checks that it matches the generated header.
+#[cfg(feature = "generate-header")]
+fn generate_header() {
+    use std::env;
+    use std::path::Path;
+
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_root_or_default(Path::new(&crate_dir));
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("Unable to generate the C header")
+        .write_to_file(Path::new(&out_dir).join("gluon.h"));
+}
+
+#[cfg(not(feature = "generate-header"))]
+fn generate_header() {}
+
+fn main() {
+    generate_header();
+}
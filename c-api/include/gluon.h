@@ -0,0 +1,114 @@
+#ifndef GLUON_H
+#define GLUON_H
+
+/* This file is generated by cbindgen (`cargo build --features generate-header`), do not edit it by hand */
+
+#include <stdint.h>
+#include <stdlib.h>
+#include <stdbool.h>
+
+typedef enum {
+  Error_Ok,
+  Error_Unknown,
+} Error;
+
+typedef enum {
+  Status_Ok,
+  Status_Yield,
+  Status_Error,
+} Status;
+
+typedef struct GluValue GluValue;
+
+typedef struct Thread Thread;
+
+typedef uint32_t VmIndex;
+
+typedef Status (*Function)(const Thread*);
+
+typedef intptr_t VmInt;
+
+typedef uint32_t VmTag;
+
+void glu_last_error_message(const uint8_t **out, size_t *out_len);
+
+const Thread *glu_new_vm(void);
+
+void glu_free_vm(const Thread *vm);
+
+Error glu_run_expr(const Thread *vm,
+                   const uint8_t *module,
+                   size_t module_len,
+                   const uint8_t *expr,
+                   size_t expr_len);
+
+Error glu_load_script(const Thread *vm,
+                      const uint8_t *module,
+                      size_t module_len,
+                      const uint8_t *expr,
+                      size_t expr_len);
+
+Error glu_call_function(const Thread *thread, VmIndex args);
+
+size_t glu_len(const Thread *vm);
+
+void glu_pop(const Thread *vm, size_t n);
+
+void glu_push_int(const Thread *vm, VmInt int_);
+
+void glu_push_byte(const Thread *vm, uint8_t b);
+
+void glu_push_float(const Thread *vm, double float_);
+
+void glu_push_bool(const Thread *vm, int8_t b);
+
+Error glu_push_function(const Thread *vm,
+                        const uint8_t *name,
+                        size_t len,
+                        Function function,
+                        VmIndex args);
+
+Error glu_push_string(const Thread *vm, const uint8_t *s, size_t len);
+
+Error glu_push_string_unchecked(const Thread *vm, const uint8_t *s, size_t len);
+
+void glu_push_light_userdata(const Thread *vm, void *data);
+
+Error glu_push_record(const Thread *vm,
+                      const uint8_t *const *fields,
+                      const size_t *field_lens,
+                      size_t len);
+
+Error glu_push_array(const Thread *vm, size_t len);
+
+Error glu_push_variant(const Thread *vm, VmTag tag, size_t len);
+
+Error glu_get_global(const Thread *vm, const uint8_t *name, size_t len);
+
+Error glu_get_byte(const Thread *vm, VmIndex index, uint8_t *out);
+
+Error glu_get_int(const Thread *vm, VmIndex index, VmInt *out);
+
+Error glu_get_float(const Thread *vm, VmIndex index, double *out);
+
+Error glu_get_bool(const Thread *vm, VmIndex index, int8_t *out);
+
+Error glu_get_string(const Thread *vm, VmIndex index, const uint8_t **out, size_t *out_len);
+
+Error glu_get_light_userdata(const Thread *vm, VmIndex index, void **out);
+
+Error glu_get_field(const Thread *vm, VmIndex index, const uint8_t *name, size_t len);
+
+Error glu_get_array_len(const Thread *vm, VmIndex index, size_t *out);
+
+Error glu_get_array_element(const Thread *vm, VmIndex index, size_t element);
+
+Error glu_get_tag(const Thread *vm, VmIndex index, VmTag *out);
+
+Error glu_root_value(const Thread *vm, VmIndex index, GluValue **out);
+
+Error glu_push_value(const Thread *vm, const GluValue *value);
+
+void glu_release_value(GluValue *value);
+
+#endif /* GLUON_H */
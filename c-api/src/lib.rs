@@ -1,38 +1,87 @@
 //! A (WIP) C API allowing use of gluon in other langauges than Rust.
+//!
+//! A C header declaring the functions of this crate is checked in at `include/gluon.h`. Building
+//! with `--features generate-header` regenerates it into `OUT_DIR` using `cbindgen`, which
+//! `tests/c.rs` checks against the checked in header.
 #![doc(html_root_url = "https://docs.rs/gluon_c-api/0.6.2")] // # GLUON
 
 extern crate gluon;
 extern crate libc;
 
+use std::cell::RefCell;
+use std::fmt;
 use std::str;
 use std::slice;
 
-use gluon::vm::api::{CPrimitive, Getable, Hole, OpaqueValue, Pushable};
-use gluon::vm::types::{VmIndex, VmInt};
-use gluon::vm::thread::{RootedThread, Status, Thread, ThreadInternal};
+use gluon::vm::Variants;
+use gluon::vm::api::{CPrimitive, Getable, Hole, OpaqueValue, Pushable, ValueRef};
+use gluon::vm::types::{VmIndex, VmInt, VmTag};
+use gluon::vm::thread::{Context, RootedThread, Status, Thread, ThreadInternal, VmRoot};
 
-use gluon::Compiler;
+use gluon::{new_vm, Compiler};
 
 pub type Function = extern "C" fn(&Thread) -> Status;
 
-// TODO What should the c api return as errors
-// TODO How should error messages be returned
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub enum Error {
     Ok,
+    /// The function failed, the message describing the failure can be retrieved with
+    /// `glu_last_error_message`
     Unknown,
 }
 
+/// A value which is kept alive by the garbage collector until it is released with
+/// `glu_release_value`
+pub struct GluValue(OpaqueValue<RootedThread, Hole>);
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = RefCell::new(String::new());
+}
+
+fn set_error<E>(err: E) -> Error
+where
+    E: fmt::Display,
+{
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = err.to_string());
+    Error::Unknown
+}
+
+macro_rules! try_str {
+    ($s: expr, $len: expr) => {
+        match str::from_utf8(slice::from_raw_parts($s, $len)) {
+            Ok(s) => s,
+            Err(err) => return set_error(err),
+        }
+    };
+}
+
+/// Retrieves the message of the last error returned by a function called on this OS thread. The
+/// message is valid until the next error occurs on the same OS thread.
+#[no_mangle]
+pub extern "C" fn glu_last_error_message(out: &mut *const u8, out_len: &mut usize) {
+    LAST_ERROR.with(|last_error| {
+        let last_error = last_error.borrow();
+        *out = last_error.as_ptr();
+        *out_len = last_error.len();
+    })
+}
+
+/// Creates a new virtual machine in the same way as `gluon::new_vm`. The `import!` macro is
+/// available and the standard library can be imported, so expressions run with `glu_run_expr` are
+/// compiled with the implicit prelude.
+#[no_mangle]
 pub extern "C" fn glu_new_vm() -> *const Thread {
-    let vm = RootedThread::new();
+    let vm = new_vm();
     vm.into_raw()
 }
 
+#[no_mangle]
 pub unsafe extern "C" fn glu_free_vm(vm: &Thread) {
     RootedThread::from_raw(vm);
 }
 
+#[no_mangle]
 pub unsafe extern "C" fn glu_run_expr(
     vm: &Thread,
     module: &u8,
@@ -40,21 +89,16 @@ pub unsafe extern "C" fn glu_run_expr(
     expr: &u8,
     expr_len: usize,
 ) -> Error {
-    let module = match str::from_utf8(slice::from_raw_parts(module, module_len)) {
-        Ok(s) => s,
-        Err(_) => return Error::Unknown,
-    };
-    let expr = match str::from_utf8(slice::from_raw_parts(expr, expr_len)) {
-        Ok(s) => s,
-        Err(_) => return Error::Unknown,
-    };
+    let module = try_str!(module, module_len);
+    let expr = try_str!(expr, expr_len);
     let result = Compiler::new().run_expr::<OpaqueValue<&Thread, Hole>>(&vm, module, expr);
     match result {
         Ok(_) => Error::Ok,
-        Err(_) => Error::Unknown,
+        Err(err) => set_error(err),
     }
 }
 
+#[no_mangle]
 pub unsafe extern "C" fn glu_load_script(
     vm: &Thread,
     module: &u8,
@@ -62,35 +106,32 @@ pub unsafe extern "C" fn glu_load_script(
     expr: &u8,
     expr_len: usize,
 ) -> Error {
-    let module = match str::from_utf8(slice::from_raw_parts(module, module_len)) {
-        Ok(s) => s,
-        Err(_) => return Error::Unknown,
-    };
-    let expr = match str::from_utf8(slice::from_raw_parts(expr, expr_len)) {
-        Ok(s) => s,
-        Err(_) => return Error::Unknown,
-    };
+    let module = try_str!(module, module_len);
+    let expr = try_str!(expr, expr_len);
     let result = Compiler::new().load_script(vm, module, expr);
     match result {
         Ok(_) => Error::Ok,
-        Err(_) => Error::Unknown,
+        Err(err) => set_error(err),
     }
 }
 
+#[no_mangle]
 pub extern "C" fn glu_call_function(thread: &Thread, args: VmIndex) -> Error {
     let context = thread.context();
     match thread.call_function(context, args) {
         Ok(_) => Error::Ok,
-        Err(_) => Error::Unknown,
+        Err(err) => set_error(err),
     }
 }
 
+#[no_mangle]
 pub extern "C" fn glu_len(vm: &Thread) -> usize {
     let mut context = vm.context();
     let stack = context.stack.current_frame();
     stack.len() as usize
 }
 
+#[no_mangle]
 pub extern "C" fn glu_pop(vm: &Thread, n: usize) {
     let mut context = vm.context();
     for _ in 0..n {
@@ -98,22 +139,27 @@ pub extern "C" fn glu_pop(vm: &Thread, n: usize) {
     }
 }
 
+#[no_mangle]
 pub extern "C" fn glu_push_int(vm: &Thread, int: VmInt) {
     Thread::push(vm, int).unwrap();
 }
 
+#[no_mangle]
 pub extern "C" fn glu_push_byte(vm: &Thread, b: u8) {
     Thread::push(vm, b).unwrap();
 }
 
+#[no_mangle]
 pub extern "C" fn glu_push_float(vm: &Thread, float: f64) {
     Thread::push(vm, float).unwrap();
 }
 
+#[no_mangle]
 pub extern "C" fn glu_push_bool(vm: &Thread, b: i8) {
     Thread::push(vm, b != 0).unwrap();
 }
 
+#[no_mangle]
 pub unsafe extern "C" fn glu_push_function(
     vm: &Thread,
     name: &u8,
@@ -121,54 +167,102 @@ pub unsafe extern "C" fn glu_push_function(
     function: Function,
     args: VmIndex,
 ) -> Error {
-    let s = match str::from_utf8(slice::from_raw_parts(name, len)) {
-        Ok(s) => s,
-        Err(_) => return Error::Unknown,
-    };
+    let s = try_str!(name, len);
     match Thread::push(vm, CPrimitive::new(function, args, s)) {
         Ok(()) => Error::Ok,
-        Err(_) => Error::Unknown,
+        Err(err) => set_error(err),
     }
 }
 
 /// Push a string to the stack. The string must be valid utf-8 or an error will be returned
+#[no_mangle]
 pub unsafe extern "C" fn glu_push_string(vm: &Thread, s: &u8, len: usize) -> Error {
-    let s = match str::from_utf8(slice::from_raw_parts(s, len)) {
-        Ok(s) => s,
-        Err(_) => return Error::Unknown,
-    };
+    let s = try_str!(s, len);
     match s.push(vm, &mut vm.context()) {
         Ok(()) => Error::Ok,
-        Err(_) => Error::Unknown,
+        Err(err) => set_error(err),
     }
 }
 
 /// Push a string to the stack. If the string is not utf-8 this function will trigger undefined
 /// behaviour.
+#[no_mangle]
 pub unsafe extern "C" fn glu_push_string_unchecked(vm: &Thread, s: &u8, len: usize) -> Error {
     let s = str::from_utf8_unchecked(slice::from_raw_parts(s, len));
     match s.push(vm, &mut vm.context()) {
         Ok(()) => Error::Ok,
-        Err(_) => Error::Unknown,
+        Err(err) => set_error(err),
     }
 }
 
+#[no_mangle]
 pub extern "C" fn glu_push_light_userdata(vm: &Thread, data: *mut libc::c_void) {
     Thread::push(vm, data as usize).unwrap()
 }
 
+/// Pops `len` values from the stack and pushes a record where the fields, named by the `len`
+/// strings in `fields` and `field_lens`, are bound to those values in order
+#[no_mangle]
+pub unsafe extern "C" fn glu_push_record(
+    vm: &Thread,
+    fields: *const *const u8,
+    field_lens: *const usize,
+    len: usize,
+) -> Error {
+    let mut names = Vec::with_capacity(len);
+    if len != 0 {
+        let fields = slice::from_raw_parts(fields, len);
+        let field_lens = slice::from_raw_parts(field_lens, len);
+        for (&field, &field_len) in fields.iter().zip(field_lens) {
+            names.push(try_str!(field, field_len));
+        }
+    }
+    pop_values(vm, len, |context| context.push_new_record(vm, &names))
+}
+
+/// Pops `len` values from the stack and pushes an array containing them
+#[no_mangle]
+pub extern "C" fn glu_push_array(vm: &Thread, len: usize) -> Error {
+    pop_values(vm, len, |context| context.push_new_array(vm, len))
+}
+
+/// Pops `len` values from the stack and pushes the variant with `tag` containing them. The tag of
+/// a variant is its position in the declaration of its type.
+#[no_mangle]
+pub extern "C" fn glu_push_variant(vm: &Thread, tag: VmTag, len: usize) -> Error {
+    pop_values(vm, len, |context| context.push_new_data(vm, tag, len))
+}
+
+/// Pushes the global `name` (such as a module loaded with `glu_load_script`) to the stack
+#[no_mangle]
+pub unsafe extern "C" fn glu_get_global(vm: &Thread, name: &u8, len: usize) -> Error {
+    let name = try_str!(name, len);
+    let value = match vm.get_global::<OpaqueValue<&Thread, Hole>>(name) {
+        Ok(value) => value,
+        Err(err) => return set_error(err),
+    };
+    match Thread::push(vm, value) {
+        Ok(()) => Error::Ok,
+        Err(err) => set_error(err),
+    }
+}
+
+#[no_mangle]
 pub extern "C" fn glu_get_byte(vm: &Thread, index: VmIndex, out: &mut u8) -> Error {
     get_value(vm, index, out)
 }
 
+#[no_mangle]
 pub extern "C" fn glu_get_int(vm: &Thread, index: VmIndex, out: &mut VmInt) -> Error {
     get_value(vm, index, out)
 }
 
+#[no_mangle]
 pub extern "C" fn glu_get_float(vm: &Thread, index: VmIndex, out: &mut f64) -> Error {
     get_value(vm, index, out)
 }
 
+#[no_mangle]
 pub extern "C" fn glu_get_bool(vm: &Thread, index: VmIndex, out: &mut i8) -> Error {
     let mut b = false;
     let err = get_value(vm, index, &mut b);
@@ -180,6 +274,7 @@ pub extern "C" fn glu_get_bool(vm: &Thread, index: VmIndex, out: &mut i8) -> Err
 
 /// The returned string is garbage collected and may not be valid after the string is removed from
 /// its slot in the stack
+#[no_mangle]
 pub unsafe extern "C" fn glu_get_string(
     vm: &Thread,
     index: VmIndex,
@@ -196,10 +291,11 @@ pub unsafe extern "C" fn glu_get_string(
             *out_len = value.len();
             Error::Ok
         }
-        None => Error::Unknown,
+        None => set_error(format!("No value at index {}", index)),
     }
 }
 
+#[no_mangle]
 pub extern "C" fn glu_get_light_userdata(
     vm: &Thread,
     index: VmIndex,
@@ -213,6 +309,108 @@ pub extern "C" fn glu_get_light_userdata(
     err
 }
 
+/// Pushes the field `name` of the record at `index` to the stack
+#[no_mangle]
+pub unsafe extern "C" fn glu_get_field(
+    vm: &Thread,
+    index: VmIndex,
+    name: &u8,
+    len: usize,
+) -> Error {
+    let name = try_str!(name, len);
+    push_from(vm, index, |value| match value.as_ref() {
+        ValueRef::Data(data) => data.lookup_field(vm, name)
+            .ok_or_else(|| format!("The record at index {} has no field `{}`", index, name)),
+        _ => Err(format!("The value at index {} is not a record", index)),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn glu_get_array_len(vm: &Thread, index: VmIndex, out: &mut usize) -> Error {
+    let mut context = vm.context();
+    let stack = context.stack.current_frame();
+    match stack.get_variants(index).map(|value| value.as_ref()) {
+        Some(ValueRef::Array(array)) => {
+            *out = array.len();
+            Error::Ok
+        }
+        Some(_) => set_error(format!("The value at index {} is not an array", index)),
+        None => set_error(format!("No value at index {}", index)),
+    }
+}
+
+/// Pushes the element at `element` of the array at `index` to the stack
+#[no_mangle]
+pub extern "C" fn glu_get_array_element(vm: &Thread, index: VmIndex, element: usize) -> Error {
+    push_from(vm, index, |value| match value.as_ref() {
+        ValueRef::Array(array) => array.get(element).ok_or_else(|| {
+            format!(
+                "Index {} is out of bounds for the array at index {}",
+                element, index
+            )
+        }),
+        _ => Err(format!("The value at index {} is not an array", index)),
+    })
+}
+
+/// Retrieves the tag of the variant at `index`
+#[no_mangle]
+pub extern "C" fn glu_get_tag(vm: &Thread, index: VmIndex, out: &mut VmTag) -> Error {
+    let mut context = vm.context();
+    let stack = context.stack.current_frame();
+    match stack.get_variants(index).map(|value| value.as_ref()) {
+        Some(ValueRef::Data(data)) => {
+            *out = data.tag();
+            Error::Ok
+        }
+        Some(_) => set_error(format!("The value at index {} is not a variant", index)),
+        None => set_error(format!("No value at index {}", index)),
+    }
+}
+
+/// Roots the value at `index`, letting it be used after it has been removed from the stack. The
+/// value is kept alive until it is released with `glu_release_value`.
+#[no_mangle]
+pub extern "C" fn glu_root_value(
+    vm: &Thread,
+    index: VmIndex,
+    out: &mut *mut GluValue,
+) -> Error {
+    let value = match get_opaque(vm, index, |value| Ok(value)) {
+        Ok(value) => value,
+        Err(err) => return err,
+    };
+    // Each value is rooted in its own thread as values must be unrooted in the reverse order that
+    // they were rooted in
+    let thread = match vm.new_thread() {
+        Ok(thread) => thread,
+        Err(err) => return set_error(err),
+    };
+    let value = unsafe { thread.root_value_with_self(value.get_value()) };
+    *out = Box::into_raw(Box::new(GluValue(OpaqueValue::from_value(value))));
+    Error::Ok
+}
+
+/// Pushes a value rooted with `glu_root_value` to the stack
+#[no_mangle]
+pub extern "C" fn glu_push_value(vm: &Thread, value: &GluValue) -> Error {
+    let value: OpaqueValue<&Thread, Hole> =
+        OpaqueValue::from_value(vm.root_value(unsafe { value.0.get_value() }));
+    match Thread::push(vm, value) {
+        Ok(()) => Error::Ok,
+        Err(err) => set_error(err),
+    }
+}
+
+/// Releases a value rooted with `glu_root_value`. The value may not be used after it has been
+/// released.
+#[no_mangle]
+pub unsafe extern "C" fn glu_release_value(value: *mut GluValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
 fn get_value<T>(vm: &Thread, index: VmIndex, out: &mut T) -> Error
 where
     T: for<'vm> Getable<'vm>,
@@ -226,7 +424,57 @@ where
             *out = value;
             Error::Ok
         }
-        None => Error::Unknown,
+        None => set_error(format!("No value at index {}", index)),
+    }
+}
+
+fn get_opaque<'vm, F>(
+    vm: &'vm Thread,
+    index: VmIndex,
+    f: F,
+) -> Result<OpaqueValue<&'vm Thread, Hole>, Error>
+where
+    F: for<'a> FnOnce(Variants<'a>) -> Result<Variants<'a>, String>,
+{
+    let mut context = vm.context();
+    let stack = context.stack.current_frame();
+    match stack.get_variants(index) {
+        Some(value) => f(value)
+            .map(|value| Getable::from_value(vm, value))
+            .map_err(set_error),
+        None => Err(set_error(format!("No value at index {}", index))),
+    }
+}
+
+fn push_from<F>(vm: &Thread, index: VmIndex, f: F) -> Error
+where
+    F: for<'a> FnOnce(Variants<'a>) -> Result<Variants<'a>, String>,
+{
+    let value = match get_opaque(vm, index, f) {
+        Ok(value) => value,
+        Err(err) => return err,
+    };
+    match Thread::push(vm, value) {
+        Ok(()) => Error::Ok,
+        Err(err) => set_error(err),
+    }
+}
+
+fn pop_values<F>(vm: &Thread, len: usize, f: F) -> Error
+where
+    F: FnOnce(&mut Context) -> gluon::vm::Result<()>,
+{
+    let mut context = vm.context();
+    let stack_len = context.stack.current_frame().len() as usize;
+    if stack_len < len {
+        return set_error(format!(
+            "Expected at least {} values on the stack but found {}",
+            len, stack_len
+        ));
+    }
+    match f(&mut context) {
+        Ok(()) => Error::Ok,
+        Err(err) => set_error(err),
     }
 }
 
@@ -326,4 +574,107 @@ mod tests {
             glu_free_vm(vm);
         }
     }
+
+    #[test]
+    fn records_arrays_and_variants() {
+        unsafe {
+            let vm = &*glu_new_vm();
+
+            glu_push_int(vm, 1);
+            glu_push_int(vm, 2);
+            let fields = ["x".as_ptr(), "y".as_ptr()];
+            let field_lens = [1, 1];
+            assert_eq!(
+                glu_push_record(vm, fields.as_ptr(), field_lens.as_ptr(), 2),
+                Error::Ok
+            );
+            let name = "y";
+            assert_eq!(glu_get_field(vm, 0, &name.as_bytes()[0], name.len()), Error::Ok);
+            let mut int = 0;
+            assert_eq!(glu_get_int(vm, 1, &mut int), Error::Ok);
+            assert_eq!(int, 2);
+
+            glu_push_int(vm, 10);
+            glu_push_int(vm, 20);
+            glu_push_int(vm, 30);
+            assert_eq!(glu_push_array(vm, 3), Error::Ok);
+            let mut len = 0;
+            assert_eq!(glu_get_array_len(vm, 2, &mut len), Error::Ok);
+            assert_eq!(len, 3);
+            assert_eq!(glu_get_array_element(vm, 2, 2), Error::Ok);
+            assert_eq!(glu_get_int(vm, 3, &mut int), Error::Ok);
+            assert_eq!(int, 30);
+
+            glu_push_int(vm, 5);
+            assert_eq!(glu_push_variant(vm, 1, 1), Error::Ok);
+            let mut tag = 0;
+            assert_eq!(glu_get_tag(vm, 4, &mut tag), Error::Ok);
+            assert_eq!(tag, 1);
+
+            assert_eq!(glu_len(vm), 5);
+
+            glu_free_vm(vm);
+        }
+    }
+
+    #[test]
+    fn rooted_closure() {
+        unsafe {
+            let vm = &*glu_new_vm();
+
+            let module = "test";
+            let script = "let double x = x * 2\n{ double }";
+            assert_eq!(
+                glu_load_script(
+                    vm,
+                    &module.as_bytes()[0],
+                    module.len(),
+                    &script.as_bytes()[0],
+                    script.len(),
+                ),
+                Error::Ok
+            );
+            assert_eq!(glu_get_global(vm, &module.as_bytes()[0], module.len()), Error::Ok);
+            let name = "double";
+            assert_eq!(glu_get_field(vm, 0, &name.as_bytes()[0], name.len()), Error::Ok);
+
+            let mut double = ptr::null_mut();
+            assert_eq!(glu_root_value(vm, 1, &mut double), Error::Ok);
+            glu_pop(vm, 2);
+
+            assert_eq!(glu_push_value(vm, &*double), Error::Ok);
+            glu_push_int(vm, 21);
+            assert_eq!(glu_call_function(vm, 1), Error::Ok);
+            let mut int = 0;
+            assert_eq!(glu_get_int(vm, 0, &mut int), Error::Ok);
+            assert_eq!(int, 42);
+
+            glu_release_value(double);
+            glu_free_vm(vm);
+        }
+    }
+
+    #[test]
+    fn last_error_message() {
+        unsafe {
+            let vm = &*glu_new_vm();
+
+            glu_push_int(vm, 1);
+            let name = "missing";
+            assert_eq!(
+                glu_get_field(vm, 0, &name.as_bytes()[0], name.len()),
+                Error::Unknown
+            );
+
+            let mut message_ptr = ptr::null();
+            let mut message_len = 0;
+            glu_last_error_message(&mut message_ptr, &mut message_len);
+            assert_eq!(
+                str::from_utf8(slice::from_raw_parts(message_ptr, message_len)),
+                Ok("The value at index 0 is not a record")
+            );
+
+            glu_free_vm(vm);
+        }
+    }
 }
@@ -0,0 +1,76 @@
+//! Compiles `tests/main.c` against the static library and the checked in header and runs it
+#![cfg(unix)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+// The system libraries which the Rust standard library links to. They are not included in the
+// static library so they must be passed to the C compiler as well. These are the libraries printed
+// by `rustc --print native-static-libs` when building the static library.
+#[cfg(target_os = "macos")]
+const NATIVE_LIBS: &[&str] = &["-lSystem", "-lresolv", "-lc", "-lm"];
+#[cfg(not(target_os = "macos"))]
+const NATIVE_LIBS: &[&str] = &["-lpthread", "-ldl", "-lrt", "-lutil", "-lm"];
+
+#[test]
+fn c_program() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    // The test executable is placed in `target/<profile>/deps` and the library in
+    // `target/<profile>`
+    let target_dir = env::current_exe()
+        .unwrap()
+        .parent()
+        .and_then(|deps| deps.parent())
+        .expect("target directory")
+        .to_owned();
+    let executable = target_dir.join("c_api_test");
+
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".into()))
+        .arg(manifest_dir.join("tests/main.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(target_dir.join("libgluon_c_api.a"))
+        .args(NATIVE_LIBS)
+        .arg("-o")
+        .arg(&executable)
+        .status()
+        .expect("Unable to run the C compiler");
+    assert!(status.success(), "Unable to compile tests/main.c");
+
+    let output = Command::new(&executable)
+        .output()
+        .expect("Unable to run the C program");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
+
+#[cfg(feature = "generate-header")]
+#[test]
+fn checked_in_header_is_up_to_date() {
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+
+    fn read_to_string(path: &Path) -> String {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .unwrap_or_else(|err| panic!("Unable to read `{}`: {}", path.display(), err));
+        contents
+    }
+
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let generated_path = Path::new(env!("OUT_DIR")).join("gluon.h");
+    let generated = read_to_string(&generated_path);
+    let checked_in = read_to_string(&manifest_dir.join("include/gluon.h"));
+    assert!(
+        generated == checked_in,
+        "`include/gluon.h` is out of date, replace it with `{}`",
+        generated_path.display()
+    );
+}
@@ -0,0 +1,113 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "gluon.h"
+
+#define STR(s) (const uint8_t *)(s), strlen(s)
+
+#define CHECK(expr)                                                            \
+    do {                                                                       \
+        if ((expr) != Error_Ok) {                                              \
+            const uint8_t *message;                                            \
+            size_t message_len;                                                \
+            glu_last_error_message(&message, &message_len);                    \
+            fprintf(stderr, "%s:%d: `%s` failed: %.*s\n", __FILE__, __LINE__, \
+                    #expr, (int)message_len, (const char *)message);           \
+            return 1;                                                          \
+        }                                                                      \
+    } while (0)
+
+#define ASSERT(expr)                                                           \
+    do {                                                                       \
+        if (!(expr)) {                                                         \
+            fprintf(stderr, "%s:%d: assertion `%s` failed\n", __FILE__,       \
+                    __LINE__, #expr);                                          \
+            return 1;                                                          \
+        }                                                                      \
+    } while (0)
+
+static Status add(const Thread *vm) {
+    VmInt l, r;
+    if (glu_get_int(vm, 0, &l) != Error_Ok || glu_get_int(vm, 1, &r) != Error_Ok) {
+        return Status_Error;
+    }
+    glu_push_int(vm, l + r);
+    return Status_Ok;
+}
+
+int main(void) {
+    const Thread *vm = glu_new_vm();
+
+    CHECK(glu_load_script(vm, STR("test"),
+                          STR("let double x = x * 2\n"
+                              "{ name = \"gluon\", values = [1, 2, 3], double, opt = Some 1 }")));
+    CHECK(glu_get_global(vm, STR("test")));
+
+    /* Records */
+    const uint8_t *name;
+    size_t name_len;
+    CHECK(glu_get_field(vm, 0, STR("name")));
+    CHECK(glu_get_string(vm, 1, &name, &name_len));
+    ASSERT(name_len == 5 && memcmp(name, "gluon", 5) == 0);
+
+    /* Arrays */
+    size_t len;
+    VmInt value;
+    CHECK(glu_get_field(vm, 0, STR("values")));
+    CHECK(glu_get_array_len(vm, 2, &len));
+    ASSERT(len == 3);
+    CHECK(glu_get_array_element(vm, 2, 1));
+    CHECK(glu_get_int(vm, 3, &value));
+    ASSERT(value == 2);
+
+    /* Variants */
+    VmTag tag;
+    CHECK(glu_get_field(vm, 0, STR("opt")));
+    CHECK(glu_get_tag(vm, 4, &tag));
+    ASSERT(tag == 1);
+
+    /* Closures which outlive their slot in the stack */
+    GluValue *double_;
+    CHECK(glu_get_field(vm, 0, STR("double")));
+    CHECK(glu_root_value(vm, 5, &double_));
+    glu_pop(vm, glu_len(vm));
+
+    CHECK(glu_push_value(vm, double_));
+    glu_push_int(vm, 21);
+    CHECK(glu_call_function(vm, 1));
+    CHECK(glu_get_int(vm, 0, &value));
+    ASSERT(value == 42);
+    glu_pop(vm, 1);
+    glu_release_value(double_);
+
+    /* Values constructed from C */
+    const uint8_t *fields[] = { (const uint8_t *)"x", (const uint8_t *)"y" };
+    size_t field_lens[] = { 1, 1 };
+    glu_push_int(vm, 1);
+    glu_push_int(vm, 2);
+    CHECK(glu_push_record(vm, fields, field_lens, 2));
+    CHECK(glu_get_field(vm, 0, STR("y")));
+    CHECK(glu_get_int(vm, 1, &value));
+    ASSERT(value == 2);
+    glu_pop(vm, 2);
+
+    CHECK(glu_push_function(vm, STR("add"), add, 2));
+    glu_push_int(vm, 1);
+    glu_push_int(vm, 2);
+    CHECK(glu_call_function(vm, 2));
+    CHECK(glu_get_int(vm, 0, &value));
+    ASSERT(value == 3);
+    glu_pop(vm, 1);
+
+    /* Errors */
+    const uint8_t *message;
+    size_t message_len;
+    glu_push_int(vm, 1);
+    ASSERT(glu_get_field(vm, 0, STR("x")) == Error_Unknown);
+    glu_last_error_message(&message, &message_len);
+    ASSERT(message_len != 0);
+
+    glu_free_vm(vm);
+    printf("ok\n");
+    return 0;
+}
@@ -6,6 +6,7 @@ use std::fmt;
 use std::iter::once;
 use std::mem;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use itertools::Itertools;
 
@@ -59,6 +60,8 @@ pub enum TypeError<I> {
     },
     /// Found a case expression without any alternatives
     EmptyCase,
+    /// Typechecking was aborted as the interrupt flag was set
+    Interrupted,
     Message(String),
 }
 
@@ -147,6 +150,7 @@ impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
                 Ok(())
             }
             EmptyCase => write!(f, "`case` expression with no alternatives"),
+            Interrupted => write!(f, "Typechecking was interrupted"),
             Message(ref msg) => write!(f, "{}", msg),
         }
     }
@@ -271,6 +275,9 @@ pub struct Typecheck<'a> {
     type_variables: ScopedMap<Symbol, ArcType>,
     type_cache: TypeCache<Symbol, ArcType>,
     kind_cache: KindCache,
+    /// Flag which aborts typechecking when set (see `set_interrupt`)
+    interrupt: Option<&'a AtomicBool>,
+    interrupted: bool,
 }
 
 /// Error returned when unsuccessfully typechecking an expression
@@ -300,9 +307,25 @@ impl<'a> Typecheck<'a> {
             type_variables: ScopedMap::new(),
             type_cache: type_cache,
             kind_cache: kind_cache,
+            interrupt: None,
+            interrupted: false,
         }
     }
 
+    /// Sets a flag which is checked periodically while typechecking. If the flag becomes `true`
+    /// typechecking is aborted and a `TypeError::Interrupted` error is returned.
+    pub fn set_interrupt(&mut self, interrupt: &'a AtomicBool) {
+        self.interrupt = Some(interrupt);
+    }
+
+    fn check_interrupted(&mut self) -> bool {
+        if !self.interrupted {
+            self.interrupted = self.interrupt
+                .map_or(false, |interrupt| interrupt.load(Ordering::Relaxed));
+        }
+        self.interrupted
+    }
+
     fn error<E>(&mut self, span: Span<BytePos>, error: E) -> ArcType
     where
         E: Into<HelpError<Symbol>>,
@@ -505,6 +528,7 @@ impl<'a> Typecheck<'a> {
                 | DuplicateField(_)
                 | UndefinedRecord { .. }
                 | EmptyCase
+                | Interrupted
                 | Rename(_)
                 | KindError(_)
                 | Message(_) => (),
@@ -570,8 +594,17 @@ impl<'a> Typecheck<'a> {
         }
         self.subs.clear();
         self.environment.stack.clear();
+        self.interrupted = false;
 
         let mut typ = self.typecheck_opt(expr, expected_type);
+        if self.interrupted {
+            self.errors = Errors::new();
+            self.errors.push(Spanned {
+                span: expr_check_span(expr),
+                value: TypeError::Interrupted.into(),
+            });
+            return Err(mem::replace(&mut self.errors, Errors::new()));
+        }
         if let Some(expected) = expected_type {
             let expected = self.create_unifiable_signature(expected)
                 .unwrap_or_else(|| expected.clone());
@@ -630,6 +663,11 @@ impl<'a> Typecheck<'a> {
         let mut scope_count = 0;
         let returned_type;
         loop {
+            if self.check_interrupted() {
+                // The error is reported once in `typecheck_expr_expected`
+                returned_type = self.subs.new_var();
+                break;
+            }
             let expected_type = expected_type.map(|t| self.skolemize(t));
             match self.typecheck_(expr, expected_type.as_ref()) {
                 Ok(tailcall) => {
@@ -684,6 +722,7 @@ impl<'a> Typecheck<'a> {
             }
             Expr::Literal(ref lit) => Ok(TailCall::Type(match *lit {
                 Literal::Int(_) => self.type_cache.int(),
+                Literal::FixedInt(i) => Type::ident(Symbol::from(i.type_name())),
                 Literal::Byte(_) => self.type_cache.byte(),
                 Literal::Float(_) => self.type_cache.float(),
                 Literal::String(_) => self.type_cache.string(),
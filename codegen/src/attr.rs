@@ -0,0 +1,75 @@
+use syn::{Attribute, Lit, Meta, NestedMeta};
+
+/// Attributes placed on the type which is derived for
+#[derive(Default)]
+pub struct Container {
+    /// `#[gluon(vm_type = "module.Type")]`, binds the type to an existing gluon type
+    pub vm_type: Option<String>,
+    /// `#[gluon(clone)]`, lets userdata be deep cloned by cloning it
+    pub clone: bool,
+}
+
+/// Attributes placed on a field
+#[derive(Default)]
+pub struct Field {
+    /// `#[gluon(name = "field_name")]`, the name of the field in gluon
+    pub name: Option<String>,
+}
+
+fn gluon_meta_items(attrs: &[Attribute]) -> Vec<NestedMeta> {
+    attrs
+        .iter()
+        .filter_map(|attr| attr.interpret_meta())
+        .filter_map(|meta| match meta {
+            Meta::List(ref list) if list.ident == "gluon" => {
+                Some(list.nested.iter().cloned().collect::<Vec<_>>())
+            }
+            _ => None,
+        })
+        .flat_map(|items| items)
+        .collect()
+}
+
+fn string_value(name: &str, lit: &Lit) -> String {
+    match *lit {
+        Lit::Str(ref s) => s.value(),
+        _ => panic!("Expected a string literal for `#[gluon({} = ...)]`", name),
+    }
+}
+
+impl Container {
+    pub fn from_ast(attrs: &[Attribute]) -> Container {
+        let mut container = Container::default();
+        for item in gluon_meta_items(attrs) {
+            match item {
+                NestedMeta::Meta(Meta::NameValue(ref name_value))
+                    if name_value.ident == "vm_type" =>
+                {
+                    container.vm_type = Some(string_value("vm_type", &name_value.lit));
+                }
+                NestedMeta::Meta(Meta::Word(ref word)) if word == "clone" => {
+                    container.clone = true;
+                }
+                _ => panic!("Unexpected gluon attribute on the type"),
+            }
+        }
+        container
+    }
+}
+
+impl Field {
+    pub fn from_ast(attrs: &[Attribute]) -> Field {
+        let mut field = Field::default();
+        for item in gluon_meta_items(attrs) {
+            match item {
+                NestedMeta::Meta(Meta::NameValue(ref name_value))
+                    if name_value.ident == "name" =>
+                {
+                    field.name = Some(string_value("name", &name_value.lit));
+                }
+                _ => panic!("Unexpected gluon attribute on a field"),
+            }
+        }
+        field
+    }
+}
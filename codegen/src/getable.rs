@@ -0,0 +1,92 @@
+use proc_macro2::TokenStream;
+use syn::{Data, DeriveInput, Fields, Index};
+
+use shared::{add_vm_lifetime, construct, field_names, where_clause, wrap_impl};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    let DeriveInput {
+        ident,
+        generics,
+        data,
+        ..
+    } = input;
+
+    let from_value = match data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Unit => quote!(#ident),
+            _ => {
+                let exprs = field_names(&data.fields)
+                    .into_iter()
+                    .map(|name| {
+                        quote! {
+                            _gluon::vm::api::Getable::from_value(
+                                vm,
+                                data.lookup_field(vm, #name).unwrap_or_else(|| {
+                                    panic!("Expected a record with the field `{}`", #name)
+                                }),
+                            )
+                        }
+                    })
+                    .collect();
+                let construct = construct(quote!(#ident), &data.fields, exprs);
+                quote! {
+                    match value.as_ref() {
+                        _gluon::vm::api::ValueRef::Data(data) => #construct,
+                        _ => panic!("ValueRef is not a record"),
+                    }
+                }
+            }
+        },
+        Data::Enum(ref data) => {
+            let arms = data.variants.iter().enumerate().map(|(tag, variant)| {
+                let variant_ident = variant.ident;
+                let exprs = (0..variant.fields.iter().count())
+                    .map(|i| {
+                        quote! {
+                            _gluon::vm::api::Getable::from_value(
+                                vm,
+                                data.get_variants(#i).unwrap(),
+                            )
+                        }
+                    })
+                    .collect();
+                let construct = construct(quote!(#ident::#variant_ident), &variant.fields, exprs);
+                let tag = Index::from(tag);
+                quote!(#tag => #construct,)
+            });
+            quote! {
+                match value.as_ref() {
+                    _gluon::vm::api::ValueRef::Data(data) => match data.tag() {
+                        #(#arms)*
+                        tag => panic!("Unexpected tag {}", tag),
+                    },
+                    _ => panic!("ValueRef is not a variant"),
+                }
+            }
+        }
+        Data::Union(_) => panic!("Unions are not supported by `Getable` (found `{}`)", ident),
+    };
+
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let vm_generics = add_vm_lifetime(&generics);
+    let (impl_generics, _, _) = vm_generics.split_for_impl();
+    let where_clause = where_clause(&generics, |param| {
+        quote!(#param: _gluon::vm::api::Getable<'__vm>,)
+    });
+
+    let tokens = quote! {
+        #[automatically_derived]
+        impl #impl_generics _gluon::vm::api::Getable<'__vm> for #ident #ty_generics
+            #where_clause
+        {
+            #[allow(unused_variables)]
+            fn from_value(
+                vm: &'__vm _gluon::vm::thread::Thread,
+                value: _gluon::vm::Variants,
+            ) -> Self {
+                #from_value
+            }
+        }
+    };
+    wrap_impl("GETABLE", &ident, tokens)
+}
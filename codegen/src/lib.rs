@@ -0,0 +1,86 @@
+//! Custom derives for marshalling Rust types to and from gluon.
+//!
+//! Structs are marshalled as records, with tuple structs using the field names of tuples (`_0`,
+//! `_1`, ...), and enums as variants where each variant's tag is its position in the enum.
+//!
+//! ```rust,ignore
+//! #[macro_use]
+//! extern crate gluon_codegen;
+//! extern crate gluon;
+//!
+//! #[derive(VmType, Pushable, Getable)]
+//! struct Vec2 {
+//!     x: i32,
+//!     #[gluon(name = "y_coord")]
+//!     y: i32,
+//! }
+//!
+//! // Enums need to be bound to a type which is already declared in gluon
+//! #[derive(VmType, Pushable, Getable)]
+//! #[gluon(vm_type = "shapes.Shape")]
+//! enum Shape {
+//!     Circle(f64),
+//!     Rectangle { width: f64, height: f64 },
+//! }
+//! ```
+//!
+//! The following attributes are recognized
+//!
+//! * `#[gluon(vm_type = "module.Type")]` on a type makes `VmType` use the gluon type of that
+//!   name instead of a record type. The type must be declared in gluon or registered with
+//!   `Thread::register_type` before it is used.
+//! * `#[gluon(name = "name")]` on a field renames the field in gluon.
+//! * `#[gluon(clone)]` on a type which derives `Userdata` makes it possible to deep clone it
+//!   (for instance when it is sent to another thread) by cloning it.
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+
+use syn::DeriveInput;
+
+mod attr;
+mod getable;
+mod pushable;
+mod shared;
+mod traverseable;
+mod userdata;
+mod vm_type;
+
+fn parse(input: TokenStream) -> DeriveInput {
+    syn::parse(input).expect("Unable to parse the input of the derive")
+}
+
+#[doc(hidden)]
+#[proc_macro_derive(VmType, attributes(gluon))]
+pub fn vm_type(input: TokenStream) -> TokenStream {
+    vm_type::derive(parse(input)).into()
+}
+
+#[doc(hidden)]
+#[proc_macro_derive(Pushable, attributes(gluon))]
+pub fn pushable(input: TokenStream) -> TokenStream {
+    pushable::derive(parse(input)).into()
+}
+
+#[doc(hidden)]
+#[proc_macro_derive(Getable, attributes(gluon))]
+pub fn getable(input: TokenStream) -> TokenStream {
+    getable::derive(parse(input)).into()
+}
+
+#[doc(hidden)]
+#[proc_macro_derive(Userdata, attributes(gluon))]
+pub fn userdata(input: TokenStream) -> TokenStream {
+    userdata::derive(parse(input)).into()
+}
+
+#[doc(hidden)]
+#[proc_macro_derive(Traverseable, attributes(gluon))]
+pub fn traverseable(input: TokenStream) -> TokenStream {
+    traverseable::derive(parse(input)).into()
+}
@@ -0,0 +1,90 @@
+use proc_macro2::TokenStream;
+use syn::{Data, DeriveInput, Fields, Ident, Index};
+
+use shared::{add_vm_lifetime, destructure, field_bindings, field_names, where_clause, wrap_impl};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    let DeriveInput {
+        ident,
+        generics,
+        data,
+        ..
+    } = input;
+
+    let push = match data {
+        Data::Struct(ref data) => {
+            let bindings = field_bindings(&data.fields);
+            let pattern = destructure(quote!(#ident), &data.fields, &bindings, false);
+            let push_fields = push_fields(&bindings);
+            let alloc = match data.fields {
+                Fields::Unit => quote!(_gluon::vm::api::Pushable::push((), thread, context)?;),
+                _ => {
+                    let names = field_names(&data.fields);
+                    quote!(context.push_new_record(thread, &[#(#names),*])?;)
+                }
+            };
+            quote! {
+                let #pattern = self;
+                #push_fields
+                #alloc
+            }
+        }
+        Data::Enum(ref data) => {
+            let arms = data.variants.iter().enumerate().map(|(tag, variant)| {
+                let variant_ident = variant.ident;
+                let bindings = field_bindings(&variant.fields);
+                let pattern = destructure(
+                    quote!(#ident::#variant_ident),
+                    &variant.fields,
+                    &bindings,
+                    false,
+                );
+                let push_fields = push_fields(&bindings);
+                let tag = Index::from(tag);
+                let len = Index::from(bindings.len());
+                quote! {
+                    #pattern => {
+                        #push_fields
+                        context.push_new_data(thread, #tag, #len)?;
+                    }
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => panic!("Unions are not supported by `Pushable` (found `{}`)", ident),
+    };
+
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let vm_generics = add_vm_lifetime(&generics);
+    let (impl_generics, _, _) = vm_generics.split_for_impl();
+    let where_clause = where_clause(&generics, |param| {
+        quote!(#param: _gluon::vm::api::Pushable<'__vm>,)
+    });
+
+    let tokens = quote! {
+        #[automatically_derived]
+        impl #impl_generics _gluon::vm::api::Pushable<'__vm> for #ident #ty_generics
+            #where_clause
+        {
+            fn push(
+                self,
+                thread: &'__vm _gluon::vm::thread::Thread,
+                context: &mut _gluon::vm::thread::Context,
+            ) -> _gluon::vm::Result<()> {
+                #push
+                Ok(())
+            }
+        }
+    };
+    wrap_impl("PUSHABLE", &ident, tokens)
+}
+
+fn push_fields(bindings: &[Ident]) -> TokenStream {
+    quote! {
+        #(_gluon::vm::api::Pushable::push(#bindings, thread, context)?;)*
+    }
+}
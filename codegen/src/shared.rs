@@ -0,0 +1,113 @@
+use proc_macro2::{Span, TokenStream};
+use syn::{Fields, GenericParam, Generics, Ident};
+
+use attr;
+
+/// Wraps `tokens` in an anonymous constant which brings the `gluon` crate into scope as `_gluon`,
+/// letting the generated code refer to it regardless of how the deriving crate imports it
+pub fn wrap_impl(trait_name: &str, ident: &Ident, tokens: TokenStream) -> TokenStream {
+    let dummy_const = Ident::new(
+        &format!("_IMPL_{}_FOR_{}", trait_name, ident),
+        Span::call_site(),
+    );
+    quote! {
+        #[allow(non_upper_case_globals)]
+        const #dummy_const: () = {
+            extern crate gluon as _gluon;
+            #tokens
+        };
+    }
+}
+
+/// Returns `generics` with the `'__vm` lifetime used by `Pushable` and `Getable` added
+pub fn add_vm_lifetime(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    let lifetime: GenericParam = parse_quote!('__vm);
+    generics.params.insert(0, lifetime);
+    generics
+}
+
+/// Returns a `where` clause containing `bounds` applied to each type parameter of `generics`
+/// followed by the predicates of the `where` clause of `generics`
+pub fn where_clause<F>(generics: &Generics, bounds: F) -> TokenStream
+where
+    F: FnMut(&Ident) -> TokenStream,
+{
+    let bounds: Vec<_> = generics
+        .type_params()
+        .map(|param| &param.ident)
+        .map(bounds)
+        .collect();
+    let predicates = generics
+        .where_clause
+        .iter()
+        .flat_map(|where_clause| where_clause.predicates.iter());
+    quote!(where #(#bounds)* #(#predicates,)*)
+}
+
+/// The type parameters of `generics`
+pub fn type_params(generics: &Generics) -> Vec<Ident> {
+    generics.type_params().map(|param| param.ident).collect()
+}
+
+/// Returns the names that the fields are known as in gluon. Unnamed fields are named like the
+/// fields of tuples (`_0`, `_1`, ...).
+pub fn field_names(fields: &Fields) -> Vec<String> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let attrs = attr::Field::from_ast(&field.attrs);
+            match (attrs.name, field.ident) {
+                (Some(name), _) => name,
+                (None, Some(ident)) => ident.to_string(),
+                (None, None) => format!("_{}", i),
+            }
+        })
+        .collect()
+}
+
+/// Returns a variable name to bind each field to when destructuring
+pub fn field_bindings(fields: &Fields) -> Vec<Ident> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match field.ident {
+            Some(ident) => ident,
+            None => Ident::new(&format!("_{}", i), Span::call_site()),
+        })
+        .collect()
+}
+
+/// Creates a pattern which binds each field of `path` to the names in `bindings`. Bindings are
+/// made by reference if `by_ref` is set.
+pub fn destructure(path: TokenStream, fields: &Fields, bindings: &[Ident], by_ref: bool) -> TokenStream {
+    let bindings = bindings.iter().map(|binding| {
+        if by_ref {
+            quote!(ref #binding)
+        } else {
+            quote!(#binding)
+        }
+    });
+    match *fields {
+        Fields::Named(ref named) => {
+            let idents = named.named.iter().map(|field| field.ident);
+            quote!(#path { #(#idents: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(#path( #(#bindings),* )),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+/// Creates an expression constructing `path` where each field is initialized with the matching
+/// expression in `exprs`
+pub fn construct(path: TokenStream, fields: &Fields, exprs: Vec<TokenStream>) -> TokenStream {
+    match *fields {
+        Fields::Named(ref named) => {
+            let idents = named.named.iter().map(|field| field.ident);
+            quote!(#path { #(#idents: #exprs),* })
+        }
+        Fields::Unnamed(_) => quote!(#path( #(#exprs),* )),
+        Fields::Unit => quote!(#path),
+    }
+}
@@ -0,0 +1,55 @@
+use proc_macro2::TokenStream;
+use syn::{Data, DeriveInput, Fields};
+
+use shared::{destructure, field_bindings, where_clause, wrap_impl};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    let DeriveInput {
+        ident,
+        generics,
+        data,
+        ..
+    } = input;
+
+    let traverse_fields = |path: TokenStream, fields: &Fields| {
+        let bindings = field_bindings(fields);
+        let pattern = destructure(path, fields, &bindings, true);
+        quote! {
+            #pattern => {
+                #(_gluon::vm::gc::Traverseable::traverse(#bindings, gc);)*
+            }
+        }
+    };
+    let arms = match data {
+        Data::Struct(ref data) => vec![traverse_fields(quote!(#ident), &data.fields)],
+        Data::Enum(ref data) => data.variants
+            .iter()
+            .map(|variant| {
+                let variant_ident = variant.ident;
+                traverse_fields(quote!(#ident::#variant_ident), &variant.fields)
+            })
+            .collect(),
+        Data::Union(_) => panic!(
+            "Unions are not supported by `Traverseable` (found `{}`)",
+            ident
+        ),
+    };
+
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = where_clause(&generics, |param| {
+        quote!(#param: _gluon::vm::gc::Traverseable,)
+    });
+
+    let tokens = quote! {
+        #[automatically_derived]
+        impl #impl_generics _gluon::vm::gc::Traverseable for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn traverse(&self, gc: &mut _gluon::vm::gc::Gc) {
+                match *self {
+                    #(#arms)*
+                }
+            }
+        }
+    };
+    wrap_impl("TRAVERSEABLE", &ident, tokens)
+}
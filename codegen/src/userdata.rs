@@ -0,0 +1,43 @@
+use proc_macro2::TokenStream;
+use syn::DeriveInput;
+
+use attr::Container;
+use shared::{where_clause, wrap_impl};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    let DeriveInput {
+        ident,
+        generics,
+        attrs,
+        ..
+    } = input;
+
+    let container = Container::from_ast(&attrs);
+
+    let deep_clone = if container.clone {
+        quote! {
+            fn deep_clone(
+                &self,
+                deep_cloner: &mut _gluon::vm::internal::Cloner,
+            ) -> _gluon::vm::Result<_gluon::vm::gc::GcPtr<Box<_gluon::vm::api::Userdata>>> {
+                let data: Box<_gluon::vm::api::Userdata> = Box::new(self.clone());
+                deep_cloner.gc().alloc(_gluon::vm::api::MoveUserdata(data))
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = where_clause(&generics, |param| {
+        quote!(#param: Send + Sync + 'static,)
+    });
+
+    let tokens = quote! {
+        #[automatically_derived]
+        impl #impl_generics _gluon::vm::api::Userdata for #ident #ty_generics #where_clause {
+            #deep_clone
+        }
+    };
+    wrap_impl("USERDATA", &ident, tokens)
+}
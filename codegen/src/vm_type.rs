@@ -0,0 +1,99 @@
+use proc_macro2::TokenStream;
+use syn::{Data, DeriveInput, Fields};
+
+use attr::Container;
+use shared::{field_names, type_params, where_clause, wrap_impl};
+
+pub fn derive(input: DeriveInput) -> TokenStream {
+    let DeriveInput {
+        ident,
+        generics,
+        attrs,
+        data,
+        ..
+    } = input;
+
+    let container = Container::from_ast(&attrs);
+
+    let make_type = match container.vm_type {
+        Some(ref vm_type) => bind_existing_type(vm_type, &type_params(&generics)),
+        None => match data {
+            Data::Struct(ref data) => record_type(&data.fields),
+            Data::Enum(_) => panic!(
+                "Enums must be bound to a gluon type with `#[gluon(vm_type = \"...\")]` to \
+                 derive `VmType` (found `{}`)",
+                ident
+            ),
+            Data::Union(_) => panic!("Unions are not supported by `VmType` (found `{}`)", ident),
+        },
+    };
+
+    let params = type_params(&generics);
+    let associated_type = if params.is_empty() {
+        quote!(Self)
+    } else {
+        quote!(#ident<#(<#params as _gluon::vm::api::VmType>::Type),*>)
+    };
+
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_clause = where_clause(&generics, |param| {
+        quote! {
+            #param: _gluon::vm::api::VmType,
+            #param::Type: Sized,
+        }
+    });
+
+    let tokens = quote! {
+        #[automatically_derived]
+        impl #impl_generics _gluon::vm::api::VmType for #ident #ty_generics #where_clause {
+            type Type = #associated_type;
+
+            fn make_type(vm: &_gluon::vm::thread::Thread) -> _gluon::base::types::ArcType {
+                #make_type
+            }
+        }
+    };
+    wrap_impl("VM_TYPE", &ident, tokens)
+}
+
+/// Looks up `vm_type` which must already be known to the virtual machine, applying it to the
+/// types of `params`
+fn bind_existing_type(vm_type: &str, params: &[::syn::Ident]) -> TokenStream {
+    let alias = quote! {
+        vm.find_type_info(#vm_type)
+            .unwrap_or_else(|_| panic!("Expected type `{}` to be registered", #vm_type))
+            .into_type()
+    };
+    if params.is_empty() {
+        alias
+    } else {
+        quote! {
+            _gluon::base::types::Type::app(
+                #alias,
+                vec![#(<#params as _gluon::vm::api::VmType>::make_type(vm)),*]
+                    .into_iter()
+                    .collect(),
+            )
+        }
+    }
+}
+
+fn record_type(fields: &Fields) -> TokenStream {
+    if let Fields::Unit = *fields {
+        return quote!(<() as _gluon::vm::api::VmType>::make_type(vm));
+    }
+    let names = field_names(fields);
+    let types = fields.iter().map(|field| &field.ty);
+    quote! {
+        let type_cache = vm.global_env().type_cache();
+        type_cache.record(
+            Vec::new(),
+            vec![#(
+                _gluon::base::types::Field::new(
+                    _gluon::base::symbol::Symbol::from(#names),
+                    <#types as _gluon::vm::api::VmType>::make_type(vm),
+                )
+            ),*],
+        )
+    }
+}
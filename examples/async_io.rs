@@ -0,0 +1,126 @@
+//! This example shows how asynchronous Rust functions can be exposed to gluon as `IO` actions by
+//! returning `AsyncIO`. The futures run on the `tokio_core` event loop which is passed to the
+//! virtual machine, letting gluon wait on timers and talk to a local TCP echo server without
+//! blocking the thread.
+
+extern crate env_logger;
+extern crate futures;
+extern crate gluon;
+#[macro_use]
+extern crate gluon_vm as vm;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::error::Error as StdError;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::{Future, IntoFuture, Stream};
+
+use tokio_core::net::{TcpListener, TcpStream};
+use tokio_core::reactor::{Core, Handle, Timeout};
+use tokio_io::AsyncRead;
+
+use vm::{ExternModule, Result as VmResult};
+use vm::api::{AsyncIO, IO};
+use vm::thread::Thread;
+use vm::types::VmInt;
+
+use gluon::import::add_extern_module;
+use gluon::{Compiler, VmBuilder};
+
+/// Waits `ms` milliseconds before returning
+fn sleep(ms: VmInt) -> AsyncIO<()> {
+    AsyncIO::with_handle(move |handle| {
+        Timeout::new(Duration::from_millis(ms as u64), handle)
+            .into_future()
+            .flatten()
+    })
+}
+
+/// Sends `msg` to the echo server listening on `port` and returns its response
+fn echo(port: VmInt, msg: String) -> AsyncIO<String> {
+    AsyncIO::with_handle(move |handle| {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port as u16));
+        TcpStream::connect(&addr, handle)
+            .and_then(move |stream| tokio_io::io::write_all(stream, msg.into_bytes()))
+            .and_then(|(stream, msg)| tokio_io::io::read_exact(stream, vec![0; msg.len()]))
+            .and_then(|(_, response)| {
+                String::from_utf8(response)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            })
+    })
+}
+
+fn load(thread: &Thread) -> VmResult<ExternModule> {
+    ExternModule::new(
+        thread,
+        record! {
+            sleep => primitive!(1 sleep),
+            echo => primitive!(2 echo)
+        },
+    )
+}
+
+/// Starts a server which writes back everything it receives, returning the port it listens on
+fn start_echo_server(handle: &Handle) -> io::Result<u16> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+    let listener = TcpListener::bind(&addr, handle)?;
+    let port = listener.local_addr()?.port();
+
+    let handle2 = handle.clone();
+    let server = listener.incoming().for_each(move |(socket, _)| {
+        let (reader, writer) = socket.split();
+        handle2.spawn(
+            tokio_io::io::copy(reader, writer)
+                .map(|_| ())
+                .map_err(|_| ()),
+        );
+        Ok(())
+    });
+    handle.spawn(server.map_err(|err| panic!("{}", err)));
+    Ok(port)
+}
+
+fn main() {
+    if let Err(err) = main_() {
+        panic!("{}", err)
+    }
+}
+
+fn main_() -> Result<(), Box<StdError>> {
+    let _ = env_logger::init();
+
+    let mut core = Core::new()?;
+    let port = start_echo_server(&core.handle())?;
+
+    let thread = VmBuilder::new().event_loop(Some(core.remote())).build();
+    add_extern_module(&thread, "async_io", load);
+
+    let expr = format!(
+        r#"
+        let io@{{ applicative = {{ wrap }}, monad = {{ flat_map }} }} = import! std.io
+        let string = import! std.string
+        let {{ sleep, echo }} = import! async_io
+
+        do _ = io.println "Sleeping for 100ms"
+        do _ = sleep 100
+        do response = echo {} "Hello from gluon!"
+        do _ = io.println (string.append "The server answered: " response)
+        wrap response
+        "#,
+        port
+    );
+
+    let (result, _) = core.run(Compiler::new().run_io(true).run_expr_async::<IO<String>>(
+        &thread,
+        "async_io",
+        &expr,
+    ))?;
+    match result {
+        IO::Value(response) => assert_eq!(response, "Hello from gluon!"),
+        IO::Exception(err) => return Err(err.into()),
+    }
+    Ok(())
+}
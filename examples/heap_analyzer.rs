@@ -0,0 +1,83 @@
+//! Reports what retains memory in a heap snapshot written by `HeapSnapshot::write`.
+//!
+//! ```text
+//! heap_analyzer <snapshot>
+//! heap_analyzer <old snapshot> <new snapshot>
+//! ```
+//!
+//! Given a single snapshot the roots and objects retaining the most memory are printed. Given two
+//! snapshots the retainers of the newer snapshot are printed along with how the memory used by
+//! each type changed between them.
+extern crate gluon;
+
+use std::env;
+use std::error::Error as StdError;
+use std::fs::File;
+use std::io::BufReader;
+
+use gluon::vm::heap_snapshot::{HeapSnapshot, Retainer};
+
+const TOP_RETAINERS: usize = 20;
+
+fn read_snapshot(path: &str) -> Result<HeapSnapshot, Box<StdError>> {
+    let file = File::open(path)?;
+    Ok(HeapSnapshot::read(BufReader::new(file))?)
+}
+
+fn print_retainers(snapshot: &HeapSnapshot) {
+    println!(
+        "{} objects, {} bytes",
+        snapshot.objects.len(),
+        snapshot.total_size()
+    );
+    println!("Top retainers:");
+    for retained in snapshot.top_retainers(TOP_RETAINERS) {
+        let path = match retained.retainer {
+            Retainer::Root(_) => String::new(),
+            Retainer::Object(id) => {
+                let names: Vec<_> = snapshot
+                    .retention_path(id)
+                    .into_iter()
+                    .map(|retainer| snapshot.name(retainer).to_string())
+                    .collect();
+                format!(" ({})", names.join(" -> "))
+            }
+        };
+        println!(
+            "{:>12} bytes {:>8} objects  {}{}",
+            retained.size,
+            retained.objects,
+            retained.name,
+            path
+        );
+    }
+}
+
+fn main() {
+    if let Err(err) = main_() {
+        panic!("{}", err)
+    }
+}
+
+fn main_() -> Result<(), Box<StdError>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.len() {
+        1 => print_retainers(&read_snapshot(&args[0])?),
+        2 => {
+            let old = read_snapshot(&args[0])?;
+            let new = read_snapshot(&args[1])?;
+            print_retainers(&new);
+            println!("Changes since `{}`:", args[0]);
+            for diff in old.diff(&new) {
+                println!(
+                    "{:>+12} bytes {:>+8} objects  {}",
+                    diff.size,
+                    diff.count,
+                    diff.type_name
+                );
+            }
+        }
+        _ => return Err("Usage: heap_analyzer <snapshot> [<newer snapshot>]".into()),
+    }
+    Ok(())
+}
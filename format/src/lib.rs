@@ -7,6 +7,8 @@ extern crate pretty;
 extern crate gluon_base as base;
 extern crate gluon_parser as parser;
 
+use base::ast::SpannedExpr;
+
 mod pretty_print;
 
 pub fn format_expr(input: &str) -> Result<String, parser::ParseErrors> {
@@ -35,3 +37,17 @@ pub fn format_expr(input: &str) -> Result<String, parser::ParseErrors> {
     let printer = pretty_print::Printer::new(&arena, &source);
     Ok(printer.format(100, newline, &expr))
 }
+
+/// Pretty prints `expr` which must have been parsed from `input`. Unlike `format_expr` this
+/// accepts expressions which have been macro expanded and renamed.
+pub fn pretty_expr<Id>(input: &str, expr: &SpannedExpr<Id>) -> String
+where
+    Id: AsRef<str>,
+{
+    use base::source::Source;
+
+    let source = Source::new(input);
+    let arena = pretty::Arena::new();
+    let printer = pretty_print::Printer::new(&arena, &source);
+    printer.format(100, "\n", expr)
+}
@@ -1,4 +1,4 @@
-use base::ast::{is_operator_char, Comment, CommentType};
+use base::ast::{is_operator_char, Comment, CommentType, FixedInt};
 use base::pos::{self, BytePos, Column, Line, Location, Spanned};
 use std::fmt;
 use std::str::Chars;
@@ -15,6 +15,7 @@ pub enum Token<'input> {
     CharLiteral(char),
     IntLiteral(i64),
     ByteLiteral(u8),
+    FixedIntLiteral(FixedInt),
     FloatLiteral(f64),
     DocComment(Comment),
 
@@ -66,6 +67,7 @@ impl<'input> fmt::Display for Token<'input> {
             CharLiteral(_) => "CharLiteral",
             IntLiteral(_) => "IntLiteral",
             ByteLiteral(_) => "ByteLiteral",
+            FixedIntLiteral(_) => "FixedIntLiteral",
             FloatLiteral(_) => "FloatLiteral",
             DocComment { .. } => "DocComment",
 
@@ -473,7 +475,19 @@ impl<'input> Tokenizer<'input> {
                     },
                 }
             }
-            Some((start, ch)) if is_ident_start(ch) => return self.error(start, UnexpectedChar(ch)),
+            Some((suffix_start, ch)) if is_ident_start(ch) => {
+                let (end, suffix) = self.take_while(suffix_start, is_ident_continue);
+                let literal = match suffix {
+                    "i32" => int.parse().map(FixedInt::I32),
+                    "u64" => int.parse().map(FixedInt::U64),
+                    "w" => int.parse().map(FixedInt::Word),
+                    _ => return self.error(suffix_start, UnexpectedChar(ch)),
+                };
+                match literal {
+                    Ok(literal) => (start, end, Token::FixedIntLiteral(literal)),
+                    Err(_) => return self.error(start, NonParseableInt),
+                }
+            }
             None | Some(_) => if let Ok(val) = int.parse() {
                 (start, end, Token::IntLiteral(val))
             } else {
@@ -952,6 +966,38 @@ mod test {
         );
     }
 
+    #[test]
+    fn fixed_int_literals() {
+        test(
+            r#"3i32 -1i32 18446744073709551615u64 8w"#,
+            vec![
+                (r#"~~~~                                 "#, FixedIntLiteral(FixedInt::I32(3))),
+                (r#"     ~~~~~                           "#, FixedIntLiteral(FixedInt::I32(-1))),
+                (
+                    r#"           ~~~~~~~~~~~~~~~~~~~~~~~   "#,
+                    FixedIntLiteral(FixedInt::U64(::std::u64::MAX)),
+                ),
+                (r#"                                   ~~"#, FixedIntLiteral(FixedInt::Word(8))),
+            ],
+        );
+    }
+
+    #[test]
+    fn fixed_int_literals_errors() {
+        assert_eq!(
+            tokenizer(r#"1i33"#).last(),
+            Some(error(loc(1), UnexpectedChar('i')))
+        );
+        assert_eq!(
+            tokenizer(r#"-1u64"#).last(),
+            Some(error(loc(0), NonParseableInt))
+        );
+        assert_eq!(
+            tokenizer(r#"2147483648i32"#).last(),
+            Some(error(loc(0), NonParseableInt))
+        );
+    }
+
     #[test]
     fn float_literals() {
         test(
@@ -19,6 +19,7 @@ extern crate gluon_format;
 #[macro_use]
 extern crate gluon_vm;
 
+use std::env;
 use std::io::{self, Write};
 use std::ffi::OsStr;
 use std::path::Path;
@@ -31,12 +32,49 @@ use gluon::vm;
 
 use base::error::InFile;
 
-use gluon::{new_vm, Compiler, Error, Result, Thread};
+use gluon::{load_manifest, new_vm, Compiler, Error, Result, Thread};
 use gluon::vm::thread::ThreadInternal;
 use gluon::vm::Error as VMError;
 
 mod repl;
 
+fn emit_file(vm: &Thread, emit: &str, file: &str) -> Result<()> {
+    use std::fs::File;
+    use std::io::Read;
+
+    use gluon::compiler_pipeline::{Compileable, Typecheckable};
+    use gluon::filename_to_module;
+    use gluon::vm::disassembler::disassemble_module;
+
+    let mut expr_str = String::new();
+    File::open(file)?.read_to_string(&mut expr_str)?;
+
+    let mut compiler = Compiler::new();
+    let name = filename_to_module(file);
+    let typechecked = expr_str
+        .as_str()
+        .typecheck(&mut compiler, vm, &name, &expr_str)?;
+
+    let output = match emit {
+        "ast" => gluon_format::pretty_expr(&expr_str, &typechecked.expr),
+        "core" => typechecked.optimized_core(vm),
+        "bytecode" => {
+            let compiled = typechecked.compile(&mut compiler, vm, &name, &expr_str, ())?;
+            disassemble_module(&compiled.module)
+        }
+        _ => {
+            return Err(VMError::Message(format!(
+                "Unknown --emit option `{}`, expected `ast`, `core` or `bytecode`",
+                emit
+            )).into())
+        }
+    };
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    writeln!(stdout, "{}", output)?;
+    Ok(())
+}
+
 fn run_files<'s, I>(vm: &Thread, files: I) -> Result<()>
 where
     I: Iterator<Item = &'s str>,
@@ -106,6 +144,9 @@ fn run() -> std::result::Result<(), Box<std::error::Error + Send + Sync>> {
         )
         (about: "executes gluon programs")
         (@arg REPL: -i --interactive "Starts the repl")
+        (@arg EMIT: --emit +takes_value
+            "Prints the renamed AST (`ast`), the optimized core language (`core`) or the bytecode \
+             (`bytecode`) of each file instead of executing it")
         (@subcommand fmt =>
             (about: "Formats gluon source code")
             (@arg INPUT: ... "Formats each file")
@@ -142,6 +183,18 @@ fn run() -> std::result::Result<(), Box<std::error::Error + Send + Sync>> {
         repl::run()?;
     } else if let Some(args) = matches.values_of("INPUT") {
         let vm = new_vm();
+        // Use the manifest of the package which the (first) input file is part of
+        let input_dir = matches
+            .value_of("INPUT")
+            .and_then(|input| Path::new(input).parent())
+            .unwrap_or(Path::new(""));
+        load_manifest(&vm, &env::current_dir()?.join(input_dir))?;
+        if let Some(emit) = matches.value_of("EMIT") {
+            for file in args {
+                emit_file(&vm, emit, file)?;
+            }
+            return Ok(());
+        }
         match run_files(&vm, args) {
             Ok(()) => (),
             Err(err @ Error::VM(VMError::Message(_))) => {
@@ -3,6 +3,7 @@ extern crate rustyline;
 
 extern crate gluon_completion as completion;
 
+use std::env;
 use std::error::Error as StdError;
 use std::sync::Mutex;
 
@@ -436,6 +437,8 @@ pub fn run() -> Result<(), Box<StdError + Send + Sync>> {
         .event_loop(Some(core.remote()))
         .build();
 
+    ::gluon::load_manifest(&vm, &env::current_dir()?)?;
+
     compile_repl(&vm)?;
 
     let mut repl: OwnedFunction<fn(()) -> IO<()>> = vm.get_global("repl")?;
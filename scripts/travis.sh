@@ -3,6 +3,7 @@
 (
   export RUST_BACKTRACE=1;
   cargo test --features test --all &&
+  cargo test -p gluon_c-api --features generate-header &&
   cargo check --benches --features test &&
   cargo check --all --no-default-features &&
   ([ "$TRAVIS_RUST_VERSION" != "nightly" ] || cargo test --features "test nightly" -p gluon compile_test)
@@ -147,6 +147,23 @@ pub struct TypecheckValue<E> {
     pub typ: ArcType,
 }
 
+impl<E> TypecheckValue<E>
+where
+    E: Borrow<SpannedExpr<Symbol>>,
+{
+    /// Translates the expression into the core language (`vm::core`) and optimizes it, returning
+    /// the pretty printed result. This is the representation that the bytecode compiler receives.
+    pub fn optimized_core(&self, thread: &Thread) -> String {
+        let env = thread.get_env();
+        let translator = core::Translator::new(&*env);
+        let expr = translator
+            .allocator
+            .arena
+            .alloc(translator.translate(self.expr.borrow()));
+        core::optimize::optimize(&translator.allocator, expr).to_string()
+    }
+}
+
 pub trait Typecheckable: Sized {
     type Expr: BorrowMut<SpannedExpr<Symbol>>;
 
@@ -227,6 +244,8 @@ where
             &*env,
             thread.global_env().type_cache().clone(),
         );
+        // Let timeouts and other interrupts abort typechecking
+        tc.set_interrupt(thread.interrupt_flag());
 
         let typ = tc.typecheck_expr_expected(self.expr.borrow_mut(), expected_type)
             .map_err(|err| InFile::new(file, expr_str, err))?;
@@ -3,15 +3,16 @@
 use std::any::Any;
 use std::borrow::Cow;
 use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::fs::File;
 use std::io;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
 use itertools::Itertools;
 
 use base::ast::{expr_to_path, Expr, Literal, SpannedExpr, Typed, TypedIdent};
-use base::fnv::FnvMap;
+use base::fnv::{FnvMap, FnvSet};
 use base::pos::{self, BytePos, Span};
 use base::symbol::Symbol;
 use base::types::ArcType;
@@ -21,7 +22,7 @@ use vm::{ExternLoader, ExternModule};
 use vm::macros::{Error as MacroError, Macro, MacroExpander};
 use vm::thread::{Thread, ThreadInternal};
 
-use super::{filename_to_module, Compiler};
+use super::{filename_to_module, Compiler, Error as GluonError};
 
 quick_error! {
     /// Error type for the import macro
@@ -63,6 +64,9 @@ static STD_LIBS: &[(&str, &str)] = &std_libs!(
     "bool",
     "float",
     "int",
+    "i32",
+    "u64",
+    "word",
     "char",
     "io",
     "list",
@@ -77,8 +81,16 @@ static STD_LIBS: &[(&str, &str)] = &std_libs!(
     "test",
     "unit",
     "writer",
-    "array"
+    "array",
+    "mutable_array",
+    "byte_buffer"
 );
+// Modules of the standard library which need an extern module that is only available when a
+// feature is enabled
+#[cfg(feature = "bigint")]
+static FEATURE_STD_LIBS: &[(&str, &str)] = &std_libs!("bigint");
+#[cfg(not(feature = "bigint"))]
+static FEATURE_STD_LIBS: &[(&str, &str)] = &[];
 
 pub trait Importer: Any + Clone + Sync + Send {
     fn import(
@@ -129,8 +141,178 @@ impl Importer for DefaultImporter {
     }
 }
 
+/// The source code of a module
+#[derive(Clone, Debug)]
+pub struct ModuleText {
+    pub source: Cow<'static, str>,
+    /// A stable path identifying where the module was loaded from. Parse and type errors in the
+    /// module name this path instead of the module
+    pub path: Option<PathBuf>,
+}
+
+impl ModuleText {
+    pub fn new<S>(source: S, path: Option<PathBuf>) -> ModuleText
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        ModuleText {
+            source: source.into(),
+            path,
+        }
+    }
+}
+
+/// Trait for the providers of source code which `import!` loads modules from
+pub trait ModuleSource: Send + Sync + 'static {
+    /// Returns the source code of `module` or `None` if this source does not contain it.
+    /// `filename` is the relative path of the module (`std/map.glu` for `std.map`).
+    fn read_module(&self, module: &str, filename: &str) -> Result<Option<ModuleText>, Error>;
+}
+
+impl<S> ModuleSource for Arc<S>
+where
+    S: ModuleSource,
+{
+    fn read_module(&self, module: &str, filename: &str) -> Result<Option<ModuleText>, Error> {
+        (**self).read_module(module, filename)
+    }
+}
+
+/// Module source which holds the source code of modules in memory
+#[derive(Default)]
+pub struct InMemorySource {
+    modules: RwLock<FnvMap<String, ModuleText>>,
+}
+
+impl InMemorySource {
+    pub fn new() -> InMemorySource {
+        InMemorySource::default()
+    }
+
+    /// Adds or replaces the source code of `module`
+    pub fn insert<S>(&self, module: &str, source: S)
+    where
+        S: Into<String>,
+    {
+        self.insert_text(module, ModuleText::new(source.into(), None));
+    }
+
+    pub fn insert_text(&self, module: &str, text: ModuleText) {
+        self.modules.write().unwrap().insert(module.into(), text);
+    }
+
+    pub fn remove(&self, module: &str) -> Option<ModuleText> {
+        self.modules.write().unwrap().remove(module)
+    }
+}
+
+impl ModuleSource for InMemorySource {
+    fn read_module(&self, module: &str, _filename: &str) -> Result<Option<ModuleText>, Error> {
+        Ok(self.modules.read().unwrap().get(module).cloned())
+    }
+}
+
+/// Module source for modules which are embedded in the binary, for instance by `include_str!`
+///
+/// ```
+/// use gluon::import::EmbeddedSource;
+///
+/// static MODULES: &[(&str, &str)] = &[("example.hello", r#" "Hello world" "#)];
+/// let source = EmbeddedSource::new(MODULES);
+/// ```
+#[derive(Clone, Copy)]
+pub struct EmbeddedSource {
+    modules: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedSource {
+    /// Creates a source from pairs of module names and source code
+    pub fn new(modules: &'static [(&'static str, &'static str)]) -> EmbeddedSource {
+        EmbeddedSource { modules }
+    }
+}
+
+impl ModuleSource for EmbeddedSource {
+    fn read_module(&self, module: &str, _filename: &str) -> Result<Option<ModuleText>, Error> {
+        Ok(self.modules
+            .iter()
+            .find(|&&(name, _)| name == module)
+            .map(|&(_, source)| ModuleText::new(source, None)))
+    }
+}
+
+/// Module source which reads modules from the files in a directory
+#[derive(Clone, Debug)]
+pub struct DirectorySource {
+    root: PathBuf,
+    restrict: bool,
+    prefix: Option<String>,
+}
+
+impl DirectorySource {
+    pub fn new<P>(root: P) -> DirectorySource
+    where
+        P: Into<PathBuf>,
+    {
+        DirectorySource {
+            root: root.into(),
+            restrict: false,
+            prefix: None,
+        }
+    }
+
+    /// Only loads modules whose names start with `prefix`, with the prefix stripped from the path
+    /// of the file. With the prefix `mylib`, `mylib.util` is loaded from `util.glu` and `mylib`
+    /// itself from `lib.glu`.
+    pub fn prefix<S>(mut self, prefix: S) -> DirectorySource
+    where
+        S: Into<String>,
+    {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// If `restrict` is `true` files which are not inside the directory once `..` and symlinks
+    /// have been resolved are refused
+    pub fn restrict(mut self, restrict: bool) -> DirectorySource {
+        self.restrict = restrict;
+        self
+    }
+}
+
+impl ModuleSource for DirectorySource {
+    fn read_module(&self, module: &str, filename: &str) -> Result<Option<ModuleText>, Error> {
+        let path = match self.prefix {
+            Some(ref prefix) => if module == prefix {
+                self.root.join("lib.glu")
+            } else if module.starts_with(prefix) && module[prefix.len()..].starts_with('.') {
+                self.root.join(&filename[prefix.len() + 1..])
+            } else {
+                return Ok(None);
+            },
+            None => self.root.join(filename),
+        };
+        if self.restrict && !is_inside(&self.root, &path) {
+            if path.exists() {
+                return Err(Error::String(format!(
+                    "Module '{}' refers to a path outside of the import paths",
+                    module
+                )));
+            }
+            return Ok(None);
+        }
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer)?;
+        Ok(Some(ModuleText::new(buffer, Some(path))))
+    }
+}
+
 enum UnloadedModule {
-    Source(Cow<'static, str>),
+    Source(ModuleText),
     Extern(ExternModule),
 }
 
@@ -141,6 +323,16 @@ pub struct Import<I = DefaultImporter> {
     pub loaders: RwLock<FnvMap<String, ExternLoader>>,
     pub importer: I,
 
+    /// The extern modules which may be imported. All extern modules may be imported if this is
+    /// `None`
+    allowed_modules: RwLock<Option<FnvSet<String>>>,
+    /// If set, files are only loaded if they are inside one of `paths`
+    restrict_paths: AtomicBool,
+    /// Sources which modules are loaded from before looking in `paths`
+    sources: RwLock<Vec<Box<ModuleSource>>>,
+    /// The paths that the loaded modules were read from
+    module_paths: RwLock<FnvMap<String, PathBuf>>,
+
     /// Map of modules currently being loaded
     loading: Mutex<FnvMap<String, Arc<Mutex<()>>>>,
 }
@@ -152,15 +344,78 @@ impl<I> Import<I> {
             paths: RwLock::new(vec![PathBuf::from(".")]),
             loaders: RwLock::default(),
             importer: importer,
+            allowed_modules: RwLock::default(),
+            restrict_paths: AtomicBool::new(false),
+            sources: RwLock::default(),
+            module_paths: RwLock::default(),
             loading: Mutex::default(),
         }
     }
 
+    /// Restricts which extern modules may be imported to the modules in `modules`. Passing `None`
+    /// lets every extern module be imported.
+    ///
+    /// The modules of the standard library which are written in gluon may always be imported as
+    /// they can only interact with the world outside of the virtual machine through extern
+    /// modules.
+    pub fn set_allowed_modules(&self, modules: Option<FnvSet<String>>) {
+        *self.allowed_modules.write().unwrap() = modules;
+    }
+
+    /// Allows `module` to be imported even if only some extern modules are allowed
+    pub fn allow_module(&self, module: &str) {
+        if let Some(ref mut allowed) = *self.allowed_modules.write().unwrap() {
+            allowed.insert(module.into());
+        }
+    }
+
+    /// Returns whether the extern module `module` may be imported
+    pub fn is_allowed(&self, module: &str) -> bool {
+        match *self.allowed_modules.read().unwrap() {
+            Some(ref allowed) => allowed.contains(module),
+            None => true,
+        }
+    }
+
+    /// Returns an error if `module` is an extern module which may not be imported. Checked before
+    /// looking at whether the module is already loaded, as the module may have been loaded by the
+    /// host or before the allowed modules were restricted.
+    fn check_allowed(&self, module: &str) -> Result<(), MacroError> {
+        if self.loaders.read().unwrap().contains_key(module) && !self.is_allowed(module) {
+            return Err(Error::String(format!(
+                "Module '{}' is not allowed to be imported",
+                module
+            )).into());
+        }
+        Ok(())
+    }
+
+    /// If `restrict` is `true`, files are only loaded if they are inside one of the import paths.
+    /// Files reached through `..`, absolute paths or symlinks pointing outside of the import paths
+    /// are refused.
+    pub fn restrict_paths(&self, restrict: bool) {
+        self.restrict_paths.store(restrict, Ordering::SeqCst);
+    }
+
     /// Adds a path to the list of paths which the importer uses to find files
     pub fn add_path<P: Into<PathBuf>>(&self, path: P) {
         self.paths.write().unwrap().push(path.into());
     }
 
+    /// Adds a source which modules are loaded from. Sources are searched in the order they were
+    /// added after the standard library and the extern modules but before the import paths.
+    pub fn add_source<S>(&self, source: S)
+    where
+        S: ModuleSource,
+    {
+        self.sources.write().unwrap().push(Box::new(source));
+    }
+
+    /// Returns the path that `module` was loaded from, if its source provided one
+    pub fn module_path(&self, module: &str) -> Option<PathBuf> {
+        self.module_paths.read().unwrap().get(module).cloned()
+    }
+
     pub fn add_loader(&self, module: &str, loader: ExternLoader) {
         self.loaders
             .write()
@@ -174,44 +429,46 @@ impl<I> Import<I> {
         module: &str,
         filename: &str,
     ) -> Result<UnloadedModule, MacroError> {
-        let mut buffer = String::new();
-
         // Retrieve the source, first looking in the standard library included in the
         // binary
-
-        let std_file = STD_LIBS.iter().find(|tup| tup.0 == module);
-        if let Some(tup) = std_file {
-            return Ok(UnloadedModule::Source(Cow::Borrowed(tup.1)));
+        for &std_libs in &[STD_LIBS, FEATURE_STD_LIBS] {
+            if let Some(text) = EmbeddedSource::new(std_libs).read_module(module, filename)? {
+                return Ok(UnloadedModule::Source(text));
+            }
         }
-        Ok(match std_file {
-            Some(tup) => UnloadedModule::Source(Cow::Borrowed(tup.1)),
-            None => {
-                {
-                    let loaders = self.loaders.read().unwrap();
-                    if let Some(loader) = loaders.get(module) {
-                        let value = loader(vm)?;
-                        return Ok(UnloadedModule::Extern(value));
-                    }
+        {
+            let loaders = self.loaders.read().unwrap();
+            if let Some(loader) = loaders.get(module) {
+                if !self.is_allowed(module) {
+                    return Err(Error::String(format!(
+                        "Module '{}' is not allowed to be imported",
+                        module
+                    )).into());
                 }
-                let file = self.paths
-                    .read()
-                    .unwrap()
-                    .iter()
-                    .filter_map(|p| {
-                        let base = p.join(filename);
-                        match File::open(&base) {
-                            Ok(file) => Some(file),
-                            Err(_) => None,
-                        }
-                    })
-                    .next();
-                let mut file = file.ok_or_else(|| {
-                    Error::String(format!("Could not find module '{}'", module))
-                })?;
-                file.read_to_string(&mut buffer)?;
-                UnloadedModule::Source(Cow::Owned(buffer))
+                let value = loader(vm)?;
+                return Ok(UnloadedModule::Extern(value));
             }
-        })
+        }
+        for source in self.sources.read().unwrap().iter() {
+            if let Some(text) = source.read_module(module, filename)? {
+                return Ok(UnloadedModule::Source(text));
+            }
+        }
+
+        let restrict_paths = self.restrict_paths.load(Ordering::SeqCst);
+        if restrict_paths && !is_relative_path(Path::new(filename)) {
+            return Err(Error::String(format!(
+                "Module '{}' refers to a path outside of the import paths",
+                module
+            )).into());
+        }
+        for path in self.paths.read().unwrap().iter() {
+            let source = DirectorySource::new(path.clone()).restrict(restrict_paths);
+            if let Some(text) = source.read_module(module, filename)? {
+                return Ok(UnloadedModule::Source(text));
+            }
+        }
+        Err(Error::String(format!("Could not find module '{}'", module)).into())
     }
 
     pub fn load_module(
@@ -227,6 +484,7 @@ impl<I> Import<I> {
     {
         assert!(module_id.is_global());
         let modulename = module_id.name().definition_name();
+        self.check_allowed(modulename).map_err(|err| (None, err))?;
         let mut filename = modulename.replace(".", "/");
         filename.push_str(".glu");
         {
@@ -297,7 +555,21 @@ impl<I> Import<I> {
                 vm.set_global(module_id.clone(), typ, metadata, *value)
                     .map_err(|err| (None, err.into()))?;
             }
-            UnloadedModule::Source(file_contents) => {
+            UnloadedModule::Source(ModuleText {
+                source: file_contents,
+                path,
+            }) => {
+                if let Some(ref path) = path {
+                    self.module_paths
+                        .write()
+                        .unwrap()
+                        .insert(modulename.to_string(), path.clone());
+                }
+                let in_source = |err: MacroError| match path {
+                    Some(ref path) => in_source_path(err, path),
+                    None => err,
+                };
+
                 // Modules marked as this would create a cyclic dependency if they included the implicit
                 // prelude
                 let implicit_prelude = !file_contents.starts_with("//@NO-IMPLICIT-PRELUDE");
@@ -307,29 +579,77 @@ impl<I> Import<I> {
                 let macro_result =
                     match file_contents.expand_macro_with(compiler, macros, &modulename) {
                         Ok(m) => m,
-                        Err((None, err)) => return Err((None, err.into())),
+                        Err((None, err)) => return Err((None, in_source(err.into()))),
                         Err((Some(m), err)) => {
-                            macros.errors.push(pos::spanned(span, err.into()));
+                            macros.errors.push(pos::spanned(span, in_source(err.into())));
                             m
                         }
                     };
 
                 let earlier_errors_exist = errors_before != macros.errors.len();
-                self.importer.import(
-                    compiler,
-                    vm,
-                    earlier_errors_exist,
-                    &modulename,
-                    &file_contents,
-                    macro_result.expr,
-                )?;
+                self.importer
+                    .import(
+                        compiler,
+                        vm,
+                        earlier_errors_exist,
+                        &modulename,
+                        &file_contents,
+                        macro_result.expr,
+                    )
+                    .map_err(|(typ, err)| (typ, in_source(err)))?;
             }
         }
         Ok(())
     }
 }
 
+/// Names `path`, which the module was read from, in the diagnostics of `err` instead of the name
+/// of the module
+fn in_source_path(err: MacroError, path: &Path) -> MacroError {
+    fn rename(err: GluonError, path: &Path) -> GluonError {
+        match err {
+            GluonError::Parse(mut err) => {
+                err.source_name = path.display().to_string();
+                GluonError::Parse(err)
+            }
+            GluonError::Typecheck(mut err) => {
+                err.source_name = path.display().to_string();
+                GluonError::Typecheck(err)
+            }
+            GluonError::Multiple(errors) => GluonError::Multiple(
+                errors
+                    .into_iter()
+                    .map(|err| rename(err, path))
+                    .collect(),
+            ),
+            err => err,
+        }
+    }
+    match err.downcast::<GluonError>() {
+        Ok(err) => Box::new(rename(*err, path)),
+        Err(err) => err,
+    }
+}
+
+/// Returns `true` if `path` only consists of normal components (no `..`, root or prefix)
+fn is_relative_path(path: &Path) -> bool {
+    path.components().all(|component| match component {
+        Component::Normal(_) | Component::CurDir => true,
+        _ => false,
+    })
+}
+
+/// Returns `true` if `path` is inside `root` after all symlinks have been resolved
+fn is_inside(root: &Path, path: &Path) -> bool {
+    match (root.canonicalize(), path.canonicalize()) {
+        (Ok(root), Ok(path)) => path.starts_with(root),
+        _ => false,
+    }
+}
+
 /// Adds an extern module to `thread`, letting it be loaded with `import! name` from gluon code.
+/// The module is allowed to be imported even if the virtual machine only allows some extern
+/// modules to be imported (see `VmBuilder::std_lib`).
 ///
 /// ```
 /// extern crate gluon;
@@ -383,6 +703,44 @@ pub fn add_extern_module(thread: &Thread, name: &str, loader: ExternLoader) {
             )
         });
     import.add_loader(name, loader);
+    import.allow_module(name);
+}
+
+/// Adds `source` to the sources which `import!` loads modules from (see `Import::add_source`).
+///
+/// ```
+/// extern crate gluon;
+///
+/// use gluon::Compiler;
+/// use gluon::import::{add_module_source, InMemorySource};
+///
+/// fn main() {
+///     let thread = gluon::new_vm();
+///     let source = InMemorySource::new();
+///     source.insert("greeting", r#" "Hello World!" "#);
+///     add_module_source(&thread, source);
+///
+///     let (result, _) = Compiler::new()
+///         .run_expr::<String>(&thread, "example", "import! greeting")
+///         .unwrap();
+///     assert_eq!(result, "Hello World!");
+/// }
+/// ```
+pub fn add_module_source<S>(thread: &Thread, source: S)
+where
+    S: ModuleSource,
+{
+    let opt_macro = thread.get_macros().get("import");
+    let import = opt_macro
+        .as_ref()
+        .and_then(|mac| mac.downcast_ref::<Import>())
+        .unwrap_or_else(|| {
+            ice!(
+                "Can't add a module source without a import macro. \
+                 Did you mean to create this `Thread` with `gluon::new_vm`"
+            )
+        });
+    import.add_source(source);
 }
 
 fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
@@ -470,6 +828,7 @@ where
 
         // Only load the script if it is not already loaded
         debug!("Import '{}' {:?}", modulename, get_state(macros).visited);
+        self.check_allowed(&modulename)?;
         if !vm.global_env().global_exists(&modulename) {
             if let Err((typ, err)) =
                 self.load_module(&mut Compiler::new(), vm, macros, &name, args[0].span)
@@ -35,7 +35,11 @@ fn println(s: &str) -> IO<()> {
 
 struct GluonFile(Mutex<File>);
 
-impl Userdata for GluonFile {}
+impl Userdata for GluonFile {
+    fn is_mutable(&self) -> bool {
+        false
+    }
+}
 
 impl fmt::Debug for GluonFile {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -222,22 +226,65 @@ mod std {
     }
 }
 
-pub fn load(vm: &Thread) -> Result<ExternModule> {
-    vm.register_type::<GluonFile>("File", &[])?;
+/// Replacements for the primitives which access the filesystem, stdin or the compiler. Used by
+/// `load_without_filesystem`.
+mod sandboxed {
+    use super::*;
+
+    fn denied(name: &str) -> String {
+        format!("`std.io.{}` is not available in this virtual machine", name)
+    }
+
+    pub fn open_file(_: &str) -> IO<GluonFile> {
+        IO::Exception(denied("open_file"))
+    }
+
+    pub fn read_file<'vm>(_: WithVM<'vm, &GluonFile>, _: usize) -> IO<Array<'vm, u8>> {
+        IO::Exception(denied("read_file"))
+    }
+
+    pub fn read_file_to_string(_: &str) -> IO<String> {
+        IO::Exception(denied("read_file_to_string"))
+    }
+
+    pub fn read_char() -> IO<char> {
+        IO::Exception(denied("read_char"))
+    }
+
+    pub fn read_line() -> IO<String> {
+        IO::Exception(denied("read_line"))
+    }
+
+    pub fn run_expr(_: WithVM<&str>) -> PrimitiveFuture<IO<String>> {
+        FutureValue::sync(Ok(IO::Exception(denied("run_expr")))).boxed()
+    }
+
+    pub fn load_script(_: WithVM<&str>, _: &str) -> PrimitiveFuture<IO<String>> {
+        FutureValue::sync(Ok(IO::Exception(denied("load_script")))).boxed()
+    }
+}
+
+type FlatMap = fn(fn(A) -> IO<B>, IO<A>) -> IO<B>;
+type Wrap = fn(A) -> IO<A>;
 
-    // flat_map f m : (a -> IO b) -> IO a -> IO b
-    //     = f (m ())
-    let flat_map = vec![
+// flat_map f m : (a -> IO b) -> IO a -> IO b
+//     = f (m ())
+fn flat_map() -> TypedBytecode<FlatMap> {
+    let instructions = vec![
         // [f, m, ()]       Initial stack
         Call(1),     // [f, m_ret]       Call m ()
         PushInt(0),  // [f, m_ret, ()]   Add a dummy argument ()
         TailCall(2), /* [f_ret]          Call f m_ret () */
     ];
+    TypedBytecode::new("std.io.prim.flat_map", 3, instructions)
+}
 
-    type FlatMap = fn(fn(A) -> IO<B>, IO<A>) -> IO<B>;
-    type Wrap = fn(A) -> IO<A>;
+fn wrap() -> TypedBytecode<Wrap> {
+    TypedBytecode::new("std.io.prim.wrap", 2, vec![Pop(1)])
+}
 
-    let wrap = vec![Pop(1)];
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    vm.register_type::<GluonFile>("File", &[])?;
 
     use self::std;
 
@@ -245,8 +292,8 @@ pub fn load(vm: &Thread) -> Result<ExternModule> {
     ExternModule::new(
         vm,
         record! {
-            flat_map => TypedBytecode::<FlatMap>::new("std.io.prim.flat_map", 3, flat_map),
-            wrap => TypedBytecode::<Wrap>::new("std.io.prim.wrap", 2, wrap),
+            flat_map => flat_map(),
+            wrap => wrap(),
             open_file => primitive!(1 std::io::prim::open_file),
             read_file => primitive!(2 std::io::prim::read_file),
             read_file_to_string => primitive!(1 std::io::prim::read_file_to_string),
@@ -260,3 +307,33 @@ pub fn load(vm: &Thread) -> Result<ExternModule> {
         },
     )
 }
+
+/// Loads `std.io.prim` where every function accessing the filesystem, stdin or the compiler
+/// raises an exception instead. The module has the same type as the one created by `load`.
+pub fn load_without_filesystem(vm: &Thread) -> Result<ExternModule> {
+    vm.register_type::<GluonFile>("File", &[])?;
+
+    use self::std;
+
+    ExternModule::new(
+        vm,
+        record! {
+            flat_map => flat_map(),
+            wrap => wrap(),
+            open_file => named_primitive!(1, "std.io.prim.open_file", sandboxed::open_file),
+            read_file => named_primitive!(2, "std.io.prim.read_file", sandboxed::read_file),
+            read_file_to_string => named_primitive!(
+                1,
+                "std.io.prim.read_file_to_string",
+                sandboxed::read_file_to_string
+            ),
+            read_char => named_primitive!(0, "std.io.prim.read_char", sandboxed::read_char),
+            read_line => named_primitive!(0, "std.io.prim.read_line", sandboxed::read_line),
+            print => primitive!(1 std::io::prim::print),
+            println => primitive!(1 std::io::prim::println),
+            catch => primitive!(2 std::io::prim::catch),
+            run_expr => named_primitive!(1, "std.io.prim.run_expr", sandboxed::run_expr),
+            load_script => named_primitive!(2, "std.io.prim.load_script", sandboxed::load_script),
+        },
+    )
+}
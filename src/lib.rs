@@ -16,6 +16,7 @@ extern crate log;
 #[macro_use]
 extern crate quick_error;
 extern crate tokio_core;
+extern crate toml;
 pub extern crate either;
 
 #[cfg(feature = "serde_derive_state")]
@@ -34,11 +35,15 @@ pub extern crate gluon_vm as vm;
 pub mod compiler_pipeline;
 pub mod import;
 pub mod io;
+pub mod manifest;
+pub mod reload;
 #[cfg(feature = "regex")]
 pub mod regex_bind;
 #[cfg(feature = "rand")]
 pub mod rand_bind;
 
+mod timeout;
+
 pub use vm::thread::{RootedThread, Thread};
 
 pub use futures::Future;
@@ -48,6 +53,8 @@ use either::Either;
 use std::result::Result as StdResult;
 use std::string::String as StdString;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use base::ast::{self, SpannedExpr};
 use base::error::{Errors, InFile};
@@ -64,6 +71,7 @@ use vm::thread::ThreadInternal;
 use vm::macros;
 use compiler_pipeline::*;
 use import::{add_extern_module, DefaultImporter, Import};
+use timeout::Deadline;
 
 quick_error! {
     /// Error type wrapping all possible errors that can be generated from gluon
@@ -99,6 +107,28 @@ quick_error! {
             display("{}", err)
             from()
         }
+        /// Error found when reading a package manifest
+        Manifest(err: manifest::Error) {
+            description(err.description())
+            display("{}", err)
+            from()
+        }
+        /// Compilation or execution did not finish before the timeout expired
+        Timeout(timeout: Duration) {
+            description("timeout expired")
+            display("Timed out after {:?}", timeout)
+        }
+        /// A reloaded module does not have a type compatible with the module it would replace
+        IncompatibleReload(module: StdString, expected: ArcType, actual: ArcType) {
+            description("reloaded module has an incompatible type")
+            display(
+                "Unable to reload `{}` as its type is incompatible with the loaded module\n\
+                 Expected: {}\nFound: {}",
+                module,
+                expected,
+                actual
+            )
+        }
         /// Multiple errors where found
         Multiple(err: Errors<Error>) {
             description(err.description())
@@ -396,6 +426,54 @@ impl Compiler {
         ).boxed()
     }
 
+    /// Recompiles the already loaded module `module` from `input` and replaces the loaded module
+    /// with the result.
+    ///
+    /// The type of the new module must be compatible with the type of the old module, otherwise
+    /// `Error::IncompatibleReload` is returned and the old module is left in place. The module is
+    /// swapped atomically so lookups of the module (such as `Thread::get_global`) see either the
+    /// old or the new module and calls which are already running finish using the old code. Modules
+    /// which imported the old module keep referring to it until they are reloaded themselves.
+    pub fn reload_script(&mut self, vm: &Thread, module: &str, input: &str) -> Result<()> {
+        use check::check_signature;
+
+        let expected = vm.get_env()
+            .globals
+            .get(module)
+            .map(|global| global.typ.clone())
+            .ok_or_else(|| format!("Module `{}` must be loaded before it can be reloaded", module))?;
+
+        let compiled = input.compile(self, vm, module, input, None)?;
+        if !check_signature(&*vm.get_env(), &expected, &compiled.typ) {
+            return Err(Error::IncompatibleReload(
+                module.into(),
+                expected,
+                compiled.typ,
+            ));
+        }
+        compiled.load_script(self, vm, module, input, ()).wait()?;
+        info!("Reloaded module `{}`", module);
+        Ok(())
+    }
+
+    /// Reads `filename` and reloads the module it was loaded as by `load_file`, see
+    /// `reload_script`
+    pub fn reload_file(&mut self, vm: &Thread, filename: &str) -> Result<()> {
+        use std::fs::File;
+        use std::io::Read;
+
+        let mut input = StdString::new();
+        File::open(filename)?.read_to_string(&mut input)?;
+
+        let module_name = filename_to_module(filename);
+        // Mimick the import macro which decides whether to include the implicit prelude
+        let implicit_prelude = self.implicit_prelude;
+        self.implicit_prelude = !input.starts_with("//@NO-IMPLICIT-PRELUDE");
+        let result = self.reload_script(vm, &module_name[1..], &input);
+        self.implicit_prelude = implicit_prelude;
+        result
+    }
+
     /// Compiles and runs the expression in `expr_str`. If successful the value from running the
     /// expression is returned
     ///
@@ -476,6 +554,66 @@ impl Compiler {
             .boxed()
     }
 
+    /// Compiles and runs the expression in `expr_str` like `run_expr` but aborts compilation and
+    /// execution with `Error::Timeout` if the expression has not been evaluated within `timeout`.
+    ///
+    /// After a timeout `vm` is reset so that it can be used to run other expressions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate gluon;
+    /// # use std::time::Duration;
+    /// # use gluon::{new_vm, Compiler, Error};
+    /// # fn main() {
+    /// let vm = new_vm();
+    /// let result = Compiler::new()
+    ///     .run_expr_with_timeout::<i32>(
+    ///         &vm,
+    ///         "example",
+    ///         "let loop x = loop (x + 1) in loop 0",
+    ///         Duration::from_millis(100),
+    ///     );
+    /// match result {
+    ///     Err(Error::Timeout(_)) => (),
+    ///     _ => panic!("Expected a timeout"),
+    /// }
+    /// # }
+    /// ```
+    ///
+    pub fn run_expr_with_timeout<'vm, T>(
+        &mut self,
+        vm: &'vm Thread,
+        name: &str,
+        expr_str: &str,
+        timeout: Duration,
+    ) -> Result<(T, ArcType)>
+    where
+        T: Getable<'vm> + VmType + Send + 'vm,
+    {
+        self.run_expr_async_with_timeout(vm, name, expr_str, timeout).wait()
+    }
+
+    /// Asynchronous version of `run_expr_with_timeout`.
+    ///
+    /// The timer is run on the event loop of `vm` if it has one, otherwise a separate OS thread
+    /// is used to wait for the timeout.
+    pub fn run_expr_async_with_timeout<'vm, T>(
+        &mut self,
+        vm: &'vm Thread,
+        name: &str,
+        expr_str: &str,
+        timeout: Duration,
+    ) -> BoxFutureValue<'vm, (T, ArcType), Error>
+    where
+        T: Getable<'vm> + VmType + Send + 'vm,
+    {
+        let deadline = Deadline::start(vm, timeout);
+        self.run_expr_async(vm, name, expr_str)
+            .then(move |result| FutureValue::sync(deadline.finish(result)))
+            .boxed()
+    }
+
     fn include_implicit_prelude(
         &mut self,
         type_cache: &TypeCache<Symbol, ArcType>,
@@ -562,9 +700,73 @@ pub fn filename_to_module(filename: &str) -> StdString {
     format!("@{}", name.replace(|c: char| c == '/' || c == '\\', "."))
 }
 
+/// The extern modules of the standard library which only compute values and do not interact with
+/// the world outside of the virtual machine
+const PURE_MODULES: &[&str] = &[
+    "std.prim",
+    "std.int.prim",
+    "std.i32.prim",
+    "std.u64.prim",
+    "std.word.prim",
+    "std.float.prim",
+    "std.string.prim",
+    "std.char.prim",
+    "std.array.prim",
+    "std.mutable_array.prim",
+    "std.byte_buffer.prim",
+    "std.lazy",
+    "std.reference",
+    "std.weak",
+    "std.bigint.prim",
+    "std.regex",
+];
+
+/// The extern modules which the implicit prelude imports. These are always allowed with
+/// `StdLib::Allowlist` so that scripts compiled with the implicit prelude can be run.
+const PRELUDE_MODULES: &[&str] = &[
+    "std.prim",
+    "std.int.prim",
+    "std.float.prim",
+    "std.string.prim",
+];
+
+/// The extern modules which are available in addition to `PURE_MODULES` when using
+/// `StdLib::NoFilesystem`
+const NO_FILESYSTEM_MODULES: &[&str] = &[
+    "std.io.prim",
+    "std.channel",
+    "std.thread.prim",
+    "std.debug",
+    "std.random.prim",
+];
+
+/// Selects which extern modules of the standard library scripts are allowed to import
+#[derive(Clone, Debug, PartialEq)]
+pub enum StdLib {
+    /// Every module may be imported
+    Full,
+    /// Only modules which do not interact with the world outside of the virtual machine may be
+    /// imported. Importing `std.io` fails.
+    Pure,
+    /// Like `Pure` but also allows `std.io`, threads, channels, `std.random` and `std.debug`. The
+    /// functions of `std.io` which access the filesystem, stdin or the compiler raise an exception.
+    NoFilesystem,
+    /// Only the listed extern modules may be imported, along with the ones needed by the implicit
+    /// prelude (`std.prim`, `std.int.prim`, `std.float.prim` and `std.string.prim`)
+    Allowlist(Vec<StdString>),
+}
+
+impl Default for StdLib {
+    fn default() -> StdLib {
+        StdLib::Full
+    }
+}
+
 #[derive(Default)]
 pub struct VmBuilder {
     event_loop: Option<::tokio_core::reactor::Remote>,
+    std_lib: StdLib,
+    import_paths: Option<Vec<PathBuf>>,
 }
 
 impl VmBuilder {
@@ -578,11 +780,34 @@ impl VmBuilder {
         event_loop set_event_loop: Option<::tokio_core::reactor::Remote>
     }
 
+    option!{
+        /// Selects which extern modules scripts may import. Any setting other than `StdLib::Full`
+        /// also prevents files outside of the import paths from being imported.
+        /// (default: StdLib::Full)
+        std_lib set_std_lib: StdLib
+    }
+
+    option!{
+        /// Sets the paths which modules are imported from and prevents files outside of them from
+        /// being imported. If `None` modules are imported from the current directory and
+        /// `GLUON_PATH`
+        /// (default: None)
+        import_paths set_import_paths: Option<Vec<PathBuf>>
+    }
+
     pub fn build(self) -> RootedThread {
         let vm = RootedThread::with_event_loop(self.event_loop);
-        let gluon_path = env::var("GLUON_PATH").unwrap_or_else(|_| String::from("."));
         let import = Import::new(DefaultImporter);
-        import.add_path(gluon_path);
+        match self.import_paths {
+            Some(ref paths) => {
+                *import.paths.write().unwrap() = paths.clone();
+            }
+            None => {
+                let gluon_path = env::var("GLUON_PATH").unwrap_or_else(|_| String::from("."));
+                import.add_path(gluon_path);
+            }
+        }
+        import.restrict_paths(self.import_paths.is_some() || self.std_lib != StdLib::Full);
         vm.get_macros().insert(String::from("import"), import);
 
         Compiler::new()
@@ -593,21 +818,63 @@ impl VmBuilder {
 
         add_extern_module(&vm, "std.prim", ::vm::primitives::load);
         add_extern_module(&vm, "std.int.prim", ::vm::primitives::load_int);
+        add_extern_module(&vm, "std.i32.prim", ::vm::fixed_int::load_i32);
+        #[cfg(target_pointer_width = "64")]
+        add_extern_module(&vm, "std.u64.prim", ::vm::fixed_int::load_u64);
+        add_extern_module(&vm, "std.word.prim", ::vm::fixed_int::load_word);
         add_extern_module(&vm, "std.float.prim", ::vm::primitives::load_float);
         add_extern_module(&vm, "std.string.prim", ::vm::primitives::load_string);
         add_extern_module(&vm, "std.char.prim", ::vm::primitives::load_char);
         add_extern_module(&vm, "std.array.prim", ::vm::primitives::load_array);
+        add_extern_module(&vm, "std.mutable_array.prim", ::vm::mutable_array::load);
+        add_extern_module(
+            &vm,
+            "std.byte_buffer.prim",
+            ::vm::mutable_array::load_byte_buffer,
+        );
 
         add_extern_module(&vm, "std.lazy", ::vm::lazy::load);
         add_extern_module(&vm, "std.reference", ::vm::reference::load);
+        add_extern_module(&vm, "std.weak", ::vm::weak::load);
 
         add_extern_module(&vm, "std.channel", ::vm::channel::load_channel);
         add_extern_module(&vm, "std.thread.prim", ::vm::channel::load_thread);
         add_extern_module(&vm, "std.debug", ::vm::debug::load);
-        add_extern_module(&vm, "std.io.prim", ::io::load);
+        if self.std_lib == StdLib::NoFilesystem {
+            add_extern_module(&vm, "std.io.prim", ::io::load_without_filesystem);
+        } else {
+            add_extern_module(&vm, "std.io.prim", ::io::load);
+        }
 
         load_regex(&vm);
         load_random(&vm);
+        load_bigint(&vm);
+
+        let allowed_modules = match self.std_lib {
+            StdLib::Full => None,
+            StdLib::Pure => Some(PURE_MODULES.iter().map(|s| s.to_string()).collect()),
+            StdLib::NoFilesystem => Some(
+                PURE_MODULES
+                    .iter()
+                    .chain(NO_FILESYSTEM_MODULES)
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            StdLib::Allowlist(modules) => Some(
+                modules
+                    .into_iter()
+                    .chain(PRELUDE_MODULES.iter().map(|s| s.to_string()))
+                    .collect(),
+            ),
+        };
+        {
+            let import = vm.get_macros().get("import");
+            import
+                .as_ref()
+                .and_then(|import| import.downcast_ref::<Import>())
+                .expect("Import macro")
+                .set_allowed_modules(allowed_modules);
+        }
 
         vm
     }
@@ -619,6 +886,25 @@ pub fn new_vm() -> RootedThread {
     VmBuilder::default().build()
 }
 
+/// Searches `dir` and its parents for a `gluon.toml` manifest and, if one is found, lets `vm`
+/// import the modules of the package and its dependencies. Returns the manifest that was loaded.
+pub fn load_manifest(vm: &Thread, dir: &Path) -> Result<Option<manifest::Manifest>> {
+    match manifest::Manifest::find(dir)? {
+        Some(manifest) => {
+            manifest.configure(vm)?;
+            Ok(Some(manifest))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(feature = "bigint")]
+fn load_bigint(vm: &Thread) {
+    add_extern_module(&vm, "std.bigint.prim", ::vm::bigint::load);
+}
+#[cfg(not(feature = "bigint"))]
+fn load_bigint(_: &Thread) {}
+
 #[cfg(feature = "regex")]
 fn load_regex(vm: &Thread) {
     add_extern_module(&vm, "std.regex", ::regex_bind::load);
@@ -0,0 +1,216 @@
+//! Reading of `gluon.toml` package manifests.
+//!
+//! A manifest declares the name of a package, the directory its modules are stored in and the
+//! packages it depends on. Dependencies are directories on the local filesystem which contain
+//! another `gluon.toml` or, if they do not, are used as a source root directly.
+//!
+//! ```toml
+//! [package]
+//! name = "mylib"
+//! # The directory containing the modules of the package, relative to the manifest (default: src)
+//! source = "src"
+//!
+//! [dependencies]
+//! other = { path = "../other" }
+//! # Shorthand for `{ path = "vendor/util" }`
+//! util = "vendor/util"
+//! ```
+//!
+//! Modules of a package are imported by prefixing them with the name of the package, with the
+//! above manifest `import! mylib.util` loads `src/util.glu` and `import! other.x` loads `x.glu`
+//! from the source directory of `other`.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use base::fnv::FnvSet;
+
+use vm::thread::Thread;
+
+use import::{add_module_source, DirectorySource};
+
+/// The file name of manifests
+pub const MANIFEST_NAME: &str = "gluon.toml";
+
+quick_error! {
+    /// Error type for reading manifests
+    #[derive(Debug)]
+    pub enum Error {
+        /// The manifest could not be read
+        IO(path: PathBuf, err: io::Error) {
+            description(err.description())
+            display("Unable to read `{}`: {}", path.display(), err)
+        }
+        /// The manifest is not valid TOML
+        Parse(path: PathBuf, err: toml::de::Error) {
+            description(err.description())
+            display("Unable to parse `{}`: {}", path.display(), err)
+        }
+        /// The manifest is valid TOML but is not a valid manifest
+        Invalid(path: PathBuf, message: String) {
+            description(message)
+            display("Invalid manifest `{}`: {}", path.display(), message)
+        }
+    }
+}
+
+/// A package which has been resolved from a manifest
+#[derive(Clone, Debug, PartialEq)]
+pub struct Package {
+    /// The name that the modules of the package are prefixed with
+    pub name: String,
+    /// The directory containing the modules of the package
+    pub source: PathBuf,
+}
+
+/// A dependency declared in a manifest
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    /// The directory of the dependency
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+    /// The path to the manifest file
+    pub path: PathBuf,
+    pub name: String,
+    /// The directory containing the modules of the package
+    pub source: PathBuf,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl Manifest {
+    /// Parses `input` as the contents of the manifest at `path`. Relative paths are resolved
+    /// relative to the directory of `path`.
+    pub fn parse(path: &Path, input: &str) -> Result<Manifest, Error> {
+        let invalid = |message: String| Error::Invalid(path.to_owned(), message);
+
+        let value: toml::Value = input
+            .parse()
+            .map_err(|err| Error::Parse(path.to_owned(), err))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let package = value
+            .get("package")
+            .and_then(|package| package.as_table())
+            .ok_or_else(|| invalid("Missing the `[package]` table".into()))?;
+        let name = package
+            .get("name")
+            .and_then(|name| name.as_str())
+            .ok_or_else(|| invalid("Missing the name of the package".into()))?;
+        let source = match package.get("source") {
+            Some(source) => source
+                .as_str()
+                .ok_or_else(|| invalid("`source` must be a string".into()))?,
+            None => "src",
+        };
+
+        let mut dependencies = Vec::new();
+        if let Some(deps) = value.get("dependencies") {
+            let deps = deps.as_table()
+                .ok_or_else(|| invalid("`dependencies` must be a table".into()))?;
+            for (dep_name, dep) in deps {
+                let dep_path = dep.as_str()
+                    .or_else(|| dep.get("path").and_then(|path| path.as_str()))
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "The dependency `{}` must be a path or a table with a `path`",
+                            dep_name
+                        ))
+                    })?;
+                dependencies.push(Dependency {
+                    name: dep_name.clone(),
+                    path: dir.join(dep_path),
+                });
+            }
+        }
+
+        Ok(Manifest {
+            path: path.to_owned(),
+            name: name.into(),
+            source: dir.join(source),
+            dependencies,
+        })
+    }
+
+    /// Reads the manifest at `path`
+    pub fn read(path: &Path) -> Result<Manifest, Error> {
+        let mut input = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut input))
+            .map_err(|err| Error::IO(path.to_owned(), err))?;
+        Manifest::parse(path, &input)
+    }
+
+    /// Searches `dir` and then each of its parents for a manifest, returning the first one found
+    pub fn find(dir: &Path) -> Result<Option<Manifest>, Error> {
+        let mut current = Some(dir);
+        while let Some(dir) = current {
+            let path = dir.join(MANIFEST_NAME);
+            if path.is_file() {
+                return Manifest::read(&path).map(Some);
+            }
+            current = dir.parent();
+        }
+        Ok(None)
+    }
+
+    /// Returns this package followed by all of its direct and indirect dependencies
+    pub fn packages(&self) -> Result<Vec<Package>, Error> {
+        let mut packages = vec![
+            Package {
+                name: self.name.clone(),
+                source: self.source.clone(),
+            },
+        ];
+        let mut visited = FnvSet::default();
+        visited.insert(self.name.clone());
+        self.add_dependencies(&mut packages, &mut visited)?;
+        Ok(packages)
+    }
+
+    fn add_dependencies(
+        &self,
+        packages: &mut Vec<Package>,
+        visited: &mut FnvSet<String>,
+    ) -> Result<(), Error> {
+        for dep in &self.dependencies {
+            if !visited.insert(dep.name.clone()) {
+                continue;
+            }
+            let manifest_path = dep.path.join(MANIFEST_NAME);
+            if manifest_path.is_file() {
+                let manifest = Manifest::read(&manifest_path)?;
+                packages.push(Package {
+                    name: dep.name.clone(),
+                    source: manifest.source.clone(),
+                });
+                manifest.add_dependencies(packages, visited)?;
+            } else {
+                packages.push(Package {
+                    name: dep.name.clone(),
+                    source: dep.path.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Lets `thread` import the modules of this package and its dependencies
+    pub fn configure(&self, thread: &Thread) -> Result<(), Error> {
+        for package in self.packages()? {
+            add_module_source(
+                thread,
+                DirectorySource::new(package.source)
+                    .prefix(package.name)
+                    .restrict(true),
+            );
+        }
+        Ok(())
+    }
+}
@@ -13,7 +13,11 @@ use vm::types::VmInt;
 #[derive(Debug, Clone)]
 struct XorShiftRng(self::rand::XorShiftRng);
 
-impl Userdata for XorShiftRng {}
+impl Userdata for XorShiftRng {
+    fn is_mutable(&self) -> bool {
+        false
+    }
+}
 
 impl VmType for XorShiftRng {
     type Type = XorShiftRng;
@@ -12,7 +12,11 @@ use vm::thread::Thread;
 #[derive(Debug)]
 struct Regex(regex::Regex);
 
-impl Userdata for Regex {}
+impl Userdata for Regex {
+    fn is_mutable(&self) -> bool {
+        false
+    }
+}
 
 impl VmType for Regex {
     type Type = Regex;
@@ -25,7 +29,11 @@ impl Traverseable for Regex {
 #[derive(Debug)]
 struct Error(regex::Error);
 
-impl Userdata for Error {}
+impl Userdata for Error {
+    fn is_mutable(&self) -> bool {
+        false
+    }
+}
 
 impl VmType for Error {
     type Type = Error;
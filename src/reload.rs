@@ -0,0 +1,116 @@
+//! Hot reloading of modules loaded from files.
+//!
+//! A `Watcher` keeps track of the modification times of a set of files and reloads the module of
+//! each file which has changed through `Compiler::reload_file`. Modules whose new type is not
+//! compatible with the loaded module are rejected and keep running the old code.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use base::fnv::FnvMap;
+
+use {Compiler, Error, Result, RootedThread, Thread};
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Watches files for changes and reloads the modules they were loaded as
+#[derive(Debug, Default)]
+pub struct Watcher {
+    files: FnvMap<PathBuf, Option<SystemTime>>,
+}
+
+impl Watcher {
+    pub fn new() -> Watcher {
+        Watcher::default()
+    }
+
+    /// Starts watching `filename` which should already be loaded using `Compiler::load_file`
+    pub fn watch<P: Into<PathBuf>>(&mut self, filename: P) {
+        let filename = filename.into();
+        let modified = modified(&filename);
+        self.files.insert(filename, modified);
+    }
+
+    /// Stops watching `filename`
+    pub fn unwatch<P: AsRef<Path>>(&mut self, filename: P) {
+        self.files.remove(filename.as_ref());
+    }
+
+    /// Reloads every watched file which has been modified since it was last loaded, returning
+    /// the result of reloading each of them.
+    ///
+    /// A file which fails to reload is not retried until it is modified again.
+    pub fn poll(&mut self, compiler: &mut Compiler, vm: &Thread) -> Vec<(PathBuf, Result<()>)> {
+        let mut reloaded = Vec::new();
+        for (path, last_modified) in &mut self.files {
+            let modified = modified(path);
+            if modified.is_none() || modified == *last_modified {
+                continue;
+            }
+            *last_modified = modified;
+
+            let result = match path.to_str() {
+                Some(filename) => compiler.reload_file(vm, filename),
+                None => Err(Error::from(format!(
+                    "Unable to reload `{}` as the path is not valid UTF-8",
+                    path.display()
+                ))),
+            };
+            reloaded.push((path.clone(), result));
+        }
+        reloaded
+    }
+
+    /// Spawns a thread which polls the watched files every `interval`, reloading them with
+    /// `compiler` and calling `on_reload` with the result of each reload. `compiler` should be
+    /// configured the same way as the compiler which loaded the files.
+    ///
+    /// The thread is stopped once the returned `WatcherHandle` is dropped.
+    pub fn spawn<F>(
+        mut self,
+        mut compiler: Compiler,
+        vm: RootedThread,
+        interval: Duration,
+        mut on_reload: F,
+    ) -> WatcherHandle
+    where
+        F: FnMut(&Path, Result<()>) + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                for (path, result) in self.poll(&mut compiler, &vm) {
+                    on_reload(&path, result);
+                }
+            }
+        });
+        WatcherHandle {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Handle to a thread created by `Watcher::spawn`. Dropping the handle stops the thread and waits
+/// for it to finish.
+#[derive(Debug)]
+pub struct WatcherHandle {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for WatcherHandle {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up immediately
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Wall clock timeouts for compiling and running gluon code.
+//!
+//! A `Deadline` interrupts a thread (see `Thread::interrupt`) once its duration has passed. The
+//! interrupt flag is checked both by the typechecker and by the virtual machine so the timeout
+//! covers every phase of `Compiler::run_expr`.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use futures::{future, Future};
+use futures::sync::oneshot;
+use tokio_core::reactor::Timeout;
+
+use vm::thread::{RootedThread, StackMarker, Thread, ThreadInternal};
+
+use {Error, Result};
+
+const RUNNING: usize = 0;
+/// The timer has expired but has not yet interrupted the thread
+const TIMED_OUT: usize = 1;
+/// The timer has expired and interrupted the thread
+const INTERRUPTED: usize = 2;
+const FINISHED: usize = 3;
+
+/// Dropping the sender wakes up the timer which then exits without interrupting the thread
+enum Cancel {
+    EventLoop(oneshot::Sender<()>),
+    Thread(mpsc::Sender<()>),
+}
+
+pub struct Deadline {
+    thread: RootedThread,
+    duration: Duration,
+    state: Arc<AtomicUsize>,
+    marker: StackMarker,
+    _cancel: Cancel,
+}
+
+impl Deadline {
+    /// Starts a timer which interrupts `thread` after `duration`. The timer is run on the event
+    /// loop of the virtual machine if one exists, otherwise a separate OS thread is spawned.
+    pub fn start(thread: &Thread, duration: Duration) -> Deadline {
+        let state = Arc::new(AtomicUsize::new(RUNNING));
+        let interrupt = {
+            let thread = thread.root_thread();
+            let state = state.clone();
+            move || if state.compare_and_swap(RUNNING, TIMED_OUT, Ordering::SeqCst) == RUNNING {
+                debug!("Timeout of {:?} expired, interrupting thread", duration);
+                thread.interrupt();
+                state.store(INTERRUPTED, Ordering::SeqCst);
+            }
+        };
+
+        let cancel = match thread.global_env().get_event_loop() {
+            Some(remote) => {
+                let (sender, receiver) = oneshot::channel();
+                remote.spawn(move |handle| {
+                    let timeout = future::result(Timeout::new(duration, handle))
+                        .flatten()
+                        .map(|()| true)
+                        .map_err(|err| warn!("Unable to start timeout: {}", err));
+                    let canceled = receiver.then(|_| Ok::<_, ()>(false));
+                    timeout.select(canceled).then(move |result| {
+                        if let Ok((true, _)) = result {
+                            interrupt();
+                        }
+                        Ok::<(), ()>(())
+                    })
+                });
+                Cancel::EventLoop(sender)
+            }
+            None => {
+                let (sender, receiver) = mpsc::channel();
+                thread::spawn(move || {
+                    if let Err(mpsc::RecvTimeoutError::Timeout) = receiver.recv_timeout(duration) {
+                        interrupt();
+                    }
+                });
+                Cancel::Thread(sender)
+            }
+        };
+
+        Deadline {
+            marker: thread.context().stack_marker(),
+            thread: thread.root_thread(),
+            duration: duration,
+            state: state,
+            _cancel: cancel,
+        }
+    }
+
+    /// Stops the timer. If the timeout expired before `result` was computed the thread is reset
+    /// so that it can be used again and any error is replaced by `Error::Timeout`.
+    pub fn finish<T>(self, result: Result<T>) -> Result<T> {
+        if self.state.compare_and_swap(RUNNING, FINISHED, Ordering::SeqCst) == RUNNING {
+            return result;
+        }
+        // The interrupt must have landed before it is cleared, otherwise the thread would be left
+        // interrupted
+        while self.state.load(Ordering::SeqCst) != INTERRUPTED {
+            thread::yield_now();
+        }
+        self.thread.context().unwind(self.marker);
+        self.thread.clear_interrupt();
+        match result {
+            // The computation finished just as the timer expired
+            Ok(value) => Ok(value),
+            Err(_) => Err(Error::Timeout(self.duration)),
+        }
+    }
+}
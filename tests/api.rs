@@ -302,3 +302,156 @@ fn tuples_start_at_0() {
         "{ _0 : Int, _1 : Float, _2 : String }"
     );
 }
+
+#[test]
+fn vec_and_fixed_array_roundtrip() {
+    let _ = ::env_logger::init();
+    let expr = r#"
+        let array = import! std.array
+        { append = \xs -> array.append xs [4] }
+    "#;
+    let vm = make_vm();
+    load_script(&vm, "test", expr).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut append: FunctionRef<fn(Vec<VmInt>) -> Vec<VmInt>> =
+        vm.get_global("test.append").unwrap();
+    assert_eq!(append.call(vec![1, 2, 3]).unwrap(), vec![1, 2, 3, 4]);
+
+    let mut append: FunctionRef<fn([VmInt; 2]) -> [VmInt; 3]> =
+        vm.get_global("test.append").unwrap();
+    assert_eq!(append.call([1, 2]).unwrap(), [1, 2, 4]);
+}
+
+#[test]
+fn pushed_tuples_have_field_names() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    load_script(&vm, "snd", r"\t -> t._1").unwrap_or_else(|err| panic!("{}", err));
+
+    let mut snd: FunctionRef<fn((VmInt, String)) -> String> = vm.get_global("snd").unwrap();
+    assert_eq!(snd.call((1, "abc".to_string())).unwrap(), "abc");
+}
+
+#[test]
+fn box_roundtrip() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    load_script(&vm, "id", r"\x -> x").unwrap_or_else(|err| panic!("{}", err));
+
+    let mut id: FunctionRef<fn(Box<str>) -> Box<str>> = vm.get_global("id").unwrap();
+    assert_eq!(&*id.call("abc".into()).unwrap(), "abc");
+
+    let mut id: FunctionRef<fn(VmInt) -> Box<VmInt>> = vm.get_global("id").unwrap();
+    assert_eq!(id.call(3).unwrap(), Box::new(3));
+}
+
+#[test]
+fn map_roundtrip() {
+    use std::collections::{BTreeMap, HashMap};
+
+    let _ = ::env_logger::init();
+    let expr = r#"
+        let string = import! std.string
+        let map = import! std.map
+        let { insert } = map.make string.ord
+        { insert_c = insert "c" 3 }
+    "#;
+    let vm = make_vm();
+    load_script(&vm, "test", expr).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut insert_c: FunctionRef<fn(BTreeMap<String, VmInt>) -> BTreeMap<String, VmInt>> =
+        vm.get_global("test.insert_c").unwrap();
+    let input: BTreeMap<_, _> = vec![("b".to_string(), 2), ("d".to_string(), 4)]
+        .into_iter()
+        .collect();
+    let mut expected = input.clone();
+    expected.insert("c".to_string(), 3);
+    assert_eq!(insert_c.call(input).unwrap(), expected);
+
+    let mut insert_c: FunctionRef<fn(HashMap<String, VmInt>) -> HashMap<String, VmInt>> =
+        vm.get_global("test.insert_c").unwrap();
+    let input: HashMap<_, _> = vec![("a".to_string(), 1), ("e".to_string(), 5)]
+        .into_iter()
+        .collect();
+    let mut expected = input.clone();
+    expected.insert("c".to_string(), 3);
+    assert_eq!(insert_c.call(input).unwrap(), expected);
+}
+
+#[test]
+fn map_without_std_map_is_a_type_error() {
+    use std::collections::BTreeMap;
+
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    Compiler::new()
+        .implicit_prelude(false)
+        .load_script_async(&vm, "test", "1")
+        .sync_or_error()
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert!(vm.get_global::<BTreeMap<String, VmInt>>("test").is_err());
+}
+
+#[test]
+fn record_ref_field_access() {
+    let _ = ::env_logger::init();
+    let expr = r#"
+        {
+            name = "server",
+            port = 8080,
+            limits = { connections = 100, timeout = 2.5 },
+        }
+    "#;
+    let vm = make_vm();
+    load_script(&vm, "config", expr).unwrap_or_else(|err| panic!("{}", err));
+
+    let config = vm.get_global_record("config").unwrap();
+    assert_eq!(config.get::<String>("name").unwrap(), "server");
+    assert_eq!(config.get::<VmInt>("port").unwrap(), 8080);
+    assert!(config.get::<String>("port").is_err());
+    assert!(config.get::<VmInt>("missing").is_err());
+
+    let limits = config.get_record("limits").unwrap();
+    assert_eq!(limits.get::<f64>("timeout").unwrap(), 2.5);
+
+    let names: Vec<_> = config.iter().map(|(name, _)| name.to_string()).collect();
+    assert_eq!(names, ["name", "port", "limits"]);
+    let port = config
+        .iter()
+        .find(|&(name, _)| name == "port")
+        .map(|(_, value)| value.get::<VmInt>().unwrap());
+    assert_eq!(port, Some(8080));
+}
+
+#[test]
+fn shared_function_called_from_other_threads() {
+    use std::collections::HashMap;
+    use std::thread;
+
+    use gluon::vm::api::SharedFunction;
+
+    fn assert_send_sync<T: Send + Sync + 'static>(_: &T) {}
+
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    load_script(&vm, "add", r"\x y -> x #Int+ y").unwrap_or_else(|err| panic!("{}", err));
+
+    let mut handlers = HashMap::new();
+    {
+        let add: FunctionRef<fn(VmInt, VmInt) -> VmInt> = vm.get_global("add").unwrap();
+        handlers.insert("add", SharedFunction::new(add).unwrap());
+    }
+    assert_send_sync(&handlers);
+
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let handlers = handlers.clone();
+            thread::spawn(move || handlers["add"].call(i, 10).unwrap())
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(results, [10, 11, 12, 13]);
+
+    assert_eq!(handlers["add"].call_async(1, 2).wait().unwrap(), 3);
+}
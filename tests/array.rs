@@ -98,4 +98,154 @@ let array = import! std.array
 0
 "#,
 0i32
-}
\ No newline at end of file
+}
+
+test_expr!{ array_map_fold,
+r#"
+let array = import! std.array.prim
+let arr = array.map (\x -> x #Int* 2) [1, 2, 3]
+let floats = array.map (\x -> x #Float+ 0.5) [1.0, 2.0]
+array.fold (\acc x -> acc #Int+ x) 0 arr #Int== 12
+    && array.index floats 1 #Float== 2.5
+    && array.len (array.map (\x -> x) []) #Int== 0
+"#,
+true
+}
+
+test_expr!{ array_slice_fill_copy,
+r#"
+let array = import! std.array.prim
+let arr = [1b, 2b, 3b, 4b]
+let s = array.slice arr 1 3
+let f = array.fill 3 7
+let c = array.copy arr 2 [9b, 9b]
+array.len s #Int== 2 && array.index s 0 #Byte== 2b
+    && array.len f #Int== 3 && array.index f 2 #Int== 7
+    && array.index c 1 #Byte== 2b && array.index c 3 #Byte== 9b
+    && array.len (array.slice arr 2 2) #Int== 0
+"#,
+true
+}
+
+test_expr!{ array_concat,
+r#"
+let array = import! std.array.prim
+let arr = array.concat [[], [1.0], [2.0, 3.0]]
+array.len arr #Int== 3 && array.index arr 2 #Float== 3.0
+"#,
+true
+}
+
+test_expr!{ array_sort_and_binary_search,
+r#"
+let array = import! std.array
+let int = import! std.int
+let { Result } = import! std.types
+let sorted = array.sort int.ord [5, 3, 8, 1]
+let found =
+    match array.binary_search int.ord 5 sorted with
+    | Ok i -> i
+    | Err _ -> -1
+let missing =
+    match array.binary_search int.ord 4 sorted with
+    | Ok _ -> -1
+    | Err i -> i
+array.index sorted 0 #Int== 1 && array.index sorted 3 #Int== 8
+    && found #Int== 2 && missing #Int== 2
+"#,
+true
+}
+
+#[test]
+fn array_slice_out_of_range_is_an_error() {
+    let _ = ::env_logger::init();
+    let vm = support::make_vm();
+    let result = gluon::Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i32>(
+            &vm,
+            "<top>",
+            "let array = import! std.array.prim in array.len (array.slice [1, 2] 1 3)",
+        );
+    assert!(result.is_err());
+}
+
+#[test]
+fn array_fill_beyond_the_memory_limit_is_an_error() {
+    let _ = ::env_logger::init();
+    let vm = support::make_vm();
+    let expr = r#"
+let array = import! std.array.prim
+let int = import! std.int.prim
+array.len (array.fill int.max_value 0)
+"#;
+    let result = gluon::Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i32>(&vm, "<top>", expr);
+    assert!(result.is_err());
+}
+
+test_expr!{ io mutable_array,
+r#"
+let io = import! std.io.prim
+let array = import! std.array.prim
+let mutable_array = import! std.mutable_array
+let (>>=) m f = io.flat_map f m
+mutable_array.new >>= \arr ->
+    mutable_array.push arr 1 >>= \_ ->
+    mutable_array.push arr 2 >>= \_ ->
+    mutable_array.set arr 0 10 >>= \_ ->
+    mutable_array.freeze arr >>= \frozen ->
+    mutable_array.push arr 3 >>= \_ ->
+    mutable_array.len arr >>= \len ->
+    io.wrap (array.index frozen 0 #Int+ array.index frozen 1 #Int+ array.len frozen #Int+ len)
+"#,
+17i32
+}
+
+test_expr!{ io byte_buffer,
+r#"
+let io = import! std.io.prim
+let array = import! std.array.prim
+let byte_buffer = import! std.byte_buffer
+let (>>=) m f = io.flat_map f m
+byte_buffer.new >>= \buffer ->
+    byte_buffer.extend buffer [1b, 2b] >>= \_ ->
+    byte_buffer.push buffer 3b >>= \_ ->
+    byte_buffer.set buffer 0 5b >>= \_ ->
+    byte_buffer.get buffer 2 >>= \b ->
+    byte_buffer.freeze buffer >>= \bytes ->
+    io.wrap (array.index bytes 0 #Byte== 5b && b #Byte== 3b && array.len bytes #Int== 3)
+"#,
+true
+}
+
+test_expr!{ io mutable_array_index_out_of_range,
+r#"
+let io = import! std.io.prim
+let mutable_array = import! std.mutable_array
+let (>>=) m f = io.flat_map f m
+let action =
+    mutable_array.new >>= \arr ->
+        mutable_array.push arr 1 >>= \_ ->
+        mutable_array.get arr 1
+io.catch action (\_ -> io.wrap 0)
+"#,
+0i32
+}
+
+test_expr!{ io with_capacity_beyond_the_memory_limit_is_an_error,
+r#"
+let io = import! std.io.prim
+let int = import! std.int.prim
+let mutable_array = import! std.mutable_array
+let byte_buffer = import! std.byte_buffer
+let (>>=) m f = io.flat_map f m
+let array_action = mutable_array.with_capacity int.max_value >>= \_ -> io.wrap 1
+let buffer_action = byte_buffer.with_capacity int.max_value >>= \_ -> io.wrap 2
+io.catch array_action (\_ -> io.wrap 0) >>= \x ->
+    io.catch buffer_action (\_ -> io.wrap 0) >>= \y ->
+    io.wrap (x #Int+ y)
+"#,
+0i32
+}
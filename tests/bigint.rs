@@ -0,0 +1,82 @@
+#![cfg(feature = "bigint")]
+extern crate env_logger;
+extern crate gluon;
+
+use gluon::vm::api::FunctionRef;
+use gluon::vm::bigint::BigInt;
+use gluon::{new_vm, Compiler};
+
+#[test]
+fn bigint_arithmetic() {
+    let _ = ::env_logger::init();
+
+    let thread = new_vm();
+    let text = r#"
+        let bigint = import! std.bigint
+        let { (*), (-) } = bigint.num
+        let { (<) } = (import! std.prelude).make_Ord bigint.ord
+        let x = bigint.from_int 9223372036854775807
+        let y = x * x - bigint.from_int 1
+        if x < y then bigint.show y else "wrong order"
+        "#;
+    let result = Compiler::new()
+        .run_expr_async::<String>(&thread, "<top>", text)
+        .sync_or_error();
+
+    assert_eq!(
+        result.unwrap_or_else(|err| panic!("{}", err)).0,
+        "85070591730234615847396907784232501248"
+    );
+}
+
+#[test]
+fn bigint_radix() {
+    let _ = ::env_logger::init();
+
+    let thread = new_vm();
+    let text = r#"
+        let bigint = import! std.bigint
+        let { Result } = import! std.result
+        match bigint.parse_radix "ffffffffffffffffffff" 16 with
+        | Ok x -> bigint.to_str_radix x 2
+        | Err _ -> "parse error"
+        "#;
+    let result = Compiler::new()
+        .run_expr_async::<String>(&thread, "<top>", text)
+        .sync_or_error();
+
+    assert_eq!(
+        result.unwrap_or_else(|err| panic!("{}", err)).0,
+        ::std::iter::repeat('1').take(80).collect::<String>()
+    );
+}
+
+#[test]
+fn bigint_divide_by_zero() {
+    let _ = ::env_logger::init();
+
+    let thread = new_vm();
+    let text = r#"
+        let bigint = import! std.bigint
+        bigint.div (bigint.from_int 1) (bigint.from_int 0)
+        "#;
+    let result = Compiler::new()
+        .run_expr_async::<BigInt>(&thread, "<top>", text)
+        .sync_or_error();
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn marshal_bigint() {
+    let _ = ::env_logger::init();
+
+    let thread = new_vm();
+    Compiler::new()
+        .load_script(&thread, "square", r#"\x -> (import! std.bigint).mul x x"#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let mut square: FunctionRef<fn(BigInt) -> BigInt> = thread.get_global("square").unwrap();
+
+    let x: BigInt = "123456789012345678901234567890".parse().unwrap();
+    assert_eq!(square.call(x.clone()), Ok(&x * &x));
+}
@@ -0,0 +1,141 @@
+#[macro_use]
+extern crate gluon_codegen;
+extern crate gluon;
+#[macro_use]
+extern crate gluon_vm;
+
+use gluon::{new_vm, Compiler, Thread};
+use gluon::import::add_extern_module;
+use gluon::vm::{self, ExternModule};
+use gluon::vm::api::FunctionRef;
+use gluon::vm::types::VmInt;
+
+#[derive(Debug, PartialEq, VmType, Pushable, Getable)]
+struct Vec2 {
+    x: VmInt,
+    #[gluon(name = "y_coord")]
+    y: VmInt,
+}
+
+#[test]
+fn derive_record() {
+    let vm = new_vm();
+    let expr = r#"
+        \v -> { y_coord = v.y_coord * 2, x = v.x + 1 }
+    "#;
+    let (mut f, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(Vec2) -> Vec2>>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(f.call(Vec2 { x: 1, y: 2 }).unwrap(), Vec2 { x: 2, y: 4 });
+}
+
+#[derive(Debug, PartialEq, VmType, Pushable, Getable)]
+struct Pair<T> {
+    first: T,
+    second: T,
+}
+
+#[test]
+fn derive_generic_record() {
+    let vm = new_vm();
+    let expr = r#"
+        \p -> { first = p.second, second = p.first }
+    "#;
+    let (mut f, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(Pair<String>) -> Pair<String>>>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let pair = Pair {
+        first: "a".to_string(),
+        second: "b".to_string(),
+    };
+    assert_eq!(
+        f.call(pair).unwrap(),
+        Pair {
+            first: "b".to_string(),
+            second: "a".to_string(),
+        }
+    );
+}
+
+#[derive(Debug, PartialEq, VmType, Pushable, Getable)]
+#[gluon(vm_type = "shapes.Shape")]
+enum Shape {
+    Circle(f64),
+    Rectangle { width: f64, height: f64 },
+    Empty,
+}
+
+#[test]
+fn derive_variant() {
+    let vm = new_vm();
+    Compiler::new()
+        .load_script(
+            &vm,
+            "shapes",
+            "type Shape = | Circle Float | Rectangle Float Float | Empty in { Shape }",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let expr = r#"
+        let { Shape } = import! shapes
+        \s ->
+            match s with
+            | Circle r -> Rectangle r r
+            | Rectangle w h -> Circle w
+            | Empty -> Empty
+    "#;
+    let (mut f, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(Shape) -> Shape>>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        f.call(Shape::Circle(1.0)).unwrap(),
+        Shape::Rectangle {
+            width: 1.0,
+            height: 1.0,
+        }
+    );
+    assert_eq!(
+        f.call(Shape::Rectangle {
+            width: 2.0,
+            height: 3.0,
+        }).unwrap(),
+        Shape::Circle(2.0)
+    );
+    assert_eq!(f.call(Shape::Empty).unwrap(), Shape::Empty);
+}
+
+#[derive(Debug, Clone, Userdata, Traverseable, VmType)]
+#[gluon(vm_type = "Counter", clone)]
+struct Counter(VmInt);
+
+fn new_counter(i: VmInt) -> Counter {
+    Counter(i)
+}
+
+fn counter_value(counter: &Counter) -> VmInt {
+    counter.0
+}
+
+fn load_counter(vm: &Thread) -> vm::Result<ExternModule> {
+    vm.register_type::<Counter>("Counter", &[])?;
+    ExternModule::new(
+        vm,
+        record! {
+            new => primitive!(1 new_counter),
+            value => primitive!(1 counter_value)
+        },
+    )
+}
+
+#[test]
+fn derive_userdata() {
+    let vm = new_vm();
+    add_extern_module(&vm, "counter", load_counter);
+    let expr = r#"
+        let counter = import! counter
+        counter.value (counter.new 3)
+    "#;
+    let (result, _) = Compiler::new()
+        .run_expr::<VmInt>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 3);
+}
@@ -0,0 +1,90 @@
+extern crate env_logger;
+extern crate gluon;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use gluon::vm::types::VmInt;
+use gluon::import::{add_module_source, DirectorySource, EmbeddedSource, Import,
+                    InMemorySource, ModuleText};
+use gluon::{new_vm, Compiler, Thread};
+
+fn run_expr(vm: &Thread, expr: &str) -> VmInt {
+    Compiler::new()
+        .run_expr::<VmInt>(vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .0
+}
+
+#[test]
+fn import_from_memory() {
+    let _ = ::env_logger::init();
+    let vm = new_vm();
+    let source = Arc::new(InMemorySource::new());
+    source.insert("config.limits", "{ max_connections = 10 }");
+    add_module_source(&vm, source.clone());
+
+    assert_eq!(
+        run_expr(&vm, "let { max_connections } = import! config.limits in max_connections"),
+        10
+    );
+
+    // Modules added after the source was registered can be imported as well
+    source.insert("config.other", "import! config.limits");
+    assert_eq!(
+        run_expr(&vm, "(import! config.other).max_connections"),
+        10
+    );
+}
+
+#[test]
+fn import_embedded() {
+    let _ = ::env_logger::init();
+    static MODULES: &[(&str, &str)] = &[
+        ("embedded.a", "let { b } = import! embedded.b in { a = b + 1 }"),
+        ("embedded.b", "{ b = 1 }"),
+    ];
+    let vm = new_vm();
+    add_module_source(&vm, EmbeddedSource::new(MODULES));
+
+    assert_eq!(run_expr(&vm, "(import! embedded.a).a"), 2);
+}
+
+#[test]
+fn import_from_directory_records_path() {
+    let _ = ::env_logger::init();
+    let vm = new_vm();
+    add_module_source(&vm, DirectorySource::new("examples"));
+
+    Compiler::new()
+        .run_expr::<()>(&vm, "test", "let _ = import! http_types in ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let import = vm.get_macros().get("import");
+    let import = import
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro");
+    assert_eq!(
+        import.module_path("http_types"),
+        Some(Path::new("examples").join("http_types.glu"))
+    );
+}
+
+#[test]
+fn errors_name_the_path_of_the_module() {
+    let _ = ::env_logger::init();
+    let vm = new_vm();
+    let source = Arc::new(InMemorySource::new());
+    source.insert_text(
+        "config.broken",
+        ModuleText::new("1 + \"\"", Some(PathBuf::from("config/broken.glu"))),
+    );
+    add_module_source(&vm, source);
+
+    let err = Compiler::new()
+        .run_expr::<VmInt>(&vm, "test", "import! config.broken")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("config/broken.glu"), "{}", err);
+}
@@ -1,10 +1,22 @@
 extern crate env_logger;
+extern crate futures;
 extern crate gluon;
+#[macro_use]
+extern crate gluon_vm;
 extern crate tokio_core;
 
+use std::time::Duration;
+
+use futures::{Future, IntoFuture};
+
+use tokio_core::reactor::Timeout;
+
 use gluon::{new_vm, Compiler, Thread};
+use gluon::import::add_extern_module;
+use gluon::vm::ExternModule;
 use gluon::vm::internal::Value;
-use gluon::vm::api::{Hole, OpaqueValue, IO};
+use gluon::vm::api::{AsyncIO, Hole, OpaqueValue, IO};
+use gluon::vm::types::VmInt;
 
 #[macro_use]
 mod support;
@@ -233,3 +245,57 @@ fn spawn_on_runexpr_in_catch() {
         IO::Exception(err) => panic!("{}", err),
     }
 }
+
+#[test]
+fn async_io_primitive() {
+    let _ = ::env_logger::init();
+
+    fn delay(ms: VmInt) -> AsyncIO<VmInt> {
+        AsyncIO::with_handle(move |handle| {
+            Timeout::new(Duration::from_millis(ms as u64), handle)
+                .into_future()
+                .flatten()
+                .map(move |()| ms)
+        })
+    }
+
+    fn fail(msg: String) -> AsyncIO<()> {
+        AsyncIO::new(futures::failed::<(), String>(msg))
+    }
+
+    let text = r#"
+        let { applicative = { wrap }, monad = { flat_map }, catch } = import! std.io
+        let { delay, fail } = import! async_test
+
+        let failing =
+            do _ = fail "boom"
+            wrap "no error"
+
+        do x = delay 10
+        do msg = catch failing wrap
+        wrap (if x #Int== 10 then msg else "wrong delay")
+    "#;
+
+    let mut core = self::tokio_core::reactor::Core::new().unwrap();
+    let vm = make_async_vm(Some(core.remote()));
+    add_extern_module(&vm, "async_test", |thread| {
+        ExternModule::new(
+            thread,
+            record! {
+                delay => primitive!(1 delay),
+                fail => primitive!(1 fail)
+            },
+        )
+    });
+    let (result, _) = core.run(Compiler::new().run_io(true).run_expr_async::<IO<String>>(
+        &vm,
+        "<top>",
+        text,
+    )).unwrap_or_else(|err| panic!("{}", err));
+    match result {
+        IO::Value(result) => {
+            assert_eq!(result, "boom");
+        }
+        IO::Exception(err) => panic!("{}", err),
+    }
+}
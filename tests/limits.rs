@@ -3,6 +3,8 @@ extern crate gluon;
 
 mod support;
 
+use std::time::Duration;
+
 use gluon::{Compiler, Error, Thread};
 use gluon::vm::Error as VMError;
 use gluon::vm::api::{Hole, OpaqueValue};
@@ -50,3 +52,97 @@ fn stack_overflow() {
         Ok(_) => panic!("Expected an error"),
     }
 }
+
+#[test]
+fn timeout_interrupts_infinite_loop() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+
+    let expr = " let loop x = loop (x + 1) in loop 0 ";
+    let result = Compiler::new().run_expr_with_timeout::<i32>(
+        &vm,
+        "example",
+        expr,
+        Duration::from_millis(100),
+    );
+
+    match result {
+        Err(Error::Timeout(_)) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+
+    // The thread should be usable after the timeout
+    let (value, _) = Compiler::new()
+        .run_expr::<i32>(&vm, "example2", " 1 + 2 ")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(value, 3);
+}
+
+/// Runs `grow`, which is expected to grow a mutable array or byte buffer until it raises an
+/// exception, with the memory limit set to 1MB more than what the loaded modules use
+fn grow_past_the_memory_limit(grow: &str) -> String {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+
+    let header = r#"
+let io = import! std.io.prim
+let array = import! std.array.prim
+let mutable_array = import! std.mutable_array
+let byte_buffer = import! std.byte_buffer
+let (>>=) m f = io.flat_map f m
+"#;
+    Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&vm, "load", &format!("{} ()", header))
+        .unwrap_or_else(|err| panic!("{}", err));
+    let limit = vm.context().gc.allocated_memory() + 1_000_000;
+    vm.set_memory_limit(limit);
+
+    let expr = format!(
+        "{}{}\nio.catch (grow >>= \\_ -> io.wrap \"\") io.wrap",
+        header, grow
+    );
+    Compiler::new()
+        .implicit_prelude(false)
+        .run_io(true)
+        .run_expr::<String>(&vm, "example", &expr)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .0
+}
+
+#[test]
+fn mutable_array_push_past_the_memory_limit() {
+    let err = grow_past_the_memory_limit(
+        r#"
+let push_forever arr = mutable_array.push arr 0 >>= \_ -> push_forever arr
+let grow = mutable_array.new >>= push_forever
+"#,
+    );
+    assert!(err.contains("out of memory"), "{}", err);
+}
+
+#[test]
+fn byte_buffer_push_past_the_memory_limit() {
+    let err = grow_past_the_memory_limit(
+        r#"
+let push_forever buffer = byte_buffer.push buffer 0b >>= \_ -> push_forever buffer
+let grow = byte_buffer.new >>= push_forever
+"#,
+    );
+    assert!(err.contains("out of memory"), "{}", err);
+}
+
+#[test]
+fn byte_buffer_extend_past_the_memory_limit() {
+    let err = grow_past_the_memory_limit(
+        r#"
+let bytes = array.fill 1024 0b
+let extend_forever buffer = byte_buffer.extend buffer bytes >>= \_ -> extend_forever buffer
+let grow = byte_buffer.new >>= extend_forever
+"#,
+    );
+    assert!(err.contains("out of memory"), "{}", err);
+}
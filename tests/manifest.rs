@@ -0,0 +1,85 @@
+extern crate env_logger;
+extern crate gluon;
+
+use std::path::Path;
+
+use gluon::vm::types::VmInt;
+use gluon::manifest::{Manifest, Package};
+use gluon::{load_manifest, new_vm, Compiler, Thread};
+
+fn run_expr(vm: &Thread, expr: &str) -> VmInt {
+    Compiler::new()
+        .run_expr::<VmInt>(vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .0
+}
+
+#[test]
+fn parse_manifest() {
+    let manifest = Manifest::parse(
+        Path::new("pkg/gluon.toml"),
+        r#"
+[package]
+name = "pkg"
+
+[dependencies]
+a = "../a"
+b = { path = "vendor/b" }
+"#,
+    ).unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(manifest.name, "pkg");
+    assert_eq!(manifest.source, Path::new("pkg").join("src"));
+    let mut dependencies = manifest
+        .dependencies
+        .iter()
+        .map(|dep| (&dep.name[..], dep.path.clone()))
+        .collect::<Vec<_>>();
+    dependencies.sort();
+    assert_eq!(
+        dependencies,
+        vec![
+            ("a", Path::new("pkg").join("../a")),
+            ("b", Path::new("pkg").join("vendor/b")),
+        ]
+    );
+}
+
+#[test]
+fn invalid_manifest() {
+    assert!(Manifest::parse(Path::new("gluon.toml"), "[package]").is_err());
+    assert!(Manifest::parse(Path::new("gluon.toml"), "[package\nname = 1").is_err());
+}
+
+#[test]
+fn find_manifest_in_parent_directory() {
+    let manifest = Manifest::find(Path::new("tests/packages/app/src"))
+        .unwrap_or_else(|err| panic!("{}", err))
+        .expect("Manifest");
+    assert_eq!(manifest.name, "app");
+
+    let packages = manifest.packages().unwrap_or_else(|err| panic!("{}", err));
+    let mut names = packages
+        .iter()
+        .map(|package| &package.name[..])
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["app", "plain", "shapes"]);
+    assert!(packages.contains(&Package {
+        name: "shapes".into(),
+        source: Path::new("tests/packages/app/../shapes").join("modules"),
+    }));
+}
+
+#[test]
+fn import_modules_of_packages() {
+    let _ = ::env_logger::init();
+    let vm = new_vm();
+    load_manifest(&vm, Path::new("tests/packages/app"))
+        .unwrap_or_else(|err| panic!("{}", err))
+        .expect("Manifest");
+
+    assert_eq!(run_expr(&vm, "(import! app).value"), 46);
+    assert_eq!(run_expr(&vm, "(import! app.util).double_side"), 8);
+    assert_eq!(run_expr(&vm, "(import! shapes.square).side"), 4);
+}
@@ -1,8 +1,12 @@
 extern crate env_logger;
+#[macro_use]
+extern crate gluon_vm;
 extern crate gluon;
 
-use gluon::vm::thread::RootedThread;
-use gluon::import::Import;
+use gluon::base::metadata::Metadata;
+use gluon::vm::ExternModule;
+use gluon::vm::thread::{RootedThread, Thread};
+use gluon::import::{add_extern_module, Import};
 use gluon::Compiler;
 
 fn make_vm() -> RootedThread {
@@ -33,3 +37,70 @@ let { List, of }  = import! std.list
     assert!(env.get_metadata("test.of").is_ok());
     assert!(env.get_metadata("test.List").is_ok());
 }
+
+fn add(l: i32, r: i32) -> i32 {
+    l + r
+}
+
+fn load_math(vm: &Thread) -> gluon::vm::Result<ExternModule> {
+    ExternModule::with_metadata(
+        vm,
+        record!(add => primitive!(2 add)),
+        Metadata::with_comment("Integer arithmetic")
+            .field("add", Metadata::with_comment("Adds two integers")),
+    )
+}
+
+#[test]
+fn metadata_from_extern_module() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    add_extern_module(&vm, "math", load_math);
+    let text = r#"
+let { add } = import! math
+{ add }
+"#;
+    Compiler::new()
+        .load_script_async(&vm, "test", text)
+        .sync_or_error()
+        .unwrap();
+
+    let env = vm.get_env();
+    assert_eq!(
+        env.get_metadata("math")
+            .ok()
+            .and_then(|metadata| metadata.comment.clone()),
+        Some("Integer arithmetic".to_string())
+    );
+    assert_eq!(
+        env.get_metadata("math.add")
+            .ok()
+            .and_then(|metadata| metadata.comment.clone()),
+        Some("Adds two integers".to_string())
+    );
+    assert_eq!(
+        env.get_metadata("test.add")
+            .ok()
+            .and_then(|metadata| metadata.comment.clone()),
+        Some("Adds two integers".to_string())
+    );
+}
+
+#[test]
+fn metadata_from_define_global() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    vm.define_global_with_metadata(
+        "documented_add",
+        primitive!(2 add),
+        Metadata::with_comment("Adds two integers"),
+    ).unwrap();
+
+    let env = vm.get_env();
+    assert_eq!(
+        env.get_metadata("documented_add")
+            .ok()
+            .and_then(|metadata| metadata.comment.clone()),
+        Some("Adds two integers".to_string())
+    );
+}
@@ -0,0 +1,109 @@
+extern crate gluon;
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gluon::{new_vm, Compiler, Error};
+use gluon::reload::Watcher;
+use gluon::vm::api::FunctionRef;
+use gluon::vm::types::VmInt;
+
+#[test]
+fn reload_module_with_compatible_type() {
+    let vm = new_vm();
+    let mut compiler = Compiler::new();
+    compiler
+        .load_script(&vm, "handler", "let handle x = x + 1 in { handle }")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut handle: FunctionRef<fn(VmInt) -> VmInt> = vm.get_global("handler.handle").unwrap();
+    assert_eq!(handle.call(10).unwrap(), 11);
+
+    compiler
+        .reload_script(&vm, "handler", "let handle x = x * 2 in { handle }")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut handle: FunctionRef<fn(VmInt) -> VmInt> = vm.get_global("handler.handle").unwrap();
+    assert_eq!(handle.call(10).unwrap(), 20);
+}
+
+#[test]
+fn reject_reload_with_incompatible_type() {
+    let vm = new_vm();
+    let mut compiler = Compiler::new();
+    compiler
+        .load_script(&vm, "handler", "let handle x = x + 1 in { handle }")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let result = compiler.reload_script(&vm, "handler", r#"let handle x = "" in { handle }"#);
+    match result {
+        Err(Error::IncompatibleReload(..)) => (),
+        _ => panic!("Expected an incompatible reload error, got {:?}", result),
+    }
+
+    let mut handle: FunctionRef<fn(VmInt) -> VmInt> = vm.get_global("handler.handle").unwrap();
+    assert_eq!(handle.call(10).unwrap(), 11);
+}
+
+#[test]
+fn reload_requires_loaded_module() {
+    let vm = new_vm();
+    let result = Compiler::new().reload_script(&vm, "handler", "1");
+    assert!(result.is_err());
+}
+
+#[test]
+fn dropping_the_watcher_handle_stops_the_thread() {
+    let vm = new_vm();
+    let handle = Watcher::new().spawn(
+        Compiler::new(),
+        vm,
+        Duration::from_secs(60),
+        |path, result| panic!("Unexpected reload of `{}`: {:?}", path.display(), result),
+    );
+    let start = Instant::now();
+    drop(handle);
+    assert!(start.elapsed() < Duration::from_secs(30));
+}
+
+fn write_file(filename: &str, contents: &str) {
+    File::create(filename)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .unwrap_or_else(|err| panic!("Unable to write `{}`: {}", filename, err));
+}
+
+#[test]
+fn poll_reloads_modified_file_once() {
+    fs::create_dir_all("target/reload").unwrap();
+    let filename = "target/reload/poll_handler.glu";
+    write_file(filename, "let handle x = x + 1 in { handle }");
+
+    let vm = new_vm();
+    let mut compiler = Compiler::new();
+    compiler
+        .load_file(&vm, filename)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut watcher = Watcher::new();
+    watcher.watch(filename);
+    assert!(watcher.poll(&mut compiler, &vm).is_empty());
+
+    // Make sure that the modification time changes on file systems with a coarse resolution
+    thread::sleep(Duration::from_millis(1100));
+    write_file(filename, "let handle x = x * 2 in { handle }");
+
+    let reloaded = watcher.poll(&mut compiler, &vm);
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded[0].0, PathBuf::from(filename));
+    if let Err(ref err) = reloaded[0].1 {
+        panic!("{}", err);
+    }
+    assert!(watcher.poll(&mut compiler, &vm).is_empty());
+
+    let mut handle: FunctionRef<fn(VmInt) -> VmInt> =
+        vm.get_global("target.reload.poll_handler.handle").unwrap();
+    assert_eq!(handle.call(10).unwrap(), 20);
+}
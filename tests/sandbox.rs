@@ -0,0 +1,158 @@
+extern crate env_logger;
+extern crate gluon;
+#[macro_use]
+extern crate gluon_vm;
+
+use std::path::PathBuf;
+
+use gluon::vm::ExternModule;
+use gluon::vm::api::IO;
+use gluon::vm::thread::{RootedThread, Thread};
+use gluon::vm::types::VmInt;
+use gluon::base::fnv::FnvSet;
+use gluon::import::{add_extern_module, Import};
+use gluon::{Compiler, StdLib, VmBuilder};
+
+fn run_expr<T>(vm: &Thread, expr: &str) -> Result<T, String>
+where
+    T: for<'vm> gluon::vm::api::Getable<'vm> + gluon::vm::api::VmType + Send + 'static,
+{
+    Compiler::new()
+        .run_io(true)
+        .run_expr::<T>(vm, "test", expr)
+        .map(|(value, _)| value)
+        .map_err(|err| err.to_string())
+}
+
+fn make_vm(std_lib: StdLib) -> RootedThread {
+    VmBuilder::new().std_lib(std_lib).build()
+}
+
+#[test]
+fn pure_vm_refuses_io() {
+    let _ = ::env_logger::init();
+    let vm = make_vm(StdLib::Pure);
+
+    assert_eq!(run_expr::<VmInt>(&vm, "1 + 2"), Ok(3));
+
+    let err = run_expr::<()>(&vm, "let io = import! std.io in ()").unwrap_err();
+    assert!(
+        err.contains("Module 'std.io.prim' is not allowed to be imported"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn io_without_filesystem_raises_exceptions() {
+    let _ = ::env_logger::init();
+    let vm = make_vm(StdLib::NoFilesystem);
+
+    let expr = r#"
+        let io = import! std.io
+        io.catch (io.read_file_to_string "Cargo.toml") (\err -> io.applicative.wrap err)
+    "#;
+    let result = run_expr::<IO<String>>(&vm, expr).unwrap_or_else(|err| panic!("{}", err));
+    match result {
+        IO::Value(msg) => assert!(msg.contains("is not available"), "{}", msg),
+        IO::Exception(err) => panic!("{}", err),
+    }
+}
+
+#[test]
+fn allowlist_refuses_unlisted_modules() {
+    let _ = ::env_logger::init();
+    let vm = make_vm(StdLib::Allowlist(vec!["std.lazy".to_string()]));
+
+    let expr = r#"
+        let { lazy, force } = import! std.lazy
+        force (lazy (\_ -> 1))
+    "#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<VmInt>(&vm, "test", expr)
+        .map(|(value, _)| value)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 1);
+
+    let err = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<()>(&vm, "test", "let r = import! std.reference in ()")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("is not allowed to be imported"), "{}", err);
+}
+
+#[test]
+fn allowlist_allows_the_modules_of_the_implicit_prelude() {
+    let _ = ::env_logger::init();
+    let vm = make_vm(StdLib::Allowlist(vec!["std.lazy".to_string()]));
+
+    let expr = r#"
+        let { lazy, force } = import! std.lazy
+        force (lazy (\_ -> 1)) + 2
+    "#;
+    assert_eq!(run_expr::<VmInt>(&vm, expr), Ok(3));
+
+    let err = run_expr::<()>(&vm, "let r = import! std.reference in ()").unwrap_err();
+    assert!(err.contains("is not allowed to be imported"), "{}", err);
+}
+
+#[test]
+fn extern_modules_added_by_the_host_are_allowed() {
+    let _ = ::env_logger::init();
+    let vm = make_vm(StdLib::Pure);
+
+    fn double(x: VmInt) -> VmInt {
+        x * 2
+    }
+    add_extern_module(&vm, "host", |thread| {
+        ExternModule::new(thread, record! { double => primitive!(1 double) })
+    });
+
+    assert_eq!(
+        run_expr::<VmInt>(&vm, "let host = import! host in host.double 4"),
+        Ok(8)
+    );
+}
+
+#[test]
+fn imports_outside_of_the_import_paths_are_refused() {
+    let _ = ::env_logger::init();
+    let vm = VmBuilder::new()
+        .import_paths(Some(vec![PathBuf::from("examples")]))
+        .build();
+
+    run_expr::<()>(&vm, r#"let _ = import! "http_types.glu" in ()"#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let err = run_expr::<()>(&vm, r#"let _ = import! "../tests/pass/lazy.glu" in ()"#)
+        .unwrap_err();
+    assert!(err.contains("outside of the import paths"), "{}", err);
+}
+
+#[test]
+fn allowlist_applies_to_modules_which_are_already_loaded() {
+    let _ = ::env_logger::init();
+    let vm = make_vm(StdLib::Full);
+
+    let import_reference = || {
+        Compiler::new()
+            .implicit_prelude(false)
+            .run_expr::<()>(&vm, "test", "let r = import! std.reference in ()")
+            .map_err(|err| err.to_string())
+    };
+    import_reference().unwrap_or_else(|err| panic!("{}", err));
+
+    let mut allowed = FnvSet::default();
+    allowed.insert("std.lazy".to_string());
+    vm.get_macros()
+        .get("import")
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro")
+        .set_allowed_modules(Some(allowed));
+
+    let err = import_reference().unwrap_err();
+    assert!(err.contains("is not allowed to be imported"), "{}", err);
+}
@@ -7,18 +7,25 @@ extern crate serde_json;
 extern crate serde_state as serde;
 
 extern crate gluon;
+#[macro_use]
+extern crate gluon_vm;
 
 use std::fs::File;
 use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use futures::Future;
 
 use serde::ser::SerializeState;
 
 use gluon::{new_vm, Compiler};
-use gluon::vm::api::{Hole, OpaqueValue};
+use gluon::import::add_extern_module;
+use gluon::vm::ExternModule;
+use gluon::vm::api::{Hole, OpaqueValue, VmType, WithVM};
+use gluon::vm::gc::Traverseable;
 use gluon::vm::thread::{RootedThread, RootedValue, Thread, ThreadInternal};
-use gluon::vm::serialization::{DeSeed, SeSeed};
+use gluon::vm::types::VmInt;
+use gluon::vm::serialization::{deserialize_thread, serialize_thread, DeSeed, SeSeed};
 use gluon::vm::internal::Value;
 
 fn serialize_value(value: &Value) {
@@ -197,3 +204,187 @@ fn roundtrip_thread() {
         .unwrap_or_else(|err| panic!("{}", err));
     roundtrip(&thread, &value);
 }
+
+static RECORDED: AtomicUsize = AtomicUsize::new(0);
+
+fn record(x: VmInt) {
+    RECORDED.store(x as usize, Ordering::SeqCst);
+}
+
+fn load_snapshot_modules(vm: &Thread) {
+    add_extern_module(vm, "record", |thread| {
+        ExternModule::new(thread, primitive!(1 record))
+    });
+    Compiler::new()
+        .run_expr::<()>(
+            vm,
+            "load",
+            "let _ = import! record\nlet _ = import! std.thread\n()",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+}
+
+#[test]
+fn resume_suspended_thread_in_another_vm() {
+    let vm = new_vm();
+    load_snapshot_modules(&vm);
+    let expr = r#"
+        let thread = import! std.thread
+        let record = import! record
+        let f x =
+            let _ = thread.yield ()
+            record (x + 2)
+        thread.spawn (\_ -> f 40)
+        "#;
+    let (thread, _) = Compiler::new()
+        .run_expr::<RootedThread>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    // Run the thread until it yields
+    assert!(thread.resume().unwrap().is_not_ready());
+
+    let mut buffer = Vec::new();
+    serialize_thread(&thread, &mut serde_json::Serializer::new(&mut buffer)).unwrap();
+
+    let vm2 = new_vm();
+    load_snapshot_modules(&vm2);
+    let thread2 = deserialize_thread(&vm2, &mut serde_json::Deserializer::from_slice(&buffer))
+        .unwrap_or_else(|err| panic!("{}\n{}", err, String::from_utf8_lossy(&buffer)));
+
+    assert_eq!(RECORDED.load(Ordering::SeqCst), 0);
+    assert!(thread2.resume().unwrap().is_ready());
+    assert_eq!(RECORDED.load(Ordering::SeqCst), 42);
+}
+
+#[derive(Debug)]
+struct Counter(VmInt);
+
+impl Traverseable for Counter {}
+
+impl VmType for Counter {
+    type Type = Counter;
+}
+
+serializable_userdata! {
+    Counter,
+    name: "Counter",
+    to_bytes: |counter: &Counter| Ok(counter.0.to_string().into_bytes()),
+    from_bytes: |bytes: &[u8]| {
+        String::from_utf8(bytes.to_owned())
+            .map_err(|err| err.to_string())
+            .and_then(|s| s.parse().map_err(|err: std::num::ParseIntError| err.to_string()))
+            .map(Counter)
+    },
+}
+
+fn new_counter(x: VmInt) -> Counter {
+    Counter(x)
+}
+
+fn counter_value(counter: &Counter) -> VmInt {
+    counter.0
+}
+
+static RECORDED_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn record_counter(counter: &Counter) {
+    RECORDED_COUNTER.store(counter.0 as usize, Ordering::SeqCst);
+}
+
+fn load_counter_module(vm: &Thread) {
+    vm.register_type::<Counter>("Counter", &[])
+        .unwrap_or_else(|err| panic!("{}", err));
+    vm.register_serializable_userdata::<Counter>()
+        .unwrap_or_else(|err| panic!("{}", err));
+    add_extern_module(vm, "counter", |thread| {
+        ExternModule::new(
+            thread,
+            record! {
+                new => primitive!(1 new_counter),
+                value => primitive!(1 counter_value),
+                record => primitive!(1 record_counter)
+            },
+        )
+    });
+}
+
+#[test]
+fn roundtrip_shared_userdata() {
+    let thread = new_vm();
+    load_counter_module(&thread);
+    let expr = r#"
+        let counter = import! counter
+        let c = counter.new 1
+        { a = c, b = c }
+        "#;
+    let (value, _) = Compiler::new()
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&thread, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut buffer = Vec::new();
+    unsafe { value.get_value() }
+        .serialize_state(
+            &mut serde_json::Serializer::new(&mut buffer),
+            &SeSeed::new(),
+        )
+        .unwrap();
+    let buffer = String::from_utf8(buffer).unwrap();
+    // The userdata is only serialized once, the second field refers to it by id
+    assert_eq!(buffer.matches("\"Counter\"").count(), 1, "{}", buffer);
+
+    roundtrip(&thread, &value);
+}
+
+#[test]
+fn resume_suspended_thread_holding_userdata_in_another_vm() {
+    let vm = new_vm();
+    load_snapshot_modules(&vm);
+    load_counter_module(&vm);
+    let expr = r#"
+        let thread = import! std.thread
+        let counter = import! counter
+        let f c =
+            let _ = thread.yield ()
+            counter.record c
+        thread.spawn (\_ -> f (counter.new 42))
+        "#;
+    let (thread, _) = Compiler::new()
+        .run_expr::<RootedThread>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert!(thread.resume().unwrap().is_not_ready());
+
+    let mut buffer = Vec::new();
+    serialize_thread(&thread, &mut serde_json::Serializer::new(&mut buffer)).unwrap();
+
+    let vm2 = new_vm();
+    load_snapshot_modules(&vm2);
+    load_counter_module(&vm2);
+    let thread2 = deserialize_thread(&vm2, &mut serde_json::Deserializer::from_slice(&buffer))
+        .unwrap_or_else(|err| panic!("{}\n{}", err, String::from_utf8_lossy(&buffer)));
+
+    assert_eq!(RECORDED_COUNTER.load(Ordering::SeqCst), 0);
+    assert!(thread2.resume().unwrap().is_ready());
+    assert_eq!(RECORDED_COUNTER.load(Ordering::SeqCst), 42);
+}
+
+fn serialize_running_thread(thread: WithVM<()>) -> bool {
+    let mut buffer = Vec::new();
+    serialize_thread(thread.vm, &mut serde_json::Serializer::new(&mut buffer)).is_err()
+}
+
+#[test]
+fn serializing_the_running_thread_is_an_error() {
+    let vm = new_vm();
+    add_extern_module(&vm, "serialize_running_thread", |thread| {
+        ExternModule::new(thread, primitive!(1 serialize_running_thread))
+    });
+    let expr = r#"
+        let serialize_running_thread = import! serialize_running_thread
+        serialize_running_thread ()
+        "#;
+    let (is_err, _) = Compiler::new()
+        .run_expr::<bool>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(is_err);
+}
@@ -19,7 +19,10 @@ use gluon::vm::internal::Value;
 use gluon::vm::internal::Value::Int;
 use gluon::vm::stack::{StackFrame, State};
 use gluon::vm::channel::Sender;
-use gluon::{Compiler, Error};
+use gluon::vm::fixed_int::{I32, Word};
+#[cfg(target_pointer_width = "64")]
+use gluon::vm::fixed_int::U64;
+use gluon::{vm, Compiler, Error};
 
 
 test_expr!{ pass_function_value,
@@ -353,6 +356,16 @@ in (id { x = 1 }).x
 1i32
 }
 
+test_expr!{ polymorphic_field_access_with_different_layouts,
+r#"
+let get_x r = r.x
+let sum = get_x { x = 1 } #Int+ get_x { y = 0, x = 2 }
+let sum = sum #Int+ get_x { x = 3, z = 0 } #Int+ get_x { x = 4, y = 0, z = 0 }
+sum #Int+ get_x { a = 0, x = 5 } #Int+ get_x { x = 6, b = 0 } #Int+ get_x { y = 0, x = 7 }
+"#,
+28i32
+}
+
 test_expr!{ module_function,
 r#"
 let string_prim = import! std.string.prim
@@ -861,3 +874,309 @@ fn deep_clone_partial_application() {
         global_memory_with_closures
     );
 }
+
+#[test]
+fn thread_stats() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+
+    let before = vm.stats();
+    run_expr::<i32>(&vm, "let f x = x #Int+ 1 in f 2");
+    vm.collect();
+    let after = vm.stats();
+
+    assert!(after.instructions_executed > before.instructions_executed);
+    assert!(after.gc.collections >= before.gc.collections + 1);
+    assert_eq!(after.stack_depth, before.stack_depth);
+    assert!(after.gc.allocations.iter().any(|allocation| allocation.count > 0));
+}
+
+test_expr!{ io debug_stats,
+r#"
+let io = import! std.io.prim
+let { stats } = import! std.debug
+io.flat_map (\s -> io.wrap (0 #Int< s.instructions_executed)) (stats ())
+"#,
+true
+}
+
+test_expr!{ prelude weak_ref_to_reachable_value,
+r#"
+let { weak, get } = import! std.weak
+let array = import! std.array.prim
+let x = [1, 2]
+match get (weak x) with
+| Some y -> array.len y
+| None -> 0
+"#,
+2
+}
+
+#[test]
+fn weak_ref_is_cleared_after_collection() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+
+    let expr = r#"
+let { weak, get } = import! std.weak
+let w = weak [1, 2]
+\_ ->
+    match get w with
+    | Some _ -> True
+    | None -> False
+"#;
+    let (mut is_alive, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(()) -> bool>>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    vm.collect();
+    assert_eq!(is_alive.call(()), Ok(false));
+}
+
+#[test]
+fn heap_snapshot() {
+    use gluon::vm::heap_snapshot::HeapSnapshot;
+
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let snapshot = vm.heap_snapshot();
+
+    assert!(
+        snapshot
+            .roots
+            .iter()
+            .any(|root| root.name.starts_with("global "))
+    );
+    assert!(!snapshot.objects.is_empty());
+
+    let mut buffer = Vec::new();
+    snapshot.write(&mut buffer).unwrap();
+    assert_eq!(HeapSnapshot::read(&buffer[..]).unwrap(), snapshot);
+}
+
+#[test]
+fn disassemble_resolves_constants() {
+    use gluon::compiler_pipeline::Compileable;
+    use gluon::vm::disassembler::disassemble_module;
+
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let expr = r#"let f x = { x, y = "hello" } in f 1"#;
+    let compiled = expr.compile(
+        &mut Compiler::new().implicit_prelude(false),
+        &vm,
+        "test",
+        expr,
+        None,
+    ).unwrap_or_else(|err| panic!("{}", err));
+    let disassembly = disassemble_module(&compiled.module);
+
+    assert!(disassembly.contains("\"hello\""), "{}", disassembly);
+    assert!(disassembly.contains("{ x, y }"), "{}", disassembly);
+    assert!(disassembly.contains("function 0.0"), "{}", disassembly);
+}
+
+#[test]
+fn int_overflow_is_an_error() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i64>(&vm, "<top>", "9223372036854775807 #Int+ 1");
+    match result {
+        Err(Error::VM(vm::Error::Overflow(_))) => (),
+        _ => panic!("Expected an overflow error: {:?}", result),
+    }
+
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i64>(&vm, "<top>", "let zero = 0 in 1 #Int/ zero");
+    match result {
+        Err(Error::VM(vm::Error::DivideByZero)) => (),
+        _ => panic!("Expected a divide by zero error: {:?}", result),
+    }
+}
+
+test_expr!{ io catch_int_overflow,
+r#"
+let io = import! std.io.prim
+io.catch (io.flat_map (\x -> io.wrap (x #Int+ 1)) (io.wrap 9223372036854775807)) (\_ -> io.wrap 0)
+"#,
+0
+}
+
+test_expr!{ wrapping_int_arithmetic,
+r#"
+let int = import! std.int
+int.wrapping_add int.max_value 1 #Int== int.min_value
+    && int.saturating_add int.max_value 1 #Int== int.max_value
+"#,
+true
+}
+
+test_expr!{ fixed_int_literals,
+r#"
+let i32 = import! std.i32
+let { (+), (*) } = i32.num
+2i32 * 3i32 + -1i32
+"#,
+I32(5)
+}
+
+#[cfg(target_pointer_width = "64")]
+test_expr!{ fixed_int_wrapping,
+r#"
+let u64 = import! std.u64
+u64.wrapping_add 18446744073709551615u64 2u64
+"#,
+U64(1)
+}
+
+#[test]
+fn fixed_int_overflow_is_an_error() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<I32>(&vm, "<top>", "(import! std.i32).add 2147483647i32 1i32");
+    assert!(result.is_err());
+}
+
+#[test]
+fn marshal_fixed_ints() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    Compiler::new()
+        .load_script(&vm, "test", r#"\x y -> (import! std.word).add x y"#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let mut add: FunctionRef<fn(Word, Word) -> Word> = vm.get_global("test").unwrap();
+    assert_eq!(add.call(Word(1), Word(2)), Ok(Word(3)));
+}
+
+test_expr!{ int_bitwise_operations,
+r#"
+let int = import! std.int
+int.bit_and 12 10 #Int== 8
+    && int.bit_or 12 10 #Int== 14
+    && int.bit_xor 12 10 #Int== 6
+    && int.bit_not 0 #Int== -1
+    && int.shift_left 1 10 #Int== 1024
+    && int.shift_right -16 2 #Int== -4
+    && int.trailing_zeros 8 #Int== 3
+"#,
+true
+}
+
+#[test]
+fn int_bitwise_operations_depending_on_the_word_size() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let bits = ::std::mem::size_of::<gluon::vm::types::VmInt>() * 8;
+    let expr = format!(
+        r#"
+let int = import! std.int
+int.shift_right_logical -1 {highest_bit} #Int== 1
+    && int.leading_zeros 1 #Int== {highest_bit}
+"#,
+        highest_bit = bits - 1
+    );
+    assert!(run_expr::<bool>(&vm, &expr));
+}
+
+#[test]
+fn int_shift_out_of_range_is_an_error() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i64>(&vm, "<top>", "(import! std.int).shift_left 1 64");
+    match result {
+        Err(Error::VM(vm::Error::Overflow(_))) => (),
+        _ => panic!("Expected an overflow error: {:?}", result),
+    }
+}
+
+test_expr!{ int_rem_and_modulo,
+r#"
+let int = import! std.int
+int.rem -7 3 #Int== -1
+    && int.modulo -7 3 #Int== 2
+    && int.modulo 7 -3 #Int== -2
+    && int.rem int.min_value -1 #Int== 0
+"#,
+true
+}
+
+test_expr!{ int_checked_operations,
+r#"
+let { Bool, Option } = import! std.types
+let int = import! std.int
+let is_some_of x opt =
+    match opt with
+    | Some y -> x #Int== y
+    | None -> False
+let is_none opt =
+    match opt with
+    | Some _ -> False
+    | None -> True
+is_none (int.checked_add int.max_value 1)
+    && is_none (int.checked_div 1 0)
+    && is_none (int.checked_pow 2 64)
+    && is_none (int.checked_abs int.min_value)
+    && is_some_of 81 (int.checked_pow 3 4)
+"#,
+true
+}
+
+test_expr!{ int_float_conversions,
+r#"
+let { Bool, Option } = import! std.types
+let int = import! std.int
+let { Rounding } = int
+let float = import! std.float
+let is_some_of x opt =
+    match opt with
+    | Some y -> x #Int== y
+    | None -> False
+let is_none opt =
+    match opt with
+    | Some _ -> False
+    | None -> True
+int.to_float 3 #Float== 3.0
+    && is_some_of 3 (int.from_float Round 2.5)
+    && is_some_of -3 (int.from_float Floor -2.5)
+    && is_some_of 3 (int.from_float Ceil 2.1)
+    && is_some_of -2 (int.from_float Truncate -2.9)
+    && is_none (int.from_float Round float.nan)
+    && is_none (int.from_float Round 10000000000000000000.0)
+"#,
+true
+}
+
+test_expr!{ int_byte_conversions,
+r#"
+let { Bool, Option } = import! std.types
+let int = import! std.int
+let to_byte_is x i =
+    match int.to_byte i with
+    | Some y -> x #Byte== y
+    | None -> False
+int.from_byte 255b #Int== 255
+    && to_byte_is 65b 65
+    && (match int.to_byte 256 with | Some _ -> False | None -> True)
+"#,
+true
+}
+
+test_expr!{ int_parse_radix,
+r#"
+let { Bool, Result } = import! std.types
+let int = import! std.int
+let parses_to x r =
+    match r with
+    | Ok y -> x #Int== y
+    | Err _ -> False
+parses_to 255 (int.parse_radix "ff" 16)
+    && (match int.parse_radix "12" 2 with | Ok _ -> False | Err _ -> True)
+"#,
+true
+}
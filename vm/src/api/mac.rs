@@ -260,3 +260,77 @@ macro_rules! record_p {
         }
     }
 }
+
+/// Implements `Userdata` and `SerializableUserdata` for a type, forwarding
+/// `Userdata::serialize_userdata` to `SerializableUserdata::to_serialized`. Any other `Userdata`
+/// methods can be given in the optional `userdata` block. The type must still be registered with
+/// `Thread::register_serializable_userdata` in the virtual machine which deserializes it.
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate gluon_vm;
+///
+/// use gluon_vm::gc::Traverseable;
+///
+/// #[derive(Debug)]
+/// struct Counter(u8);
+///
+/// impl Traverseable for Counter {}
+///
+/// serializable_userdata! {
+///     Counter,
+///     name: "Counter",
+///     to_bytes: |counter: &Counter| Ok(vec![counter.0]),
+///     from_bytes: |bytes: &[u8]| if bytes.len() == 1 {
+///         Ok(Counter(bytes[0]))
+///     } else {
+///         Err("Expected a single byte".to_string())
+///     },
+///     userdata: {
+///         fn is_mutable(&self) -> bool {
+///             false
+///         }
+///     }
+/// }
+///
+/// fn main() {}
+/// ```
+#[macro_export]
+macro_rules! serializable_userdata {
+    ($ty: ty, name: $name: expr, to_bytes: $to_bytes: expr, from_bytes: $from_bytes: expr
+     $(,)*) => {
+        serializable_userdata! {
+            $ty,
+            name: $name,
+            to_bytes: $to_bytes,
+            from_bytes: $from_bytes,
+            userdata: {}
+        }
+    };
+    ($ty: ty, name: $name: expr, to_bytes: $to_bytes: expr, from_bytes: $from_bytes: expr,
+     userdata: { $($userdata: tt)* } $(,)*) => {
+        impl $crate::api::Userdata for $ty {
+            fn serialize_userdata(
+                &self,
+            ) -> Option<::std::result::Result<(&'static str, Vec<u8>), String>> {
+                Some($crate::api::SerializableUserdata::to_serialized(self))
+            }
+
+            $($userdata)*
+        }
+
+        impl $crate::api::SerializableUserdata for $ty {
+            fn name() -> &'static str {
+                $name
+            }
+
+            fn to_bytes(&self) -> ::std::result::Result<Vec<u8>, String> {
+                ($to_bytes)(self)
+            }
+
+            fn from_bytes(bytes: &[u8]) -> ::std::result::Result<Self, String> {
+                ($from_bytes)(bytes)
+            }
+        }
+    };
+}
@@ -6,8 +6,9 @@ use base::symbol::{Symbol, Symbols};
 use base::scoped_map::ScopedMap;
 use stack::{Lock, StackFrame};
 use vm::{self, Root, RootStr, RootedValue, Status, Thread};
-use value::{ArrayDef, ArrayRepr, Cloner, DataStruct, Def, ExternFunction, GcStr, Value, ValueArray};
-use thread::{self, Context, RootedThread, VmRoot};
+use value::{ArrayRepr, Cloner, DataStruct, Def, ExternFunction, GcStr, MoveUserdata, Value,
+            ValueArray};
+use thread::{Context, RootedThread, VmRoot};
 use thread::ThreadInternal;
 use base::types::{self, ArcType, Type};
 use types::{Instruction, VmIndex, VmInt, VmTag};
@@ -16,14 +17,19 @@ use compiler::{CompiledFunction, CompiledModule};
 use std::any::Any;
 use std::cell::Ref;
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::result::Result as StdResult;
 
-use futures::{Async, Future};
+use futures::{Async, Future, IntoFuture};
+use futures::sync::oneshot;
 
-pub use value::Userdata;
+use tokio_core::reactor::Handle;
+
+pub use value::{MoveUserdata, SerializableUserdata, Userdata};
 
 #[macro_use]
 pub mod mac;
@@ -449,6 +455,12 @@ pub trait Pushable<'vm>: AsyncPushable<'vm> {
 }
 
 /// Trait which allows rust values to be retrieved from the virtual machine
+///
+/// # Panics
+///
+/// The type of a gluon array does not include its length, so retrieving a fixed size array
+/// (`[T; N]`) panics if the gluon array does not have exactly `N` elements. Retrieve a `Vec<T>`
+/// instead if the length is not known to be correct.
 pub trait Getable<'vm>: Sized {
     /// unsafe version of from_value which allows references to the internal of GcPtr's to be
     /// extracted if `value` is rooted
@@ -461,7 +473,7 @@ pub trait Getable<'vm>: Sized {
 impl<'vm, T: vm::Userdata> Pushable<'vm> for T {
     fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
         let data: Box<vm::Userdata> = Box::new(self);
-        let userdata = context.alloc_with(thread, Move(data))?;
+        let userdata = context.alloc_with(thread, MoveUserdata(data))?;
         context.stack.push(Value::Userdata(userdata));
         Ok(())
     }
@@ -717,7 +729,7 @@ impl<'vm> Getable<'vm> for String {
 }
 impl<'vm> Pushable<'vm> for String {
     fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
-        <&str as Pushable>::push(&self, thread, context)
+        <&str as Pushable>::push(&*self, thread, context)
     }
 }
 
@@ -822,20 +834,131 @@ where
                 return Err(Error::Message("Push error".into()));
             }
         }
-        let result = {
-            let Context {
-                ref mut gc,
-                ref stack,
-                ..
-            } = *context;
-            let values = &stack[stack.len() - len..];
-            thread::alloc(gc, thread, stack, ArrayDef(values))?
-        };
-        for _ in 0..len {
-            context.stack.pop();
+        context.push_new_array(thread, len as usize)
+    }
+}
+
+impl<'vm, T> Getable<'vm> for Vec<T>
+where
+    T: Getable<'vm>,
+{
+    fn from_value(vm: &'vm Thread, value: Variants) -> Vec<T> {
+        match value.as_ref() {
+            ValueRef::Array(array) => array.iter().map(|v| T::from_value(vm, v)).collect(),
+            _ => ice!("ValueRef is not an Array"),
         }
-        context.stack.push(Value::Array(result));
-        Ok(())
+    }
+}
+
+macro_rules! replace_expr {
+    ($_t: tt $sub: expr) => { $sub };
+}
+
+macro_rules! define_fixed_arrays {
+    ($($len: expr => [$($elem: ident)+])+) => { $(
+        impl<T> VmType for [T; $len]
+        where
+            T: VmType,
+            T::Type: Sized,
+        {
+            type Type = [T::Type; $len];
+
+            fn make_type(thread: &Thread) -> ArcType {
+                Array::<T>::make_type(thread)
+            }
+        }
+
+        impl<'vm, T> Pushable<'vm> for [T; $len]
+        where
+            T: Pushable<'vm>,
+        {
+            #[allow(non_snake_case)]
+            fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+                let [$($elem),+] = self;
+                $(
+                    $elem.push(thread, context)?;
+                )+
+                context.push_new_array(thread, $len)
+            }
+        }
+
+        /// Panics if the gluon array does not have exactly the same length as the Rust array
+        impl<'vm, T> Getable<'vm> for [T; $len]
+        where
+            T: Getable<'vm>,
+        {
+            fn from_value(vm: &'vm Thread, value: Variants) -> [T; $len] {
+                match value.as_ref() {
+                    ValueRef::Array(array) => {
+                        if array.len() != $len {
+                            panic!("Expected an array of length {} but got {}", $len, array.len());
+                        }
+                        let mut iter = array.iter();
+                        [$(
+                            replace_expr!($elem T::from_value(vm, iter.next().unwrap()))
+                        ),+]
+                    }
+                    _ => ice!("ValueRef is not an Array"),
+                }
+            }
+        }
+    )+ }
+}
+
+define_fixed_arrays! {
+    1 => [A]
+    2 => [A B]
+    3 => [A B C]
+    4 => [A B C D]
+    5 => [A B C D E]
+    6 => [A B C D E F]
+    7 => [A B C D E F G]
+    8 => [A B C D E F G H]
+    9 => [A B C D E F G H I]
+    10 => [A B C D E F G H I J]
+    11 => [A B C D E F G H I J K]
+    12 => [A B C D E F G H I J K L]
+    13 => [A B C D E F G H I J K L M]
+    14 => [A B C D E F G H I J K L M N]
+    15 => [A B C D E F G H I J K L M N O]
+    16 => [A B C D E F G H I J K L M N O P]
+}
+
+impl<T: ?Sized + VmType> VmType for Box<T> {
+    type Type = T::Type;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        T::make_type(vm)
+    }
+
+    fn extra_args() -> VmIndex {
+        T::extra_args()
+    }
+}
+
+// `Pushable` can't be implemented for every `Box<T>` as it would overlap with the implementation
+// for `Userdata` (which other crates may implement for `Box<T>`), push the boxed value instead.
+impl<'vm> Pushable<'vm> for Box<str> {
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        <&str as Pushable>::push(&*self, thread, context)
+    }
+}
+
+impl<'vm, T: Getable<'vm>> Getable<'vm> for Box<T> {
+    fn from_value(vm: &'vm Thread, value: Variants) -> Box<T> {
+        Box::new(T::from_value(vm, value))
+    }
+}
+
+impl<'vm> Getable<'vm> for Box<str> {
+    fn from_value(vm: &'vm Thread, value: Variants) -> Box<str> {
+        String::from_value(vm, value).into_boxed_str()
+    }
+}
+
+impl<'vm, T: Getable<'vm>> Getable<'vm> for Box<[T]> {
+    fn from_value(vm: &'vm Thread, value: Variants) -> Box<[T]> {
+        Vec::from_value(vm, value).into_boxed_slice()
     }
 }
 
@@ -953,6 +1076,151 @@ impl<'vm, T: Getable<'vm>, E: Getable<'vm>> Getable<'vm> for StdResult<T, E> {
     }
 }
 
+fn make_map_type<K, V>(vm: &Thread) -> ArcType
+where
+    K: VmType,
+    V: VmType,
+{
+    // If `std.map` has not been loaded the type is left unresolved which makes any use of it fail
+    // to typecheck with a type error instead of aborting
+    let map_alias = vm.find_type_info("std.map.Map")
+        .map(|alias| alias.into_type())
+        .unwrap_or_else(|_| Type::ident(Symbol::from("std.map.Map")));
+    Type::app(map_alias, collect![K::make_type(vm), V::make_type(vm)])
+}
+
+/// Pushes `entries`, which must be sorted by their keys, as a balanced `std.map.Map`
+fn push_map<'vm, K, V>(
+    thread: &'vm Thread,
+    context: &mut Context,
+    entries: &mut [Option<(K, V)>],
+) -> Result<()>
+where
+    K: Pushable<'vm>,
+    V: Pushable<'vm>,
+{
+    if entries.is_empty() {
+        // Tip
+        return context.push_new_data(thread, 1, 0);
+    }
+    let mid = entries.len() / 2;
+    let (key, value) = entries[mid].take().unwrap();
+    key.push(thread, context)?;
+    value.push(thread, context)?;
+    let (left, right) = entries.split_at_mut(mid);
+    push_map(thread, context, left)?;
+    push_map(thread, context, &mut right[1..])?;
+    // Bin key value left right
+    context.push_new_data(thread, 0, 4)
+}
+
+/// Calls `f` with each entry of the `std.map.Map` in `value` in the order of its keys
+fn get_map_entries<'vm, K, V, F>(vm: &'vm Thread, value: Variants, f: &mut F)
+where
+    K: Getable<'vm>,
+    V: Getable<'vm>,
+    F: FnMut(K, V),
+{
+    match value.as_ref() {
+        ValueRef::Data(data) => if data.tag() == 0 {
+            get_map_entries(vm, data.get_variants(2).unwrap(), f);
+            f(
+                K::from_value(vm, data.get_variants(0).unwrap()),
+                V::from_value(vm, data.get_variants(1).unwrap()),
+            );
+            get_map_entries(vm, data.get_variants(3).unwrap(), f);
+        },
+        _ => ice!("ValueRef is not a Map"),
+    }
+}
+
+/// Maps are marshalled as `std.map.Map` which is ordered using the Rust `Ord` implementation of
+/// the keys. It must therefore agree with the `Ord` instance which is used with the map in gluon.
+/// `std.map` must have been imported before a map is marshalled, otherwise the map type is
+/// unknown and is reported as a type error.
+impl<K, V> VmType for BTreeMap<K, V>
+where
+    K: VmType,
+    K::Type: Sized,
+    V: VmType,
+    V::Type: Sized,
+{
+    type Type = BTreeMap<K::Type, V::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        make_map_type::<K, V>(vm)
+    }
+}
+
+impl<'vm, K, V> Pushable<'vm> for BTreeMap<K, V>
+where
+    K: Pushable<'vm> + Ord,
+    V: Pushable<'vm>,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        let mut entries: Vec<_> = self.into_iter().map(Some).collect();
+        push_map(thread, context, &mut entries)
+    }
+}
+
+impl<'vm, K, V> Getable<'vm> for BTreeMap<K, V>
+where
+    K: Getable<'vm> + Ord,
+    V: Getable<'vm>,
+{
+    fn from_value(vm: &'vm Thread, value: Variants) -> BTreeMap<K, V> {
+        let mut map = BTreeMap::new();
+        get_map_entries(vm, value, &mut |key, value| {
+            map.insert(key, value);
+        });
+        map
+    }
+}
+
+/// See the implementation for `BTreeMap`
+impl<K, V, S> VmType for HashMap<K, V, S>
+where
+    K: VmType,
+    K::Type: Sized,
+    V: VmType,
+    V::Type: Sized,
+{
+    type Type = HashMap<K::Type, V::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        make_map_type::<K, V>(vm)
+    }
+}
+
+impl<'vm, K, V, S> Pushable<'vm> for HashMap<K, V, S>
+where
+    K: Pushable<'vm> + Eq + Hash + Ord,
+    V: Pushable<'vm>,
+    S: BuildHasher,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        let mut entries: Vec<_> = self.into_iter().collect();
+        entries.sort_by(|l, r| l.0.cmp(&r.0));
+        let mut entries: Vec<_> = entries.into_iter().map(Some).collect();
+        push_map(thread, context, &mut entries)
+    }
+}
+
+impl<'vm, K, V, S> Getable<'vm> for HashMap<K, V, S>
+where
+    K: Getable<'vm> + Eq + Hash,
+    V: Getable<'vm>,
+    S: BuildHasher + Default,
+{
+    fn from_value(vm: &'vm Thread, value: Variants) -> HashMap<K, V, S> {
+        let mut map = HashMap::default();
+        get_map_entries(vm, value, &mut |key, value| {
+            map.insert(key, value);
+        });
+        map
+    }
+}
+
 /// Wrapper around a `Future` which can be used as a return value to let the virtual machine know
 /// that it must resolve the `Future` to receive the value.
 pub struct FutureResult<F>(pub F);
@@ -1100,6 +1368,111 @@ impl<'vm, T: Pushable<'vm>> Pushable<'vm> for IO<T> {
     }
 }
 
+type SpawnIO<T> = Box<FnMut(&Handle) -> Box<Future<Item = T, Error = String>> + Send>;
+
+/// An `IO` action which is completed by a future running on the event loop of the virtual machine
+/// (see `GlobalVmState::get_event_loop`). Returning `AsyncIO<T>` from a Rust function makes it a
+/// gluon function returning `IO T` where errors of the future are raised as `IO` exceptions.
+///
+/// ```rust,ignore
+/// fn delay(ms: VmInt) -> AsyncIO<VmInt> {
+///     AsyncIO::with_handle(move |handle| {
+///         Timeout::new(Duration::from_millis(ms as u64), handle)
+///             .into_future()
+///             .flatten()
+///             .map(move |()| ms)
+///     })
+/// }
+/// ```
+pub struct AsyncIO<T>(SpawnIO<T>);
+
+impl<T> AsyncIO<T>
+where
+    T: 'static,
+{
+    /// Creates an `IO` action from `future`
+    pub fn new<F>(future: F) -> AsyncIO<T>
+    where
+        F: Future<Item = T> + Send + 'static,
+        F::Error: fmt::Display,
+    {
+        AsyncIO::with_handle(move |_| future)
+    }
+
+    /// Creates an `IO` action from a function which creates the future once it is running on the
+    /// event loop. Futures which need a `Handle` to the event loop, such as timers and sockets,
+    /// are created this way.
+    pub fn with_handle<F, R>(f: F) -> AsyncIO<T>
+    where
+        F: FnOnce(&Handle) -> R + Send + 'static,
+        R: IntoFuture<Item = T>,
+        R::Future: 'static,
+        R::Error: fmt::Display,
+    {
+        let mut f = Some(f);
+        AsyncIO(Box::new(move |handle| {
+            let f = f.take().expect("AsyncIO may only be run once");
+            Box::new(f(handle).into_future().map_err(|err| err.to_string()))
+        }))
+    }
+}
+
+impl<T> VmType for AsyncIO<T>
+where
+    T: VmType,
+    T::Type: Sized,
+{
+    type Type = IO<T::Type>;
+    fn make_type(vm: &Thread) -> ArcType {
+        IO::<T>::make_type(vm)
+    }
+    fn extra_args() -> VmIndex {
+        1
+    }
+}
+
+impl<'vm, T> AsyncPushable<'vm> for AsyncIO<T>
+where
+    T: Pushable<'vm> + Send + 'static,
+{
+    fn async_push(
+        self,
+        thread: &'vm Thread,
+        context: &mut Context,
+        lock: Lock,
+    ) -> Result<Async<()>> {
+        let remote = match thread.global_env().get_event_loop() {
+            Some(remote) => remote,
+            None => {
+                context.stack.release_lock(lock);
+                return Err(Error::Message(
+                    "An event loop is required to run asynchronous IO actions".into(),
+                ));
+            }
+        };
+        let (sender, receiver) = oneshot::channel();
+        let mut spawn = self.0;
+        remote.spawn(move |handle| {
+            spawn(handle).then(move |result| {
+                let _ = sender.send(result);
+                Ok(())
+            })
+        });
+        let future = receiver
+            .map_err(|_| {
+                Error::Message("The event loop was dropped before the IO action completed".into())
+            })
+            .map(|result| match result {
+                Ok(value) => IO::Value(value),
+                Err(err) => IO::Exception(err),
+            });
+        unsafe {
+            context.return_future(future, lock);
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
 /// Type which represents an array in gluon
 /// Type implementing both `Pushable` and `Getable` of values of `V`.
 /// The actual value, `V` is not accessible directly but is only intended to be transferred between
@@ -1205,9 +1578,9 @@ impl<'vm, V> Getable<'vm> for OpaqueValue<RootedThread, V> {
 pub struct ArrayRef<'vm>(&'vm ValueArray);
 
 impl<'vm> ArrayRef<'vm> {
-    pub fn get(&self, index: usize) -> Option<Variants> {
+    pub fn get(&self, index: usize) -> Option<Variants<'vm>> {
         if index < self.0.len() {
-            unsafe { Some(Variants::with_root(self.0.get(index), self)) }
+            unsafe { Some(Variants::with_root(self.0.get(index), self.0)) }
         } else {
             None
         }
@@ -1220,6 +1593,10 @@ impl<'vm> ArrayRef<'vm> {
         self.0.as_slice()
     }
 
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
     pub fn iter(&self) -> ::value::VariantIter<'vm> {
         self.0.variant_iter()
     }
@@ -1344,6 +1721,11 @@ where
     }
 }
 
+/// The names of the fields of tuples in gluon
+const TUPLE_FIELDS: [&str; 12] = [
+    "_0", "_1", "_2", "_3", "_4", "_5", "_6", "_7", "_8", "_9", "_10", "_11"
+];
+
 macro_rules! define_tuple {
     ($($id: ident)+) => {
         impl<$($id),+> VmType for ($($id),+)
@@ -1388,19 +1770,7 @@ macro_rules! define_tuple {
                     $id.push(thread, context)?;
                 )+
                 let len = count!($($id),+);
-                let offset = context.stack.len() - len;
-                let value = thread::alloc(&mut context.gc,
-                                          thread,
-                                          &context.stack,
-                                          Def {
-                                              tag: 0,
-                                              elems: &context.stack[offset..],
-                                          })?;
-                for _ in 0..len {
-                    context.stack.pop();
-                }
-                context.stack.push(Value::Data(value)) ;
-                Ok(())
+                context.push_new_record(thread, &TUPLE_FIELDS[..len])
             }
         }
     }
@@ -1733,6 +2103,76 @@ impl<'vm, F> Getable<'vm> for Function<RootedThread, F> {
     }
 }
 
+/// A handle to a gluon function which owns the thread it is rooted in, letting it be stored in
+/// `Send + Sync + 'static` structures and called later from any Rust thread.
+///
+/// Each call runs on a new child thread so that calls made concurrently from different Rust
+/// threads do not interfere with each other.
+pub struct SharedFunction<F> {
+    function: OwnedFunction<F>,
+}
+
+impl<F> Clone for SharedFunction<F> {
+    fn clone(&self) -> Self {
+        SharedFunction {
+            function: self.function.clone(),
+        }
+    }
+}
+
+impl<F> fmt::Debug for SharedFunction<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SharedFunction({:?})", self.function.value)
+    }
+}
+
+impl<F> SharedFunction<F> {
+    /// Creates a `SharedFunction` from `function`. The function value is rooted in a new thread
+    /// which is owned by the returned handle so it stays alive until the handle is dropped.
+    pub fn new<T>(function: Function<T, F>) -> Result<SharedFunction<F>>
+    where
+        T: Deref<Target = Thread>,
+    {
+        let thread = function.value.vm().new_thread()?;
+        Ok(SharedFunction {
+            function: Function {
+                value: thread.root_value_with_self(*function.value),
+                _marker: PhantomData,
+            },
+        })
+    }
+
+    /// The thread which roots the function
+    pub fn thread(&self) -> &Thread {
+        self.function.value.vm()
+    }
+
+    fn spawn_call(&self) -> Result<OwnedFunction<F>> {
+        let thread = self.thread().new_thread()?;
+        Ok(Function {
+            value: thread.root_value_with_self(*self.function.value),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<F> VmType for SharedFunction<F>
+where
+    F: VmType,
+{
+    type Type = F::Type;
+    fn make_type(vm: &Thread) -> ArcType {
+        F::make_type(vm)
+    }
+}
+
+impl<'vm, F> Getable<'vm> for SharedFunction<F> {
+    fn from_value(vm: &'vm Thread, value: Variants) -> Self {
+        SharedFunction::new(OwnedFunction::<F>::from_value(vm, value))
+            .unwrap_or_else(|err| panic!("Unable to create a thread: {}", err))
+    }
+}
+
 /// Trait which represents a function
 pub trait FunctionType {
     /// Returns how many arguments the function needs to be provided to call it
@@ -1950,6 +2390,28 @@ impl<'vm, T, $($args,)* R> Function<T, fn($($args),*) -> R>
         })
     }
 }
+
+impl<$($args,)* R> SharedFunction<fn($($args),*) -> R>
+    where $($args: for<'vm> Pushable<'vm>,)*
+          R: VmType + for<'x> Getable<'x> + Send + 'static,
+{
+    #[allow(non_snake_case)]
+    pub fn call(&self $(, $args: $args)*) -> Result<R> {
+        self.spawn_call()?.call($($args),*)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn call_async(
+        &self
+        $(, $args: $args)*
+        ) -> Box<Future<Item = R, Error = Error> + Send + 'static>
+    {
+        match self.spawn_call() {
+            Ok(mut function) => function.call_async($($args),*),
+            Err(err) => Box::new(::futures::failed(err)),
+        }
+    }
+}
     )
 }
 
@@ -0,0 +1,190 @@
+//! Arbitrary-precision integers exposed to gluon as the `BigInt` type.
+//!
+//! Values are stored as userdata wrapping `num_bigint::BigInt` which can be passed to and
+//! returned from Rust functions directly.
+extern crate num_bigint;
+extern crate num_traits;
+
+pub use self::num_bigint::BigInt;
+
+use std::result::Result as StdResult;
+
+use self::num_traits::{pow, Signed, ToPrimitive, Zero};
+
+use {ExternModule, Result, Variants};
+use api::{Getable, RuntimeResult, Userdata, ValueRef, VmType};
+use gc::{Gc, GcPtr, Traverseable};
+use thread::Thread;
+use types::VmInt;
+use value::{Cloner, MoveUserdata};
+
+impl Userdata for BigInt {
+    fn deep_clone(&self, deep_cloner: &mut Cloner) -> Result<GcPtr<Box<Userdata>>> {
+        let data: Box<Userdata> = Box::new(self.clone());
+        deep_cloner.gc().alloc(MoveUserdata(data))
+    }
+
+    fn is_mutable(&self) -> bool {
+        false
+    }
+}
+
+impl Traverseable for BigInt {
+    fn traverse(&self, _: &mut Gc) {}
+}
+
+impl VmType for BigInt {
+    type Type = BigInt;
+}
+
+impl<'vm> Getable<'vm> for BigInt {
+    fn from_value(_: &'vm Thread, value: Variants) -> Self {
+        match value.as_ref() {
+            ValueRef::Userdata(data) => match data.downcast_ref::<BigInt>() {
+                Some(i) => i.clone(),
+                None => ice!("ValueRef is not a BigInt"),
+            },
+            _ => ice!("ValueRef is not a BigInt"),
+        }
+    }
+}
+
+fn check_radix(radix: VmInt) -> StdResult<u32, String> {
+    if 2 <= radix && radix <= 36 {
+        Ok(radix as u32)
+    } else {
+        Err(format!("Radix must be between 2 and 36 but got {}", radix))
+    }
+}
+
+fn from_int(i: VmInt) -> BigInt {
+    BigInt::from(i as i64)
+}
+
+fn to_int(i: &BigInt) -> Option<VmInt> {
+    i.to_i64().map(|i| i as VmInt)
+}
+
+fn from_float(f: f64) -> Option<BigInt> {
+    // Truncates towards zero like `f as i64`
+    if f.is_finite() {
+        BigInt::parse_bytes(format!("{:.0}", f.trunc()).as_bytes(), 10)
+    } else {
+        None
+    }
+}
+
+fn to_float(i: &BigInt) -> f64 {
+    i.to_f64().unwrap_or(::std::f64::NAN)
+}
+
+fn add(l: &BigInt, r: &BigInt) -> BigInt {
+    l + r
+}
+
+fn sub(l: &BigInt, r: &BigInt) -> BigInt {
+    l - r
+}
+
+fn mul(l: &BigInt, r: &BigInt) -> BigInt {
+    l * r
+}
+
+fn div(l: &BigInt, r: &BigInt) -> RuntimeResult<BigInt, &'static str> {
+    if r.is_zero() {
+        RuntimeResult::Panic("Attempted to divide by zero")
+    } else {
+        RuntimeResult::Return(l / r)
+    }
+}
+
+fn rem(l: &BigInt, r: &BigInt) -> RuntimeResult<BigInt, &'static str> {
+    if r.is_zero() {
+        RuntimeResult::Panic("Attempted to calculate the remainder with a divisor of zero")
+    } else {
+        RuntimeResult::Return(l % r)
+    }
+}
+
+fn negate(i: &BigInt) -> BigInt {
+    -i
+}
+
+fn abs(i: &BigInt) -> BigInt {
+    i.abs()
+}
+
+fn signum(i: &BigInt) -> VmInt {
+    i.signum().to_i64().unwrap() as VmInt
+}
+
+fn pow_(base: &BigInt, exp: VmInt) -> RuntimeResult<BigInt, String> {
+    if exp < 0 {
+        RuntimeResult::Panic(format!("Negative exponent {}", exp))
+    } else {
+        RuntimeResult::Return(pow(base.clone(), exp as usize))
+    }
+}
+
+fn eq(l: &BigInt, r: &BigInt) -> bool {
+    l == r
+}
+
+fn lt(l: &BigInt, r: &BigInt) -> bool {
+    l < r
+}
+
+fn show(i: &BigInt) -> String {
+    i.to_string()
+}
+
+fn to_str_radix(i: &BigInt, radix: VmInt) -> RuntimeResult<String, String> {
+    check_radix(radix).map(|radix| i.to_str_radix(radix)).into()
+}
+
+fn parse(s: &str) -> StdResult<BigInt, ()> {
+    s.parse().map_err(|_| ())
+}
+
+fn parse_radix(s: &str, radix: VmInt) -> RuntimeResult<StdResult<BigInt, ()>, String> {
+    check_radix(radix)
+        .map(|radix| BigInt::parse_bytes(s.as_bytes(), radix).ok_or(()))
+        .into()
+}
+
+mod std {
+    pub mod bigint {
+        pub use bigint as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    vm.register_type::<BigInt>("BigInt", &[])?;
+
+    ExternModule::new(
+        vm,
+        record!{
+            from_int => primitive!(1 std::bigint::prim::from_int),
+            to_int => primitive!(1 std::bigint::prim::to_int),
+            from_float => primitive!(1 std::bigint::prim::from_float),
+            to_float => primitive!(1 std::bigint::prim::to_float),
+            add => primitive!(2 std::bigint::prim::add),
+            sub => primitive!(2 std::bigint::prim::sub),
+            mul => primitive!(2 std::bigint::prim::mul),
+            div => primitive!(2 std::bigint::prim::div),
+            rem => primitive!(2 std::bigint::prim::rem),
+            negate => primitive!(1 std::bigint::prim::negate),
+            abs => primitive!(1 std::bigint::prim::abs),
+            signum => primitive!(1 std::bigint::prim::signum),
+            pow => named_primitive!(2, "std.bigint.prim.pow", std::bigint::prim::pow_),
+            eq => primitive!(2 std::bigint::prim::eq),
+            lt => primitive!(2 std::bigint::prim::lt),
+            show => primitive!(1 std::bigint::prim::show),
+            to_str_radix => primitive!(2 std::bigint::prim::to_str_radix),
+            parse => primitive!(1 std::bigint::prim::parse),
+            parse_radix => primitive!(2 std::bigint::prim::parse_radix)
+        },
+    )
+}
@@ -1,6 +1,6 @@
 use std::ops::{Deref, DerefMut};
 use interner::InternedStr;
-use base::ast::{DisplayEnv, Literal, Typed, TypedIdent};
+use base::ast::{DisplayEnv, FixedInt, Literal, Typed, TypedIdent};
 use base::resolve;
 use base::kind::{ArcKind, KindEnv};
 use base::types::{self, Alias, ArcType, BuiltinType, RecordSelector, Type, TypeEnv};
@@ -12,6 +12,7 @@ use core::{self, CExpr, Expr, Pattern};
 use types::*;
 use vm::GlobalVmState;
 use source_map::{LocalMap, SourceMap};
+use peephole;
 use self::Variable::*;
 
 use {Error, Result};
@@ -172,6 +173,7 @@ impl FunctionEnvs {
     fn end_function(&mut self, compiler: &mut Compiler, current_line: Line) -> FunctionEnv {
         compiler.stack_types.exit_scope();
         compiler.stack_constructors.exit_scope();
+        peephole::optimize(&mut self.function);
         let instructions = self.function.instructions.len();
 
         if compiler.emit_debug_info {
@@ -605,6 +607,8 @@ impl<'a> Compiler<'a> {
             Expr::Const(ref lit, _) => match *lit {
                 Literal::Int(i) => function.emit(PushInt(i as isize)),
                 Literal::Byte(b) => function.emit(PushByte(b)),
+                // Fixed-width integers are stored as the bit pattern in an `Int`
+                Literal::FixedInt(i) => function.emit(PushInt(fixed_int_bits(i)?)),
                 Literal::Float(f) => function.emit(PushFloat(f)),
                 Literal::String(ref s) => function.emit_string(self.intern(&s)?),
                 Literal::Char(c) => function.emit(PushInt(c as isize)),
@@ -969,3 +973,20 @@ impl<'a> Compiler<'a> {
         Ok((function_index, free_vars, function))
     }
 }
+
+/// Returns the bit pattern of `i` as an `Int`, which is only as wide as a pointer
+fn fixed_int_bits(i: FixedInt) -> Result<isize> {
+    let fits = match i {
+        FixedInt::I32(_) => true,
+        FixedInt::U64(_) => cfg!(target_pointer_width = "64"),
+        FixedInt::Word(w) => w <= usize::max_value() as u64,
+    };
+    if fits {
+        Ok(i.to_bits() as isize)
+    } else {
+        Err(Error::Message(format!(
+            "The literal `{}` does not fit in a word on this target",
+            i
+        )))
+    }
+}
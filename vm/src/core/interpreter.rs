@@ -713,12 +713,15 @@ impl<'a, 'e> Compiler<'a, 'e> {
             l.as_ref().map(|l| l.as_ref()),
             r.as_ref().map(|r| r.as_ref()),
         ) {
-            (Some(&Expr::Const(Literal::Int(l), ..)), Some(&Expr::Const(Literal::Int(r), ..))) => {
-                let f = binop!();
+            // Overflowing operations are left to fail at runtime
+            (Some(&Expr::Const(Literal::Int(l), ..)), Some(&Expr::Const(Literal::Int(r), ..)))
+                if checked_int_binop(id, l, r).is_some() =>
+            {
+                let result = checked_int_binop(id, l, r).unwrap();
                 Some(Reduced::Local(
                     self.allocator
                         .arena
-                        .alloc(Expr::Const(Literal::Int(f(l, r)), expr.span())),
+                        .alloc(Expr::Const(Literal::Int(result), expr.span())),
                 ))
             }
             (
@@ -748,6 +751,16 @@ impl<'a, 'e> Compiler<'a, 'e> {
     }
 }
 
+fn checked_int_binop(id: &TypedIdent<Symbol>, l: i64, r: i64) -> Option<i64> {
+    match id.name.as_ref().chars().last().unwrap() {
+        '+' => l.checked_add(r),
+        '-' => l.checked_sub(r),
+        '*' => l.checked_mul(r),
+        '/' => l.checked_div(r),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,6 +178,7 @@ impl<'a> Expr<'a> {
                 Literal::Char(c) => arena.text(format!("{:?}", c)),
                 Literal::Float(f) => arena.text(format!("{}", f)),
                 Literal::Int(i) => arena.text(format!("{}", i)),
+                Literal::FixedInt(i) => arena.text(format!("{}", i)),
                 Literal::String(ref s) => arena.text(format!("{:?}", s)),
             },
             Expr::Data(ref ctor, args, _, _) => match *ctor.typ {
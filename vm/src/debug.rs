@@ -1,12 +1,63 @@
+use std::time::Duration;
+
 use api::generic::A;
-use api::Generic;
+use api::{Generic, WithVM, IO};
 use thread::Thread;
+use types::VmInt;
 use {ExternModule, Result};
 
 fn trace(a: Generic<A>) {
     println!("{:?}", a.0);
 }
 
+field_decl! {
+    name, count, collections, minor_collections, total_pause, max_pause, live_bytes,
+    allocated_bytes, allocations, stack_depth, stack_size, instructions_executed
+}
+
+type AllocationCount = record_type!(name => String, count => VmInt);
+
+type Stats = record_type!(
+    collections => VmInt,
+    minor_collections => VmInt,
+    total_pause => f64,
+    max_pause => f64,
+    live_bytes => VmInt,
+    allocated_bytes => VmInt,
+    allocations => Vec<AllocationCount>,
+    stack_depth => VmInt,
+    stack_size => VmInt,
+    instructions_executed => VmInt
+);
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+fn stats(WithVM { vm, .. }: WithVM<()>) -> IO<Stats> {
+    let stats = vm.stats();
+    let allocations = stats
+        .gc
+        .allocations
+        .into_iter()
+        .map(|allocation| {
+            record_no_decl!(name => allocation.name, count => allocation.count as VmInt)
+        })
+        .collect();
+    IO::Value(record_no_decl!(
+        collections => stats.gc.collections as VmInt,
+        minor_collections => stats.gc.minor_collections as VmInt,
+        total_pause => seconds(stats.gc.total_pause),
+        max_pause => seconds(stats.gc.max_pause),
+        live_bytes => stats.gc.live_bytes as VmInt,
+        allocated_bytes => stats.gc.allocated_bytes as VmInt,
+        allocations => allocations,
+        stack_depth => stats.stack_depth as VmInt,
+        stack_size => stats.stack_size as VmInt,
+        instructions_executed => stats.instructions_executed as VmInt
+    ))
+}
+
 mod std {
     pub use debug;
 }
@@ -17,7 +68,8 @@ pub fn load(vm: &Thread) -> Result<ExternModule> {
     ExternModule::new(
         vm,
         record!{
-            trace => primitive!(1 std::debug::trace)
+            trace => primitive!(1 std::debug::trace),
+            stats => primitive!(1 std::debug::stats)
         },
     )
 }
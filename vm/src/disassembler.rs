@@ -0,0 +1,105 @@
+//! Human readable listings of the bytecode produced by `compiler::Compiler`.
+//!
+//! Each function is printed as a header followed by one instruction per line. Operands which
+//! refer to data stored in the function (string constants, record field lists, inner functions
+//! and upvariables) are resolved and printed as a comment after the instruction. The source line
+//! is printed whenever it changes from the previous instruction. Inner functions are printed after
+//! the function which contains them and are named by the path of indexes leading to them.
+use std::fmt::{self, Write};
+
+use itertools::Itertools;
+
+use compiler::{CompiledFunction, CompiledModule};
+use types::Instruction;
+use types::Instruction::*;
+
+/// Returns the disassembly of `module` and all of its functions
+pub fn disassemble_module(module: &CompiledModule) -> String {
+    let mut output = String::new();
+    if !module.module_globals.is_empty() {
+        writeln!(
+            output,
+            "globals: {}",
+            module.module_globals.iter().format(", ")
+        ).unwrap();
+    }
+    write_function(&mut output, "0", &module.function).unwrap();
+    output
+}
+
+/// Returns the disassembly of `function` and all of its inner functions
+pub fn disassemble(function: &CompiledFunction) -> String {
+    let mut output = String::new();
+    write_function(&mut output, "0", function).unwrap();
+    output
+}
+
+fn write_function<W: Write>(out: &mut W, path: &str, function: &CompiledFunction) -> fmt::Result {
+    writeln!(
+        out,
+        "function {} `{}` (args: {}, max stack size: {}, source: {})",
+        path,
+        function.id,
+        function.args,
+        function.max_stack_size,
+        function.debug_info.source_name
+    )?;
+
+    let mut previous_line = None;
+    for (index, instruction) in function.instructions.iter().enumerate() {
+        let line = function.debug_info.source_map.line(index);
+        let line_column = match line {
+            Some(line) if Some(line) != previous_line => line.to_string(),
+            _ => String::new(),
+        };
+        if line.is_some() {
+            previous_line = line;
+        }
+
+        let instruction_text = format!("{:?}", instruction);
+        match comment(path, function, instruction) {
+            Some(comment) => writeln!(
+                out,
+                "{:>6} {:>6}  {:<40} ; {}",
+                index,
+                line_column,
+                instruction_text,
+                comment
+            )?,
+            None => writeln!(out, "{:>6} {:>6}  {}", index, line_column, instruction_text)?,
+        }
+    }
+
+    for (index, inner) in function.inner_functions.iter().enumerate() {
+        writeln!(out, "")?;
+        write_function(out, &format!("{}.{}", path, index), inner)?;
+    }
+    Ok(())
+}
+
+fn comment(path: &str, function: &CompiledFunction, instruction: &Instruction) -> Option<String> {
+    match *instruction {
+        PushString(index) => function
+            .strings
+            .get(index as usize)
+            .map(|s| format!("{:?}", &s[..])),
+        GetField(index) => function
+            .strings
+            .get(index as usize)
+            .map(|s| format!(".{}", &s[..])),
+        ConstructRecord { record, .. } => function
+            .records
+            .get(record as usize)
+            .map(|fields| format!("{{ {} }}", fields.iter().format(", "))),
+        MakeClosure { function_index, .. } | NewClosure { function_index, .. } => function
+            .inner_functions
+            .get(function_index as usize)
+            .map(|inner| format!("function {}.{} `{}`", path, function_index, inner.id)),
+        PushUpVar(index) => function
+            .debug_info
+            .upvars
+            .get(index as usize)
+            .map(|upvar| upvar.name.clone()),
+        _ => None,
+    }
+}
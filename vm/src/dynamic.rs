@@ -1,10 +1,15 @@
 use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
 use std::ops::Deref;
 
 use base::resolve;
 use base::types::{ArcType, Type};
 
+use api::{Getable, VmType};
 use thread::{RootedValue, Thread, ThreadInternal, VmRoot};
+use value::Value;
+use {Error, Result, Variants};
 
 #[derive(Debug)]
 pub struct FieldIter<'a, T>
@@ -53,3 +58,193 @@ where
         resolved_type: resolve::remove_aliases_cow(&*thread.global_env().get_env(), typ),
     }
 }
+
+/// A rooted gluon value together with its type
+pub struct Dynamic<'vm, T>
+where
+    T: VmRoot<'vm>,
+{
+    value: RootedValue<T>,
+    typ: ArcType,
+    _marker: PhantomData<&'vm Thread>,
+}
+
+impl<'vm, T> fmt::Debug for Dynamic<'vm, T>
+where
+    T: VmRoot<'vm>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} : {}", self.value, self.typ)
+    }
+}
+
+impl<'vm, T> Dynamic<'vm, T>
+where
+    T: VmRoot<'vm>,
+{
+    pub fn new(value: RootedValue<T>, typ: ArcType) -> Dynamic<'vm, T> {
+        Dynamic {
+            value,
+            typ,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn value(&self) -> &RootedValue<T> {
+        &self.value
+    }
+
+    pub fn typ(&self) -> &ArcType {
+        &self.typ
+    }
+
+    /// Converts the value to `U`, returning an error if the type of the value is not `U`
+    pub fn get<'a, U>(&'a self) -> Result<U>
+    where
+        U: Getable<'a> + VmType,
+    {
+        get_checked(self.value.vm(), &*self.value, &self.typ)
+    }
+
+    /// Returns a `RecordRef` to the value if it is a record
+    pub fn into_record(self) -> Result<RecordRef<'vm, T>> {
+        RecordRef::new(self.value, self.typ)
+    }
+}
+
+/// A handle to a gluon record which allows its fields to be retrieved by name. Each field is
+/// checked against the type of the record when it is accessed so records can be inspected without
+/// defining a Rust type which mirrors them.
+pub struct RecordRef<'vm, T>
+where
+    T: VmRoot<'vm>,
+{
+    value: RootedValue<T>,
+    typ: ArcType,
+    _marker: PhantomData<&'vm Thread>,
+}
+
+impl<'vm, T> fmt::Debug for RecordRef<'vm, T>
+where
+    T: VmRoot<'vm>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} : {}", self.value, self.typ)
+    }
+}
+
+impl<'vm, T> RecordRef<'vm, T>
+where
+    T: VmRoot<'vm>,
+{
+    /// Creates a `RecordRef` from `value` which has the type `typ`. Returns an error if `typ` is
+    /// not a record type.
+    pub fn new(value: RootedValue<T>, typ: ArcType) -> Result<RecordRef<'vm, T>> {
+        let typ = resolve::remove_aliases(&*value.vm().get_env(), typ);
+        match *typ {
+            Type::Record(_) => Ok(RecordRef {
+                value,
+                typ,
+                _marker: PhantomData,
+            }),
+            _ => Err(Error::Message(format!("Expected a record but found `{}`", typ))),
+        }
+    }
+
+    pub fn value(&self) -> &RootedValue<T> {
+        &self.value
+    }
+
+    pub fn typ(&self) -> &ArcType {
+        &self.typ
+    }
+
+    /// Retrieves the field `name` as a `U`, returning an error if the field does not exist or
+    /// if its type is not `U`
+    pub fn get<'a, U>(&'a self, name: &str) -> Result<U>
+    where
+        U: Getable<'a> + VmType,
+    {
+        let (value, typ) = self.lookup(name)?;
+        get_checked(self.value.vm(), value, typ)
+    }
+
+    /// Retrieves the field `name` without converting it to a Rust value
+    pub fn get_dynamic(&self, name: &str) -> Result<Dynamic<'vm, T>> {
+        let (value, typ) = self.lookup(name)?;
+        Ok(Dynamic::new(
+            self.value.clone_vm().root_value_with_self(*value),
+            typ.clone(),
+        ))
+    }
+
+    /// Retrieves the field `name` which must itself be a record
+    pub fn get_record(&self, name: &str) -> Result<RecordRef<'vm, T>> {
+        self.get_dynamic(name)?.into_record()
+    }
+
+    /// Iterates over the name of each field together with its value
+    pub fn iter<'a>(&'a self) -> RecordIter<'a, 'vm, T> {
+        RecordIter {
+            record: self,
+            index: 0,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<(&Value, &ArcType)> {
+        let field = self.typ
+            .row_iter()
+            .enumerate()
+            .find(|&(_, field)| field.name.declared_name() == name);
+        match (field, &*self.value) {
+            (Some((index, field)), &Value::Data(ref data)) => Ok((&data.fields[index], &field.typ)),
+            _ => Err(Error::UndefinedField(self.typ.clone(), name.into())),
+        }
+    }
+}
+
+/// Iterator over the fields of a `RecordRef`
+pub struct RecordIter<'a, 'vm, T>
+where
+    T: VmRoot<'vm> + 'a,
+{
+    record: &'a RecordRef<'vm, T>,
+    index: usize,
+}
+
+impl<'a, 'vm, T> Iterator for RecordIter<'a, 'vm, T>
+where
+    T: VmRoot<'vm>,
+{
+    type Item = (&'a str, Dynamic<'vm, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let field = self.record.typ.row_iter().nth(self.index)?;
+        self.index += 1;
+        let value = match *self.record.value {
+            Value::Data(ref data) => data.fields[self.index - 1],
+            _ => return None,
+        };
+        Some((
+            field.name.declared_name(),
+            Dynamic::new(
+                self.record.value.clone_vm().root_value_with_self(value),
+                field.typ.clone(),
+            ),
+        ))
+    }
+}
+
+fn get_checked<'a, U>(thread: &'a Thread, value: &'a Value, actual: &ArcType) -> Result<U>
+where
+    U: Getable<'a> + VmType,
+{
+    use check::check_signature;
+
+    let expected = U::make_type(thread);
+    if check_signature(&*thread.get_env(), &expected, actual) {
+        unsafe { Ok(U::from_value(thread, Variants::new(value))) }
+    } else {
+        Err(Error::WrongType(expected, actual.clone()))
+    }
+}
@@ -0,0 +1,239 @@
+//! Fixed-width integer types.
+//!
+//! `I32`, `U64` and `Word` are distinct types to the typechecker but are represented as
+//! `Value::Int` in the virtual machine, holding the bit pattern of the value. Literals are written
+//! with a suffix (`10i32`, `10u64`, `10w`) and the types can be marshalled to and from Rust through
+//! the newtypes in this module, which makes them suitable for code that reads and writes binary
+//! formats. As with `Int`, arithmetic raises an error on overflow while the `wrapping_*` and
+//! `saturating_*` functions of each module provide the other behaviours.
+//!
+//! As `Value::Int` is only as wide as a pointer, `U64` is only available on targets with 64-bit
+//! pointers.
+use std::fmt;
+
+use {ExternModule, Result, Variants};
+use api::{Getable, Pushable, RuntimeResult, ValueRef, VmType};
+use thread::{Context, Thread};
+use types::VmInt;
+use value::Value;
+
+/// A 32-bit signed integer (`I32` in gluon)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct I32(pub i32);
+
+/// A 64-bit unsigned integer (`U64` in gluon)
+#[cfg(target_pointer_width = "64")]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct U64(pub u64);
+
+/// An unsigned integer with the same width as a pointer (`Word` in gluon)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Word(pub usize);
+
+impl I32 {
+    fn from_int(i: VmInt) -> Option<I32> {
+        let x = i as i32;
+        if x as VmInt == i {
+            Some(I32(x))
+        } else {
+            None
+        }
+    }
+
+    fn to_int(self) -> Option<VmInt> {
+        Some(self.0 as VmInt)
+    }
+}
+
+#[cfg(target_pointer_width = "64")]
+impl U64 {
+    fn from_int(i: VmInt) -> Option<U64> {
+        if i >= 0 {
+            Some(U64(i as u64))
+        } else {
+            None
+        }
+    }
+
+    fn to_int(self) -> Option<VmInt> {
+        if self.0 <= VmInt::max_value() as u64 {
+            Some(self.0 as VmInt)
+        } else {
+            None
+        }
+    }
+}
+
+impl Word {
+    fn from_int(i: VmInt) -> Option<Word> {
+        if i >= 0 {
+            Some(Word(i as usize))
+        } else {
+            None
+        }
+    }
+
+    fn to_int(self) -> Option<VmInt> {
+        if self.0 <= VmInt::max_value() as usize {
+            Some(self.0 as VmInt)
+        } else {
+            None
+        }
+    }
+}
+
+fn overflow<T: fmt::Display>(l: T, operation: &str, r: T) -> String {
+    format!("Integer overflow in `{} {} {}`", l, operation, r)
+}
+
+macro_rules! int_prim {
+    ($count: tt, $gluon_module: expr, $module: ident, $function: ident) => {
+        named_primitive!(
+            $count,
+            concat!("std.", $gluon_module, ".prim.", stringify!($function)),
+            $module::$function
+        )
+    };
+}
+
+macro_rules! fixed_int {
+    ($name: ident, $inner: ident, $module: ident, $gluon_module: expr, $load: ident) => {
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl VmType for $name {
+            type Type = Self;
+        }
+
+        impl<'vm> Pushable<'vm> for $name {
+            fn push(self, _: &'vm Thread, context: &mut Context) -> Result<()> {
+                context.stack.push(Value::Int(self.0 as VmInt));
+                Ok(())
+            }
+        }
+
+        impl<'vm> Getable<'vm> for $name {
+            fn from_value(_: &'vm Thread, value: Variants) -> Self {
+                match value.as_ref() {
+                    ValueRef::Int(i) => $name(i as $inner),
+                    _ => ice!("ValueRef is not an {}", stringify!($name)),
+                }
+            }
+        }
+
+        mod $module {
+            use super::*;
+
+            pub fn from_int(i: VmInt) -> Option<$name> {
+                $name::from_int(i)
+            }
+
+            pub fn to_int(i: $name) -> Option<VmInt> {
+                i.to_int()
+            }
+
+            pub fn add(l: $name, r: $name) -> RuntimeResult<$name, String> {
+                l.0.checked_add(r.0).map($name).ok_or_else(|| overflow(l, "+", r)).into()
+            }
+
+            pub fn sub(l: $name, r: $name) -> RuntimeResult<$name, String> {
+                l.0.checked_sub(r.0).map($name).ok_or_else(|| overflow(l, "-", r)).into()
+            }
+
+            pub fn mul(l: $name, r: $name) -> RuntimeResult<$name, String> {
+                l.0.checked_mul(r.0).map($name).ok_or_else(|| overflow(l, "*", r)).into()
+            }
+
+            pub fn div(l: $name, r: $name) -> RuntimeResult<$name, String> {
+                if r.0 == 0 {
+                    return RuntimeResult::Panic("Attempted to divide by zero".to_string());
+                }
+                l.0.checked_div(r.0).map($name).ok_or_else(|| overflow(l, "/", r)).into()
+            }
+
+            pub fn rem(l: $name, r: $name) -> RuntimeResult<$name, String> {
+                if r.0 == 0 {
+                    return RuntimeResult::Panic(
+                        "Attempted to calculate the remainder with a divisor of zero".to_string(),
+                    );
+                }
+                l.0.checked_rem(r.0).map($name).ok_or_else(|| overflow(l, "%", r)).into()
+            }
+
+            pub fn wrapping_add(l: $name, r: $name) -> $name {
+                $name(l.0.wrapping_add(r.0))
+            }
+
+            pub fn wrapping_sub(l: $name, r: $name) -> $name {
+                $name(l.0.wrapping_sub(r.0))
+            }
+
+            pub fn wrapping_mul(l: $name, r: $name) -> $name {
+                $name(l.0.wrapping_mul(r.0))
+            }
+
+            pub fn saturating_add(l: $name, r: $name) -> $name {
+                $name(l.0.saturating_add(r.0))
+            }
+
+            pub fn saturating_sub(l: $name, r: $name) -> $name {
+                $name(l.0.saturating_sub(r.0))
+            }
+
+            pub fn saturating_mul(l: $name, r: $name) -> $name {
+                $name(l.0.saturating_mul(r.0))
+            }
+
+            pub fn eq(l: $name, r: $name) -> bool {
+                l == r
+            }
+
+            pub fn lt(l: $name, r: $name) -> bool {
+                l < r
+            }
+
+            pub fn show(i: $name) -> String {
+                i.to_string()
+            }
+
+            pub fn parse(s: &str) -> ::std::result::Result<$name, ()> {
+                s.parse().map($name).map_err(|_| ())
+            }
+        }
+
+        pub fn $load(vm: &Thread) -> Result<ExternModule> {
+            ExternModule::new(
+                vm,
+                record! {
+                    min_value => $name($inner::min_value()),
+                    max_value => $name($inner::max_value()),
+                    from_int => int_prim!(1, $gluon_module, $module, from_int),
+                    to_int => int_prim!(1, $gluon_module, $module, to_int),
+                    add => int_prim!(2, $gluon_module, $module, add),
+                    sub => int_prim!(2, $gluon_module, $module, sub),
+                    mul => int_prim!(2, $gluon_module, $module, mul),
+                    div => int_prim!(2, $gluon_module, $module, div),
+                    rem => int_prim!(2, $gluon_module, $module, rem),
+                    wrapping_add => int_prim!(2, $gluon_module, $module, wrapping_add),
+                    wrapping_sub => int_prim!(2, $gluon_module, $module, wrapping_sub),
+                    wrapping_mul => int_prim!(2, $gluon_module, $module, wrapping_mul),
+                    saturating_add => int_prim!(2, $gluon_module, $module, saturating_add),
+                    saturating_sub => int_prim!(2, $gluon_module, $module, saturating_sub),
+                    saturating_mul => int_prim!(2, $gluon_module, $module, saturating_mul),
+                    eq => int_prim!(2, $gluon_module, $module, eq),
+                    lt => int_prim!(2, $gluon_module, $module, lt),
+                    show => int_prim!(1, $gluon_module, $module, show),
+                    parse => int_prim!(1, $gluon_module, $module, parse)
+                },
+            )
+        }
+    };
+}
+
+fixed_int!(I32, i32, i32_prim, "i32", load_i32);
+#[cfg(target_pointer_width = "64")]
+fixed_int!(U64, u64, u64_prim, "u64", load_u64);
+fixed_int!(Word, usize, word_prim, "word", load_word);
@@ -10,8 +10,12 @@ use std::cell::Cell;
 use std::any::{Any, TypeId};
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use itertools::Itertools;
 
 use base::fnv::FnvMap;
+use heap_snapshot::{HeapObject, HeapRoot, HeapSnapshot, Retainer};
 use interner::InternedStr;
 use types::VmIndex;
 use {Error, Result};
@@ -129,22 +133,246 @@ impl Generation {
     }
 }
 
-/// A mark and sweep garbage collector.
+/// The number of bytes which may be allocated in the nursery before a minor collection is run
+const DEFAULT_NURSERY_SIZE: usize = 1 << 18;
+
+/// Function which traverses the value pointed to by the pointer
+pub type TraverseFn = unsafe fn(*const (), &mut Gc);
+
+unsafe fn traverse_value<T: Traverseable>(ptr: *const (), gc: &mut Gc) {
+    (*(ptr as *const T)).traverse(gc)
+}
+
+/// A value which has survived a collection but which may refer to values allocated after it did
+/// so. These values must be traversed during a minor collection as they are not otherwise scanned
+/// (see `Gc::collect_minor`).
+#[derive(Clone, Copy)]
+struct Remembered {
+    header: *const GcHeader,
+    traverse: TraverseFn,
+}
+
+unsafe impl Send for Remembered {}
+
+impl fmt::Debug for Remembered {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Remembered {{ header: {:?} }}", self.header)
+    }
+}
+
+impl Remembered {
+    unsafe fn traverse(&self, gc: &mut Gc) {
+        let value = (self.header as *const u8).offset(GcHeader::value_offset() as isize);
+        (self.traverse)(value as *const (), gc)
+    }
+}
+
+/// Function which is run on a value after the mark phase of a collection
+pub type SweepFn = unsafe fn(*const (), &Gc);
+
+unsafe fn finalize_value<T: Finalize>(ptr: *const (), _: &Gc) {
+    (*(ptr as *const T)).finalize()
+}
+
+unsafe fn clear_dead_value<T: WeakRef>(ptr: *const (), gc: &Gc) {
+    (*(ptr as *const T)).clear_dead(gc)
+}
+
+/// A value registered with `Gc::register_finalizer` or `Gc::register_weak`
+#[derive(Clone, Copy)]
+struct SweepHook {
+    header: *const GcHeader,
+    hook: SweepFn,
+}
+
+unsafe impl Send for SweepHook {}
+
+impl fmt::Debug for SweepHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SweepHook {{ header: {:?} }}", self.header)
+    }
+}
+
+impl SweepHook {
+    unsafe fn run(&self, gc: &Gc) {
+        let value = (self.header as *const u8).offset(GcHeader::value_offset() as isize);
+        (self.hook)(value as *const (), gc)
+    }
+}
+
+/// Function which returns the number of bytes that the value pointed to by the pointer currently
+/// owns outside of the garbage collected heap
+pub type ExternalSizeFn = unsafe fn(*const ()) -> usize;
+
+/// Memory owned by a value outside of the garbage collected heap. See `DataDef::external_size_fn`.
+#[derive(Clone, Copy)]
+struct ExternalMemory {
+    header: *const GcHeader,
+    size: ExternalSizeFn,
+}
+
+unsafe impl Send for ExternalMemory {}
+
+impl fmt::Debug for ExternalMemory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExternalMemory {{ header: {:?} }}", self.header)
+    }
+}
+
+impl ExternalMemory {
+    unsafe fn size(&self) -> usize {
+        let value = (self.header as *const u8).offset(GcHeader::value_offset() as isize);
+        (self.size)(value as *const ())
+    }
+}
+
+/// Values which need to run some code once they become unreachable.
+///
+/// Unlike `Drop`, which is run while the values are freed in an arbitrary order, `finalize` is
+/// called before any value is freed which makes it safe to access the `GcPtr`s that the value
+/// contains.
+pub trait Finalize {
+    fn finalize(&self);
+}
+
+/// Values which refer to other values without keeping them alive (by not traversing them in
+/// `Traverseable::traverse`).
+pub trait WeakRef {
+    /// Called on each live, registered weak reference once a collection has marked every
+    /// reachable value. Must forget every value for which `gc.is_alive` returns `false` as those
+    /// values are about to be freed.
+    fn clear_dead(&self, gc: &Gc);
+}
+
+/// The state of a `Gc` created by `Gc::heap_walker`
+#[derive(Debug)]
+struct HeapWalk {
+    ids: FnvMap<*const GcHeader, usize>,
+    snapshot: HeapSnapshot,
+    /// The root or object whose references are currently being traversed
+    retainer: Retainer,
+}
+
+unsafe impl Send for HeapWalk {}
+
+impl HeapWalk {
+    /// Records a reference to `header` from the current retainer.
+    /// Returns true if the value had already been visited.
+    fn visit(&mut self, header: &GcHeader) -> bool {
+        let (id, visited) = match self.ids.entry(header as *const _) {
+            Entry::Occupied(entry) => (*entry.get(), true),
+            Entry::Vacant(entry) => {
+                let id = self.snapshot.objects.len();
+                entry.insert(id);
+                self.snapshot.objects.push(HeapObject {
+                    id: id,
+                    type_name: unsafe { (*header.type_info).name.clone() },
+                    size: GcHeader::value_offset() + header.value_size,
+                    retainer: self.retainer,
+                    references: Vec::new(),
+                });
+                (id, false)
+            }
+        };
+        match self.retainer {
+            Retainer::Root(root) => self.snapshot.roots[root].references.push(id),
+            Retainer::Object(object) => self.snapshot.objects[object].references.push(id),
+        }
+        visited
+    }
+}
+
+/// Statistics about the values allocated by, and the collections run by, a `Gc`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GcStats {
+    /// The number of full collections which have been run
+    pub collections: usize,
+    /// The number of minor collections which have been run
+    pub minor_collections: usize,
+    /// The total time spent collecting garbage
+    pub total_pause: Duration,
+    /// The longest time spent in a single collection
+    pub max_pause: Duration,
+    /// How many bytes that were still allocated after the last collection
+    pub live_bytes: usize,
+    /// How many bytes that are currently allocated
+    pub allocated_bytes: usize,
+    /// The number of values allocated of each type, sorted by the name of the type
+    pub allocations: Vec<AllocationCount>,
+}
+
+/// The number of allocations made of a type
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllocationCount {
+    /// The name of the Rust type or the field names if the value is a record
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct CollectionStats {
+    collections: usize,
+    minor_collections: usize,
+    total_pause: Duration,
+    max_pause: Duration,
+    live_bytes: usize,
+}
+
+/// A generational mark and sweep garbage collector.
+///
+/// Newly allocated values are placed in a nursery which is collected separately by minor
+/// collections (`collect_minor`). Values which survive a collection are moved to the old list and
+/// are only freed by a full collection (`collect`). As a minor collection does not traverse old
+/// values any old value which may be changed to refer to a young value must either be allocated
+/// with `MoveMutable` or be passed to `write_barrier` after it has been mutated.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
 #[cfg_attr(feature = "serde_derive", serde(deserialize_state = "::serialization::DeSeed"))]
 #[cfg_attr(feature = "serde_derive", serde(serialize_state = "::serialization::SeSeed"))]
 pub struct Gc {
-    /// Linked list of all objects allocted by this garbage collector.
+    /// Linked list of all objects allocted by this garbage collector which have not yet survived a
+    /// collection (the nursery).
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     values: Option<AllocPtr>,
+    /// Linked list of all objects which have survived at least one collection.
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    old_values: Option<AllocPtr>,
     /// How many bytes which is currently allocated
     allocated_memory: usize,
-    /// How many bytes this garbage collector can allocate before a collection is run
+    /// How many bytes which is currently allocated in the nursery
+    young_memory: usize,
+    /// How many bytes this garbage collector can allocate before a full collection is run
     collect_limit: usize,
+    /// How many bytes the nursery may contain before a minor collection is run
+    nursery_limit: usize,
+    /// Old values which were allocated with `MoveMutable`. Rebuilt on each full collection.
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    mutable_values: Vec<Remembered>,
+    /// Old values which have been passed to `write_barrier` since the last collection
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    remembered: Vec<Remembered>,
+    /// Values which have been passed to `register_finalizer` and which have not been finalized
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    finalizers: Vec<SweepHook>,
+    /// Values which have been passed to `register_weak`
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    weak_refs: Vec<SweepHook>,
+    /// Values which own memory outside of the heap which is included in `allocated_memory`
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    external_memory: Vec<ExternalMemory>,
+    /// `true` while a minor collection is running
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    minor_collection: bool,
+    #[cfg_attr(feature = "serde_derive", serde(skip))] collection_stats: CollectionStats,
+    /// Set if this `Gc` was created by `heap_walker`
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    walk: Option<Box<HeapWalk>>,
     /// The maximum number of bytes this garbage collector may contain
     memory_limit: usize,
-    #[cfg_attr(feature = "serde_derive", serde(skip))] type_infos: FnvMap<TypeId, Box<TypeInfo>>,
+    /// Keyed on whether the values are mutable as well, as values of the same type may be
+    /// allocated both with `Move` and `MoveMutable`
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    type_infos: FnvMap<(TypeId, bool), Box<TypeInfo>>,
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     record_infos: FnvMap<Vec<InternedStr>, Box<TypeInfo>>,
     /// The generation of a gc determines what values it needs to copy and what values it can
@@ -202,6 +430,40 @@ pub unsafe trait DataDef {
     fn fields(&self) -> Option<&[InternedStr]> {
         None
     }
+
+    /// Returns a function which traverses the allocated value if it can be mutated after it has
+    /// been allocated. Values with such a function are scanned on every minor collection.
+    fn mutable_traverse(&self) -> Option<TraverseFn> {
+        None
+    }
+
+    /// Returns the number of bytes that the value owns outside of the garbage collected heap
+    /// (such as memory reserved by a `Vec`) when it is allocated. These bytes are counted against
+    /// the memory limit until the value is freed. Values which return a non-zero size must also
+    /// return a function from `external_size_fn`.
+    fn external_size(&self) -> usize {
+        0
+    }
+
+    /// Returns a function which returns the number of bytes that the allocated value currently
+    /// owns outside of the garbage collected heap. The returned size is released once the value is
+    /// freed, so any growth after the allocation must be reported through
+    /// `Gc::add_external_memory`.
+    fn external_size_fn(&self) -> Option<ExternalSizeFn> {
+        None
+    }
+
+    /// Returns a function which is registered as if by `Gc::register_finalizer` when the value is
+    /// allocated
+    fn finalizer(&self) -> Option<SweepFn> {
+        None
+    }
+
+    /// Returns a function which is registered as if by `Gc::register_weak` when the value is
+    /// allocated
+    fn weak_ref(&self) -> Option<SweepFn> {
+        None
+    }
 }
 
 /// `DataDef` that moves its value directly into the pointer
@@ -218,18 +480,58 @@ unsafe impl<T> DataDef for Move<T> {
     }
 }
 
-#[derive(Debug)]
+/// `DataDef` which moves its value into the pointer like `Move` but which marks the value as
+/// mutable. Values which can change what they refer to after being allocated (such as userdata
+/// containing a `Mutex`) must be allocated using this so they are scanned by minor collections.
+pub struct MoveMutable<T>(pub T);
+
+unsafe impl<T> DataDef for MoveMutable<T>
+where
+    T: Traverseable,
+{
+    type Value = T;
+    fn size(&self) -> usize {
+        mem::size_of::<T>()
+    }
+    fn initialize(self, result: WriteOnly<T>) -> &mut T {
+        result.write(self.0)
+    }
+    fn mutable_traverse(&self) -> Option<TraverseFn> {
+        Some(traverse_value::<T>)
+    }
+}
+
 struct TypeInfo {
+    name: String,
+    /// The number of values allocated with this type info
+    allocations: Cell<usize>,
     drop: unsafe fn(*mut ()),
+    /// Set if values of this type are mutable (see `MoveMutable`)
+    traverse: Option<TraverseFn>,
     generation: Generation,
     fields: FnvMap<InternedStr, VmIndex>,
     fields_key: Arc<Vec<InternedStr>>,
 }
 
+impl fmt::Debug for TypeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypeInfo")
+            .field("name", &self.name)
+            .field("generation", &self.generation)
+            .field("mutable", &self.traverse.is_some())
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 struct GcHeader {
     next: Option<AllocPtr>,
     marked: Cell<bool>,
+    /// Set once the value has survived a collection
+    old: Cell<bool>,
+    /// Set if the value is in the remembered set of the garbage collector
+    remembered: Cell<bool>,
     value_size: usize,
     type_info: *const TypeInfo,
 }
@@ -254,6 +556,8 @@ impl AllocPtr {
                     type_info: type_info,
                     value_size: value_size,
                     marked: Cell::new(false),
+                    old: Cell::new(false),
+                    remembered: Cell::new(false),
                 },
             );
             AllocPtr { ptr: ptr }
@@ -471,6 +775,15 @@ where
     }
 }
 
+impl<T> Traverseable for MoveMutable<T>
+where
+    T: Traverseable,
+{
+    fn traverse(&self, gc: &mut Gc) {
+        self.0.traverse(gc)
+    }
+}
+
 impl<T: ?Sized> Traverseable for Box<T>
 where
     T: Traverseable,
@@ -583,7 +896,11 @@ where
     fn traverse(&self, gc: &mut Gc) {
         if !gc.mark(*self) {
             // Continue traversing if this ptr was not already marked
-            (**self).traverse(gc);
+            if gc.walk.is_some() {
+                gc.walk_references(*self);
+            } else {
+                (**self).traverse(gc);
+            }
         }
     }
 }
@@ -594,8 +911,19 @@ impl Gc {
     pub fn new(generation: Generation, memory_limit: usize) -> Gc {
         Gc {
             values: None,
+            old_values: None,
             allocated_memory: 0,
+            young_memory: 0,
             collect_limit: 100,
+            nursery_limit: DEFAULT_NURSERY_SIZE,
+            mutable_values: Vec::new(),
+            remembered: Vec::new(),
+            finalizers: Vec::new(),
+            weak_refs: Vec::new(),
+            external_memory: Vec::new(),
+            minor_collection: false,
+            collection_stats: CollectionStats::default(),
+            walk: None,
             memory_limit: memory_limit,
             type_infos: FnvMap::default(),
             record_infos: FnvMap::default(),
@@ -603,14 +931,107 @@ impl Gc {
         }
     }
 
+    /// Creates a garbage collector which, instead of marking the values it traverses, records
+    /// them and the references between them in a `HeapSnapshot`. Values of every generation are
+    /// recorded and no value is marked or freed.
+    ///
+    /// Values must be traversed through `walk_root` and the snapshot is retrieved with
+    /// `finish_walk`.
+    pub fn heap_walker() -> Gc {
+        let mut gc = Gc::new(Generation::default(), usize::MAX);
+        gc.walk = Some(Box::new(HeapWalk {
+            ids: FnvMap::default(),
+            snapshot: HeapSnapshot::default(),
+            retainer: Retainer::Root(0),
+        }));
+        gc
+    }
+
+    /// Adds a root named `name` to the snapshot. Every value traversed by `f` is recorded as
+    /// being referred to by that root.
+    ///
+    /// Panics if `self` was not created by `heap_walker`.
+    pub fn walk_root<F>(&mut self, name: &str, f: F)
+    where
+        F: FnOnce(&mut Gc),
+    {
+        {
+            let walk = self.walk.as_mut().expect("heap walker");
+            walk.retainer = Retainer::Root(walk.snapshot.roots.len());
+            walk.snapshot.roots.push(HeapRoot {
+                name: name.to_string(),
+                references: Vec::new(),
+            });
+        }
+        f(self)
+    }
+
+    /// Returns the snapshot recorded by a `Gc` created by `heap_walker`
+    pub fn finish_walk(mut self) -> HeapSnapshot {
+        self.walk.take().expect("heap walker").snapshot
+    }
+
+    fn walk_references<T: ?Sized + Traverseable>(&mut self, value: GcPtr<T>) {
+        let id = self.walk.as_ref().unwrap().ids[&(value.header() as *const _)];
+        let retainer = mem::replace(
+            &mut self.walk.as_mut().unwrap().retainer,
+            Retainer::Object(id),
+        );
+        (*value).traverse(self);
+        self.walk.as_mut().unwrap().retainer = retainer;
+    }
+
     pub fn allocated_memory(&self) -> usize {
         self.allocated_memory
     }
 
+    /// Returns statistics about the allocations and collections done by this garbage collector
+    pub fn stats(&self) -> GcStats {
+        let CollectionStats {
+            collections,
+            minor_collections,
+            total_pause,
+            max_pause,
+            live_bytes,
+        } = self.collection_stats;
+        let mut allocations: Vec<_> = self.type_infos
+            .values()
+            .chain(self.record_infos.values())
+            .map(|info| {
+                AllocationCount {
+                    name: info.name.clone(),
+                    count: info.allocations.get(),
+                }
+            })
+            .collect();
+        allocations.sort_by(|l, r| l.name.cmp(&r.name));
+        // Mutable and immutable values of the same type are counted separately
+        allocations.dedup_by(|r, l| if l.name == r.name {
+            l.count += r.count;
+            true
+        } else {
+            false
+        });
+        GcStats {
+            collections,
+            minor_collections,
+            total_pause,
+            max_pause,
+            live_bytes,
+            allocated_bytes: self.allocated_memory,
+            allocations,
+        }
+    }
+
     pub fn set_memory_limit(&mut self, memory_limit: usize) {
         self.memory_limit = memory_limit;
     }
 
+    /// Sets how many bytes may be allocated in the nursery before a minor collection is run
+    pub fn set_nursery_size(&mut self, nursery_size: usize) {
+        self.nursery_limit = nursery_size;
+    }
+
     pub fn generation(&self) -> Generation {
         self.generation
     }
@@ -657,13 +1078,8 @@ impl Gc {
         self.alloc(def)
     }
 
-    /// Allocates a new object.
-    pub fn alloc<D>(&mut self, def: D) -> Result<GcPtr<D::Value>>
-    where
-        D: DataDef,
-        D::Value: Sized + Any,
-    {
-        let size = def.size();
+    /// Returns an error if allocating `size` more bytes would exceed the memory limit
+    pub fn check_memory_limit(&self, size: usize) -> Result<()> {
         let needed = self.allocated_memory.saturating_add(size);
         if needed >= self.memory_limit {
             return Err(Error::OutOfMemory {
@@ -671,6 +1087,25 @@ impl Gc {
                 needed: needed,
             });
         }
+        Ok(())
+    }
+
+    /// Counts `size` bytes, which an already allocated value has grown by outside of the heap, as
+    /// allocated. The value must have been allocated with an `external_size_fn` which includes
+    /// these bytes so they are released once the value is freed. `check_memory_limit` should be
+    /// called before the value grows.
+    pub fn add_external_memory(&mut self, size: usize) {
+        self.allocated_memory += size;
+    }
+
+    /// Allocates a new object.
+    pub fn alloc<D>(&mut self, def: D) -> Result<GcPtr<D::Value>>
+    where
+        D: DataDef,
+        D::Value: Sized + Any,
+    {
+        let size = def.size();
+        self.check_memory_limit(size.saturating_add(def.external_size()))?;
         Ok(self.alloc_ignore_limit_(size, def))
     }
 
@@ -699,7 +1134,10 @@ impl Gc {
                 None => &**self.record_infos
                     .entry(fields.to_owned())
                     .or_insert(Box::new(TypeInfo {
+                        name: format!("{{ {} }}", fields.iter().format(", ")),
+                        allocations: Cell::new(0),
                         drop: drop::<D::Value>,
+                        traverse: None,
                         generation: self.generation,
                         fields: fields
                             .iter()
@@ -709,19 +1147,36 @@ impl Gc {
                         fields_key: Arc::new(fields.to_owned()),
                     })),
             },
-            None => match self.type_infos.entry(TypeId::of::<D::Value>()) {
-                Entry::Occupied(entry) => &**entry.get(),
-                Entry::Vacant(entry) => &**entry.insert(Box::new(TypeInfo {
-                    drop: drop::<D::Value>,
-                    generation: self.generation,
-                    fields: FnvMap::default(),
-                    fields_key: Arc::new(Vec::new()),
-                })),
-            },
+            None => {
+                let traverse = def.mutable_traverse();
+                match self.type_infos
+                    .entry((TypeId::of::<D::Value>(), traverse.is_some()))
+                {
+                    Entry::Occupied(entry) => &**entry.get(),
+                    Entry::Vacant(entry) => &**entry.insert(Box::new(TypeInfo {
+                        name: ::std::any::type_name::<D::Value>().to_string(),
+                        allocations: Cell::new(0),
+                        drop: drop::<D::Value>,
+                        traverse: traverse,
+                        generation: self.generation,
+                        fields: FnvMap::default(),
+                        fields_key: Arc::new(Vec::new()),
+                    })),
+                }
+            }
         };
+        unsafe {
+            let allocations = &(*type_info).allocations;
+            allocations.set(allocations.get() + 1);
+        }
+        let finalizer = def.finalizer();
+        let weak_ref = def.weak_ref();
+        let external_size = def.external_size();
+        let external_size_fn = def.external_size_fn();
         let mut ptr = AllocPtr::new::<D::Value>(type_info, size);
         ptr.next = self.values.take();
-        self.allocated_memory += ptr.size();
+        self.allocated_memory += ptr.size() + external_size;
+        self.young_memory += ptr.size() + external_size;
         unsafe {
             let p: *mut D::Value = D::Value::make_ptr(&def, ptr.value());
             let ret: *const D::Value = &*def.initialize(WriteOnly::new(p));
@@ -729,10 +1184,31 @@ impl Gc {
             // that the pointer was initialized
             assert!(ret == p);
             self.values = Some(ptr);
-            GcPtr { ptr: p }
+            let value = GcPtr { ptr: p };
+            if let Some(hook) = finalizer {
+                self.finalizers.push(SweepHook {
+                    header: value.header(),
+                    hook: hook,
+                });
+            }
+            if let Some(hook) = weak_ref {
+                self.weak_refs.push(SweepHook {
+                    header: value.header(),
+                    hook: hook,
+                });
+            }
+            if let Some(size) = external_size_fn {
+                self.external_memory.push(ExternalMemory {
+                    header: value.header(),
+                    size: size,
+                });
+            }
+            value
         }
     }
 
+    /// Runs a full collection if the collection limit has been reached, otherwise runs a minor
+    /// collection if the nursery is full.
     pub unsafe fn check_collect<R>(&mut self, roots: R) -> bool
     where
         R: Traverseable + CollectScope,
@@ -740,6 +1216,9 @@ impl Gc {
         if self.allocated_memory >= self.collect_limit {
             self.collect(roots);
             true
+        } else if self.young_memory >= self.nursery_limit {
+            self.collect_minor(roots);
+            true
         } else {
             false
         }
@@ -752,72 +1231,220 @@ impl Gc {
         R: Traverseable + CollectScope,
     {
         info!("Start collect {:?}", self.generation);
+        let start = Instant::now();
         roots.scope(self, |self_| {
             roots.traverse(self_);
             self_.sweep();
             self_.collect_limit = 2 * self_.allocated_memory;
-        })
+        });
+        self.collection_stats.collections += 1;
+        self.record_pause(start);
+    }
+
+    /// Does a mark and sweep collection of the values allocated since the last collection. Values
+    /// which have survived an earlier collection are assumed to be alive and are not traversed,
+    /// except for the values in the remembered set, so the time taken only depends on the number
+    /// of young values and not the size of the entire heap.
+    ///
+    /// This function is unsafe since roots need to cover all reachable object.
+    pub unsafe fn collect_minor<R>(&mut self, roots: R)
+    where
+        R: Traverseable + CollectScope,
+    {
+        info!("Start minor collect {:?}", self.generation);
+        let start = Instant::now();
+        // Must be set before `scope` is called as `scope` may traverse the roots of child threads
+        self.minor_collection = true;
+        roots.scope(self, |self_| {
+            roots.traverse(self_);
+            self_.traverse_remembered();
+            self_.sweep_young();
+        });
+        self.minor_collection = false;
+        self.collection_stats.minor_collections += 1;
+        self.record_pause(start);
+    }
+
+    fn record_pause(&mut self, start: Instant) {
+        let pause = start.elapsed();
+        let stats = &mut self.collection_stats;
+        stats.total_pause += pause;
+        if pause > stats.max_pause {
+            stats.max_pause = pause;
+        }
+        stats.live_bytes = self.allocated_memory;
+    }
+
+    /// Adds `value` to the remembered set if it has survived a collection. Must be called after
+    /// `value` has been mutated to refer to other values (unless it were allocated with
+    /// `MoveMutable` in which case it is always scanned).
+    pub fn write_barrier<T>(&mut self, value: GcPtr<T>)
+    where
+        T: Traverseable,
+    {
+        let header = value.header();
+        if header.old.get() && !header.remembered.get() {
+            header.remembered.set(true);
+            self.remembered.push(Remembered {
+                header: header,
+                traverse: traverse_value::<T>,
+            });
+        }
+    }
+
+    /// Registers `value` so that `Finalize::finalize` is called once the value has become
+    /// unreachable
+    pub fn register_finalizer<T: Finalize>(&mut self, value: GcPtr<T>) {
+        self.finalizers.push(SweepHook {
+            header: value.header(),
+            hook: finalize_value::<T>,
+        });
+    }
+
+    /// Registers `value` so that `WeakRef::clear_dead` is called after each collection which
+    /// `value` survives
+    pub fn register_weak<T: WeakRef>(&mut self, value: GcPtr<T>) {
+        self.weak_refs.push(SweepHook {
+            header: value.header(),
+            hook: clear_dead_value::<T>,
+        });
+    }
+
+    /// Returns `false` if `value` is about to be freed by the collection that is currently
+    /// running. Only meaningful inside of `Finalize::finalize` and `WeakRef::clear_dead`.
+    pub fn is_alive<T: ?Sized>(&self, value: GcPtr<T>) -> bool {
+        self.header_is_alive(value.header())
+    }
+
+    fn header_is_alive(&self, header: &GcHeader) -> bool {
+        header.marked.get() || header.generation().is_parent_of(self.generation())
+            || (self.minor_collection && header.old.get())
     }
 
     /// Marks the GcPtr
     /// Returns true if the pointer was already marked
     pub fn mark<T: ?Sized>(&mut self, value: GcPtr<T>) -> bool {
         let header = value.header();
+        if let Some(ref mut walk) = self.walk {
+            return walk.visit(header);
+        }
         // We only need to mark and traverse values from this garbage collectors generation
         if header.generation().is_parent_of(self.generation()) || header.marked.get() {
             true
+        } else if self.minor_collection && header.old.get()
+            && !self.generation().is_parent_of(header.generation())
+        {
+            // Old values are assumed to be alive during a minor collection
+            true
         } else {
             header.marked.set(true);
             false
         }
     }
 
+    unsafe fn traverse_remembered(&mut self) {
+        for i in 0..self.mutable_values.len() {
+            let remembered = self.mutable_values[i];
+            remembered.traverse(self);
+        }
+        for i in 0..self.remembered.len() {
+            let remembered = self.remembered[i];
+            remembered.traverse(self);
+        }
+    }
+
+    /// Runs the finalizers of unreachable values, lets weak references forget unreachable values
+    /// and releases the external memory of unreachable values. Must be called before any value is
+    /// freed.
+    unsafe fn run_sweep_hooks(&mut self) {
+        let (alive, dead): (Vec<_>, Vec<_>) = mem::replace(&mut self.finalizers, Vec::new())
+            .into_iter()
+            .partition(|finalizer| self.header_is_alive(&*finalizer.header));
+        self.finalizers = alive;
+        for finalizer in dead {
+            finalizer.run(self);
+        }
+
+        let mut weak_refs = mem::replace(&mut self.weak_refs, Vec::new());
+        weak_refs.retain(|weak| self.header_is_alive(&*weak.header));
+        for weak in &weak_refs {
+            weak.run(self);
+        }
+        self.weak_refs = weak_refs;
+
+        let (alive, dead): (Vec<_>, Vec<_>) = mem::replace(&mut self.external_memory, Vec::new())
+            .into_iter()
+            .partition(|external| self.header_is_alive(&*external.header));
+        self.external_memory = alive;
+        for external in dead {
+            self.allocated_memory -= external.size();
+        }
+    }
+
     /// Clears out any unmarked pointers and resets marked pointers.
     ///
     /// Unsafe as it is up to the caller to make sure that all reachable pointers have been marked
     pub unsafe fn sweep(&mut self) {
-        fn moving<T>(t: T) -> T {
-            t
+        self.run_sweep_hooks();
+        self.mutable_values.clear();
+        self.remembered.clear();
+
+        let young = self.values.take();
+        let old = self.old_values.take();
+        let mut survivors = None;
+        self.sweep_list(young, &mut survivors);
+        self.sweep_list(old, &mut survivors);
+        self.old_values = survivors;
+        self.young_memory = 0;
+    }
+
+    /// Clears out any unmarked pointers in the nursery and moves the marked pointers to the old
+    /// values.
+    ///
+    /// Unsafe as it is up to the caller to make sure that all reachable young pointers have been
+    /// marked
+    unsafe fn sweep_young(&mut self) {
+        self.run_sweep_hooks();
+        for remembered in self.remembered.drain(..) {
+            (*remembered.header).remembered.set(false);
         }
 
+        let young = self.values.take();
+        let mut survivors = self.old_values.take();
+        self.sweep_list(young, &mut survivors);
+        self.old_values = survivors;
+        self.young_memory = 0;
+    }
+
+    /// Frees every unmarked pointer in `list`. Marked pointers are unmarked and moved to
+    /// `survivors`.
+    fn sweep_list(&mut self, list: Option<AllocPtr>, survivors: &mut Option<AllocPtr>) {
         let mut count = 0;
         let mut free_count = 0;
 
-        let mut first = self.values.take();
-        {
-            // Pointer to the current pointer (if it exists)
-            let mut maybe_header = &mut first;
-            loop {
-                let mut free = false;
-                let mut replaced_next = None;
-                match *maybe_header {
-                    Some(ref mut header) => {
-                        // If the current pointer is not marked we take the rest of the list and
-                        // move it to `replaced_next`
-                        if !header.marked.get() {
-                            replaced_next = header.next.take();
-                            free = true;
-                        } else {
-                            header.marked.set(false);
-                        }
-                    }
-                    // Reached the end of the list
-                    None => break,
-                }
-                count += 1;
-                if free {
-                    free_count += 1;
-                    // Free the current pointer
-                    self.free(maybe_header.take());
-                    *maybe_header = replaced_next;
-                } else {
-                    // Just move to the next pointer
-                    maybe_header = &mut moving(maybe_header).as_mut().unwrap().next;
+        let mut current = list;
+        while let Some(mut ptr) = current {
+            count += 1;
+            current = ptr.next.take();
+            if ptr.marked.get() {
+                ptr.marked.set(false);
+                ptr.old.set(true);
+                ptr.remembered.set(false);
+                let traverse = unsafe { (*ptr.type_info).traverse };
+                if let Some(traverse) = traverse {
+                    self.mutable_values.push(Remembered {
+                        header: ptr.ptr,
+                        traverse: traverse,
+                    });
                 }
+                ptr.next = survivors.take();
+                *survivors = Some(ptr);
+            } else {
+                free_count += 1;
+                self.free(Some(ptr));
             }
         }
         info!("GC: Freed {} / Traversed {}", free_count, count);
-        self.values = first;
     }
 
     fn free(&mut self, header: Option<AllocPtr>) {
@@ -861,8 +1488,8 @@ mod tests {
 
 
 
-    fn object_count(gc: &Gc) -> usize {
-        let mut header: &GcHeader = match gc.values {
+    fn list_count(list: &Option<AllocPtr>) -> usize {
+        let mut header: &GcHeader = match *list {
             Some(ref x) => &**x,
             None => return 0,
         };
@@ -879,6 +1506,10 @@ mod tests {
         count
     }
 
+    fn object_count(gc: &Gc) -> usize {
+        list_count(&gc.values) + list_count(&gc.old_values)
+    }
+
 
     #[derive(Copy, Clone)]
     struct Data_ {
@@ -966,6 +1597,197 @@ mod tests {
         assert_eq!(object_count(&gc), 0);
     }
 
+    #[test]
+    fn minor_collection_promotes_survivors() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        let mut stack: Vec<Value> = Vec::new();
+        stack.push(new_data(gc.alloc(Def { elems: &[Int(1)] }).unwrap()));
+        gc.alloc(Def { elems: &[Int(2)] }).unwrap();
+        assert_eq!(list_count(&gc.values), 2);
+        unsafe {
+            gc.collect_minor(&mut *stack);
+        }
+        assert_eq!(list_count(&gc.values), 0);
+        assert_eq!(list_count(&gc.old_values), 1);
+
+        // Old values are not freed by minor collections
+        stack.pop();
+        unsafe {
+            gc.collect_minor(&mut *stack);
+        }
+        assert_eq!(object_count(&gc), 1);
+        unsafe {
+            gc.collect(&mut *stack);
+        }
+        assert_eq!(object_count(&gc), 0);
+    }
+
+    #[test]
+    fn write_barrier() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        let mut stack: Vec<Value> = Vec::new();
+        let mut old = gc.alloc(Def { elems: &[Int(1)] }).unwrap();
+        stack.push(new_data(old));
+        unsafe {
+            gc.collect_minor(&mut *stack);
+        }
+
+        // Make the old value refer to a young value
+        let young = new_data(gc.alloc(Def { elems: &[Int(2)] }).unwrap());
+        unsafe {
+            old.as_mut()[0] = young;
+        }
+        gc.write_barrier(old);
+        unsafe {
+            gc.collect_minor(&mut *stack);
+        }
+        assert_eq!(object_count(&gc), 2);
+        assert_eq!(old[0], young);
+    }
+
+    struct Slot(Cell<Option<GcPtr<Vec<Value>>>>);
+
+    impl Traverseable for Slot {
+        fn traverse(&self, gc: &mut Gc) {
+            if let Some(value) = self.0.get() {
+                value.traverse(gc)
+            }
+        }
+    }
+
+    struct SlotRoots(GcPtr<Slot>);
+
+    impl Traverseable for SlotRoots {
+        fn traverse(&self, gc: &mut Gc) {
+            self.0.traverse(gc)
+        }
+    }
+
+    impl CollectScope for SlotRoots {
+        fn scope<F>(&self, gc: &mut Gc, f: F)
+        where
+            F: FnOnce(&mut Gc),
+        {
+            f(gc)
+        }
+    }
+
+    #[test]
+    fn mutable_values_are_scanned_after_immutable_values_of_the_same_type() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.alloc(Move(Slot(Cell::new(None)))).unwrap();
+        let slot = gc.alloc(MoveMutable(Slot(Cell::new(None)))).unwrap();
+        unsafe {
+            gc.collect_minor(SlotRoots(slot));
+        }
+
+        // Refer to a young value without a write barrier
+        let young = gc.alloc(Def { elems: &[Int(2)] }).unwrap();
+        slot.0.set(Some(young));
+        unsafe {
+            gc.collect_minor(SlotRoots(slot));
+        }
+        assert_eq!(object_count(&gc), 2);
+        assert_eq!(young[0], Int(2));
+    }
+
+    struct WeakData {
+        value: Cell<Option<GcPtr<Vec<Value>>>>,
+    }
+
+    impl Traverseable for WeakData {}
+
+    impl WeakRef for WeakData {
+        fn clear_dead(&self, gc: &Gc) {
+            if let Some(value) = self.value.get() {
+                if !gc.is_alive(value) {
+                    self.value.set(None);
+                }
+            }
+        }
+    }
+
+    struct WeakRoots<'a> {
+        stack: &'a [Value],
+        weak: GcPtr<WeakData>,
+    }
+
+    impl<'a> Traverseable for WeakRoots<'a> {
+        fn traverse(&self, gc: &mut Gc) {
+            self.stack.traverse(gc);
+            self.weak.traverse(gc);
+        }
+    }
+
+    impl<'a> CollectScope for WeakRoots<'a> {
+        fn scope<F>(&self, gc: &mut Gc, f: F)
+        where
+            F: FnOnce(&mut Gc),
+        {
+            f(gc)
+        }
+    }
+
+    #[test]
+    fn weak_ref_is_cleared() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        let mut stack: Vec<Value> = Vec::new();
+        let target = gc.alloc(Def { elems: &[Int(1)] }).unwrap();
+        stack.push(new_data(target));
+        let weak = gc.alloc(Move(WeakData {
+            value: Cell::new(Some(target)),
+        })).unwrap();
+        gc.register_weak(weak);
+
+        unsafe {
+            gc.collect_minor(WeakRoots {
+                stack: &stack,
+                weak: weak,
+            });
+        }
+        assert!(weak.value.get().is_some());
+
+        stack.pop();
+        unsafe {
+            gc.collect(WeakRoots {
+                stack: &stack,
+                weak: weak,
+            });
+        }
+        assert!(weak.value.get().is_none());
+        assert_eq!(object_count(&gc), 1);
+    }
+
+    struct Finalizable {
+        value: GcPtr<Vec<Value>>,
+        finalized: Rc<Cell<Option<Value>>>,
+    }
+
+    impl Finalize for Finalizable {
+        fn finalize(&self) {
+            // Values referred to by `self` have not been freed yet
+            self.finalized.set(Some(self.value[0]));
+        }
+    }
+
+    #[test]
+    fn finalizer() {
+        let finalized = Rc::new(Cell::new(None));
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        let value = gc.alloc(Def { elems: &[Int(1)] }).unwrap();
+        let ptr = gc.alloc(Move(Finalizable {
+            value: value,
+            finalized: finalized.clone(),
+        })).unwrap();
+        gc.register_finalizer(ptr);
+
+        unsafe {
+            gc.collect(());
+        }
+        assert_eq!(finalized.get(), Some(Int(1)));
+        assert_eq!(object_count(&gc), 0);
+    }
+
     pub struct Dropable {
         dropped: Rc<Cell<bool>>,
     }
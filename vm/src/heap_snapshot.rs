@@ -0,0 +1,456 @@
+//! Snapshots of the values allocated by the garbage collector, used to find out what is keeping
+//! memory alive.
+//!
+//! A snapshot is created with `Thread::heap_snapshot` which walks every value reachable from the
+//! roots of a thread (its stack, the globals of the vm, rooted values, interned strings and
+//! threads). Every value is assigned an `id` and is retained by the first root or value that was
+//! found referring to it, following the retainers of a value therefore gives a path from a root
+//! which keeps the value alive.
+//!
+//! # Format
+//!
+//! `HeapSnapshot::write` writes the snapshot as lines of text which `HeapSnapshot::read` parses.
+//!
+//! ```text
+//! gluon heap snapshot 1
+//! root <index> <name>
+//! object <id> <size> <retainer> <type name>
+//! refs <id|r<index>> <id>...
+//! ```
+//!
+//! * `root` lines are written first, ordered by their index.
+//! * `object` lines are ordered by their id which are assigned in the order the objects are
+//! found. `size` is the number of bytes the object occupies (including the header used by the
+//! garbage collector) and `retainer` is either `r<index>` for a root or the id of an object
+//! listed before it.
+//! The type name is the rest of the line and may contain spaces.
+//! * `refs` lines list every reference from a root (`r<index>`) or an object to other objects.
+//!
+//! `HeapSnapshot::read` rejects snapshots which refer to roots or objects that do not exist.
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use base::fnv::FnvMap;
+
+/// What keeps a value alive
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Retainer {
+    /// Index into `HeapSnapshot::roots`
+    Root(usize),
+    /// Index into `HeapSnapshot::objects`
+    Object(usize),
+}
+
+impl fmt::Display for Retainer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Retainer::Root(index) => write!(f, "r{}", index),
+            Retainer::Object(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeapRoot {
+    pub name: String,
+    pub references: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeapObject {
+    pub id: usize,
+    pub type_name: String,
+    pub size: usize,
+    pub retainer: Retainer,
+    pub references: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeapSnapshot {
+    pub roots: Vec<HeapRoot>,
+    pub objects: Vec<HeapObject>,
+}
+
+/// The amount of memory that a root or object retains. See `HeapSnapshot::top_retainers`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetainedSize {
+    pub retainer: Retainer,
+    /// The name of the root or the type of the object
+    pub name: String,
+    pub size: usize,
+    pub objects: usize,
+}
+
+/// The change in the memory used by a type between two snapshots
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeDiff {
+    pub type_name: String,
+    pub count: isize,
+    pub size: isize,
+}
+
+const HEADER: &str = "gluon heap snapshot 1";
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<::std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn parse<T: ::std::str::FromStr>(s: Option<&str>, line: &str) -> io::Result<T> {
+    s.and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid_data(format!("Malformed line in heap snapshot: `{}`", line)))
+}
+
+fn parse_retainer(s: Option<&str>, line: &str) -> io::Result<Retainer> {
+    match s {
+        Some(s) if s.starts_with('r') => parse(Some(&s[1..]), line).map(Retainer::Root),
+        _ => parse(s, line).map(Retainer::Object),
+    }
+}
+
+impl HeapSnapshot {
+    /// The total number of bytes used by the objects in the snapshot
+    pub fn total_size(&self) -> usize {
+        self.objects.iter().map(|object| object.size).sum()
+    }
+
+    /// Returns the chain of retainers which keeps the object `id` alive, starting with the root.
+    pub fn retention_path(&self, id: usize) -> Vec<Retainer> {
+        let mut path = vec![Retainer::Object(id)];
+        let mut current = self.objects[id].retainer;
+        loop {
+            path.push(current);
+            match current {
+                Retainer::Root(_) => break,
+                Retainer::Object(id) => current = self.objects[id].retainer,
+            }
+        }
+        path.reverse();
+        path
+    }
+
+    /// Returns the name of a root or the type name of an object
+    pub fn name(&self, retainer: Retainer) -> &str {
+        match retainer {
+            Retainer::Root(index) => &self.roots[index].name,
+            Retainer::Object(id) => &self.objects[id].type_name,
+        }
+    }
+
+    /// Returns the `n` roots and objects which retain the most memory, largest first.
+    ///
+    /// The memory retained by a root or object is the size of every object whose retention path
+    /// passes through it.
+    pub fn top_retainers(&self, n: usize) -> Vec<RetainedSize> {
+        let mut object_sizes: Vec<(usize, usize)> = self.objects
+            .iter()
+            .map(|object| (object.size, 1))
+            .collect();
+        let mut root_sizes = vec![(0, 0); self.roots.len()];
+        // An object is always found after its retainer so iterating in reverse visits every
+        // object before its retainer
+        for object in self.objects.iter().rev() {
+            let (size, objects) = object_sizes[object.id];
+            let retained = match object.retainer {
+                Retainer::Root(index) => &mut root_sizes[index],
+                Retainer::Object(id) => &mut object_sizes[id],
+            };
+            retained.0 += size;
+            retained.1 += objects;
+        }
+
+        let mut retained: Vec<_> = root_sizes
+            .into_iter()
+            .enumerate()
+            .map(|(index, sizes)| (Retainer::Root(index), sizes))
+            .chain(
+                object_sizes
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, sizes)| (Retainer::Object(id), sizes)),
+            )
+            .map(|(retainer, (size, objects))| {
+                RetainedSize {
+                    retainer: retainer,
+                    name: self.name(retainer).to_string(),
+                    size: size,
+                    objects: objects,
+                }
+            })
+            .collect();
+        retained.sort_by(|l, r| r.size.cmp(&l.size));
+        retained.truncate(n);
+        retained
+    }
+
+    /// Compares the number of objects and bytes used by each type in `self` against `newer`.
+    /// Types whose memory use grew the most are returned first.
+    pub fn diff(&self, newer: &HeapSnapshot) -> Vec<TypeDiff> {
+        let mut types = FnvMap::default();
+        for object in &self.objects {
+            let entry = types.entry(&object.type_name[..]).or_insert((0, 0));
+            entry.0 -= 1;
+            entry.1 -= object.size as isize;
+        }
+        for object in &newer.objects {
+            let entry = types.entry(&object.type_name[..]).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += object.size as isize;
+        }
+        let mut diff: Vec<_> = types
+            .into_iter()
+            .filter(|&(_, (count, size))| count != 0 || size != 0)
+            .map(|(type_name, (count, size))| {
+                TypeDiff {
+                    type_name: type_name.to_string(),
+                    count: count,
+                    size: size,
+                }
+            })
+            .collect();
+        diff.sort_by(|l, r| {
+            r.size
+                .cmp(&l.size)
+                .then_with(|| l.type_name.cmp(&r.type_name))
+        });
+        diff
+    }
+
+    /// Writes the snapshot in the format described in the module documentation
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        for (index, root) in self.roots.iter().enumerate() {
+            writeln!(writer, "root {} {}", index, root.name)?;
+        }
+        for object in &self.objects {
+            writeln!(
+                writer,
+                "object {} {} {} {}",
+                object.id,
+                object.size,
+                object.retainer,
+                object.type_name
+            )?;
+        }
+        let references = self.roots
+            .iter()
+            .enumerate()
+            .map(|(index, root)| (Retainer::Root(index), &root.references))
+            .chain(
+                self.objects
+                    .iter()
+                    .map(|object| (Retainer::Object(object.id), &object.references)),
+            );
+        for (retainer, references) in references {
+            if !references.is_empty() {
+                write!(writer, "refs {}", retainer)?;
+                for id in references {
+                    write!(writer, " {}", id)?;
+                }
+                writeln!(writer, "")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a snapshot written by `write`
+    pub fn read<R: BufRead>(reader: R) -> io::Result<HeapSnapshot> {
+        let mut lines = reader.lines();
+        match lines.next() {
+            Some(line) => if line? != HEADER {
+                return Err(invalid_data("Expected a gluon heap snapshot"));
+            },
+            None => return Err(invalid_data("Expected a gluon heap snapshot")),
+        }
+
+        let mut snapshot = HeapSnapshot::default();
+        for line in lines {
+            let line = line?;
+            let mut parts = line.splitn(2, ' ');
+            let kind = parts.next().unwrap_or("");
+            let rest = parts.next().unwrap_or("");
+            match kind {
+                "root" => {
+                    let mut parts = rest.splitn(2, ' ');
+                    let index: usize = parse(parts.next(), &line)?;
+                    if index != snapshot.roots.len() {
+                        return Err(invalid_data(format!("Root {} is out of order", index)));
+                    }
+                    snapshot.roots.push(HeapRoot {
+                        name: parts.next().unwrap_or("").to_string(),
+                        references: Vec::new(),
+                    });
+                }
+                "object" => {
+                    let mut parts = rest.splitn(4, ' ');
+                    let id = parse(parts.next(), &line)?;
+                    if id != snapshot.objects.len() {
+                        return Err(invalid_data(format!("Object {} is out of order", id)));
+                    }
+                    let size = parse(parts.next(), &line)?;
+                    let retainer = parse_retainer(parts.next(), &line)?;
+                    // `retention_path` and `top_retainers` rely on every object being retained
+                    // by a known root or an object that was found before it
+                    let known_retainer = match retainer {
+                        Retainer::Root(index) => index < snapshot.roots.len(),
+                        Retainer::Object(retainer_id) => retainer_id < id,
+                    };
+                    if !known_retainer {
+                        return Err(invalid_data(format!(
+                            "Object {} is retained by unknown retainer {}",
+                            id,
+                            retainer
+                        )));
+                    }
+                    snapshot.objects.push(HeapObject {
+                        id: id,
+                        size: size,
+                        retainer: retainer,
+                        type_name: parts.next().unwrap_or("").to_string(),
+                        references: Vec::new(),
+                    });
+                }
+                "refs" => {
+                    let mut parts = rest.split(' ');
+                    let retainer = parse_retainer(parts.next(), &line)?;
+                    let references = parts
+                        .map(|id| parse(Some(id), &line))
+                        .collect::<io::Result<Vec<usize>>>()?;
+                    let target = match retainer {
+                        Retainer::Root(index) => snapshot
+                            .roots
+                            .get_mut(index)
+                            .map(|root| &mut root.references),
+                        Retainer::Object(id) => snapshot
+                            .objects
+                            .get_mut(id)
+                            .map(|object| &mut object.references),
+                    };
+                    match target {
+                        Some(target) => target.extend(references),
+                        None => {
+                            return Err(invalid_data(format!("Unknown retainer {}", retainer)))
+                        }
+                    }
+                }
+                "" => (),
+                _ => {
+                    return Err(invalid_data(format!(
+                        "Malformed line in heap snapshot: `{}`",
+                        line
+                    )))
+                }
+            }
+        }
+        let object_count = snapshot.objects.len();
+        let unknown_reference = snapshot
+            .roots
+            .iter()
+            .flat_map(|root| root.references.iter())
+            .chain(
+                snapshot
+                    .objects
+                    .iter()
+                    .flat_map(|object| object.references.iter()),
+            )
+            .find(|&&id| id >= object_count);
+        if let Some(id) = unknown_reference {
+            return Err(invalid_data(format!("Reference to unknown object {}", id)));
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> HeapSnapshot {
+        HeapSnapshot {
+            roots: vec![
+                HeapRoot {
+                    name: "stack".to_string(),
+                    references: vec![0, 2],
+                },
+            ],
+            objects: vec![
+                HeapObject {
+                    id: 0,
+                    type_name: "{ x, y }".to_string(),
+                    size: 32,
+                    retainer: Retainer::Root(0),
+                    references: vec![1],
+                },
+                HeapObject {
+                    id: 1,
+                    type_name: "String".to_string(),
+                    size: 24,
+                    retainer: Retainer::Object(0),
+                    references: vec![],
+                },
+                HeapObject {
+                    id: 2,
+                    type_name: "String".to_string(),
+                    size: 24,
+                    retainer: Retainer::Root(0),
+                    references: vec![1],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn write_and_read() {
+        let snapshot = snapshot();
+        let mut buffer = Vec::new();
+        snapshot.write(&mut buffer).unwrap();
+        assert_eq!(HeapSnapshot::read(&buffer[..]).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn read_rejects_unknown_ids() {
+        let read = |input: &str| HeapSnapshot::read(format!("{}\n{}", HEADER, input).as_bytes());
+
+        assert!(read("root 0 stack\nobject 0 32 r0 String\n").is_ok());
+        assert!(read("root 0 stack\nobject 0 32 r1 String\n").is_err());
+        assert!(read("root 0 stack\nobject 0 32 0 String\n").is_err());
+        assert!(read("root 0 stack\nobject 0 32 r0 String\nobject 1 32 2 String\n").is_err());
+        assert!(read("root 0 stack\nobject 0 32 r0 String\nrefs r0 1\n").is_err());
+        assert!(read("root 0 stack\nobject 0 32 r0 String\nrefs 0 3\n").is_err());
+    }
+
+    #[test]
+    fn top_retainers() {
+        let snapshot = snapshot();
+        let top = snapshot.top_retainers(2);
+        assert_eq!(top[0].retainer, Retainer::Root(0));
+        assert_eq!(top[0].size, 80);
+        assert_eq!(top[1].retainer, Retainer::Object(0));
+        assert_eq!(top[1].size, 56);
+        assert_eq!(top[1].objects, 2);
+        assert_eq!(
+            snapshot.retention_path(1),
+            [Retainer::Root(0), Retainer::Object(0), Retainer::Object(1)]
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let old = snapshot();
+        let mut new = snapshot();
+        new.objects.pop();
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff,
+            [
+                TypeDiff {
+                    type_name: "String".to_string(),
+                    count: -1,
+                    size: -24,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! Inline caches for `Instruction::GetField`.
+//!
+//! Accessing a field of a row-polymorphic record (`{ x : Int | r }`) requires looking up the
+//! field's offset in the field map of the record's layout. Since a single `GetField` instruction
+//! usually sees only a handful of distinct layouts, each instruction caches the offsets it has
+//! resolved, keyed on the identity of the layout (the `fields_key` shared by every record
+//! allocated with the same `TypeInfo` in `Gc::record_infos`). A hit reduces the access to a
+//! pointer comparison and an offset read.
+//!
+//! Once an instruction has seen more than `ENTRIES` layouts it is considered megamorphic and
+//! always falls back to looking the field up by name.
+//!
+//! Layouts are identified by address and may be freed together with the `Gc` that owns them, so
+//! every hit is verified against the record's field names before it is used. The cache is therefore
+//! only ever a hint and a stale entry degrades into a miss.
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use interner::InternedStr;
+use types::Instruction;
+use value::{DataStruct, Value};
+use gc::GcPtr;
+
+/// The number of layouts cached by a single `GetField` instruction
+const ENTRIES: usize = 4;
+
+#[derive(Default)]
+struct Entry {
+    /// The address of the layout's field names or 0 if the entry is empty
+    layout: AtomicUsize,
+    offset: AtomicUsize,
+}
+
+#[derive(Default)]
+struct FieldCache {
+    entries: [Entry; ENTRIES],
+    megamorphic: AtomicBool,
+}
+
+impl FieldCache {
+    fn lookup<'a>(&self, data: &'a GcPtr<DataStruct>, field: InternedStr) -> Option<&'a Value> {
+        let field_names = data.field_names();
+        let layout = field_names.as_ptr() as usize;
+
+        for entry in &self.entries {
+            let cached_layout = entry.layout.load(Ordering::Acquire);
+            if cached_layout == 0 {
+                break;
+            }
+            if cached_layout == layout {
+                let offset = entry.offset.load(Ordering::Relaxed);
+                if field_names.get(offset) == Some(&field) {
+                    return data.fields.get(offset);
+                }
+                break;
+            }
+        }
+
+        let offset = *data.field_map().get(&field)? as usize;
+        if !self.megamorphic.load(Ordering::Relaxed) {
+            self.insert(layout, offset);
+        }
+        data.fields.get(offset)
+    }
+
+    fn insert(&self, layout: usize, offset: usize) {
+        for entry in &self.entries {
+            // Store the offset first so that another thread which observes the layout also
+            // observes an offset which is valid for it
+            match entry.layout.load(Ordering::Acquire) {
+                0 => {
+                    entry.offset.store(offset, Ordering::Relaxed);
+                    if entry
+                        .layout
+                        .compare_and_swap(0, layout, Ordering::AcqRel) == 0
+                    {
+                        return;
+                    }
+                }
+                cached_layout if cached_layout == layout => {
+                    // A stale entry for a layout which has been freed and reallocated
+                    entry.offset.store(offset, Ordering::Relaxed);
+                    return;
+                }
+                _ => (),
+            }
+        }
+        self.megamorphic.store(true, Ordering::Relaxed);
+    }
+
+    fn layouts(&self) -> usize {
+        self.entries
+            .iter()
+            .take_while(|entry| entry.layout.load(Ordering::Relaxed) != 0)
+            .count()
+    }
+}
+
+/// The inline caches of every `GetField` instruction in a function, indexed by the instruction's
+/// index.
+#[derive(Default)]
+pub struct FieldCaches {
+    caches: Vec<Option<Box<FieldCache>>>,
+}
+
+impl FieldCaches {
+    pub fn new(instructions: &[Instruction]) -> FieldCaches {
+        let has_get_field = instructions.iter().any(|instruction| match *instruction {
+            Instruction::GetField(_) => true,
+            _ => false,
+        });
+        if !has_get_field {
+            return FieldCaches::default();
+        }
+        FieldCaches {
+            caches: instructions
+                .iter()
+                .map(|instruction| match *instruction {
+                    Instruction::GetField(_) => Some(Box::new(FieldCache::default())),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the value of `field` in `data`, using and updating the cache of the `GetField`
+    /// instruction at `index`
+    pub fn get_field<'a>(
+        &self,
+        index: usize,
+        data: &'a GcPtr<DataStruct>,
+        field: InternedStr,
+    ) -> Option<&'a Value> {
+        match self.caches.get(index) {
+            Some(&Some(ref cache)) => cache.lookup(data, field),
+            _ => data.get_field(field),
+        }
+    }
+
+    /// Returns the number of layouts cached by the instruction at `index` and whether it has
+    /// become megamorphic
+    pub fn state(&self, index: usize) -> Option<(usize, bool)> {
+        match self.caches.get(index) {
+            Some(&Some(ref cache)) => Some((
+                cache.layouts(),
+                cache.megamorphic.load(Ordering::Relaxed),
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for FieldCaches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.caches
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, _)| self.state(i).map(|state| (i, state))),
+            )
+            .finish()
+    }
+}
+
+/// Caches hold no semantic information so they are ignored when comparing functions
+impl PartialEq for FieldCaches {
+    fn eq(&self, _: &FieldCaches) -> bool {
+        true
+    }
+}
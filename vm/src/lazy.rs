@@ -6,13 +6,13 @@ use std::sync::Mutex;
 
 use base::types;
 use base::types::{ArcType, Type};
-use gc::{Gc, GcPtr, Move, Traverseable};
+use gc::{Gc, GcPtr, Traverseable};
 use api::{FunctionRef, Getable, OpaqueValue, RuntimeResult, Userdata, VmType, WithVM};
 use api::Generic;
 use api::generic::A;
 use vm::Thread;
 use {Error, ExternModule, Result, Variants};
-use value::{Cloner, Value};
+use value::{Cloner, MoveUserdata, Value};
 use thread::ThreadInternal;
 
 pub struct Lazy<T> {
@@ -35,7 +35,7 @@ where
             value: Mutex::new(cloned_value),
             _marker: PhantomData::<A>,
         });
-        deep_cloner.gc().alloc(Move(data))
+        deep_cloner.gc().alloc(MoveUserdata(data))
     }
 }
 
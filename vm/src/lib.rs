@@ -43,24 +43,33 @@ pub mod serialization;
 
 #[macro_use]
 pub mod api;
+#[cfg(feature = "bigint")]
+pub mod bigint;
 pub mod channel;
 pub mod core;
 pub mod compiler;
 pub mod debug;
+pub mod disassembler;
 pub mod dynamic;
+pub mod fixed_int;
 #[macro_use]
 pub mod future;
 pub mod gc;
+pub mod heap_snapshot;
 pub mod lazy;
 pub mod macros;
+pub mod mutable_array;
 pub mod thread;
 pub mod primitives;
 pub mod reference;
 pub mod stack;
 pub mod types;
+pub mod weak;
 
 mod array;
+mod inline_cache;
 mod interner;
+mod peephole;
 mod source_map;
 mod value;
 mod vm;
@@ -134,6 +143,12 @@ quick_error! {
         StackOverflow(limit: VmIndex) {
             display("The stack has overflowed: Limit `{}`", limit)
         }
+        Overflow(operation: String) {
+            display("Integer overflow in `{}`", operation)
+        }
+        DivideByZero {
+            display("Attempted to divide by zero")
+        }
         Message(err: String) {
             display("{}", err)
             from()
@@ -158,13 +173,50 @@ pub struct ExternModule {
 
 impl ExternModule {
     pub fn new<'vm, T>(thread: &'vm Thread, value: T) -> Result<ExternModule>
+    where
+        T: VmType + api::Pushable<'vm> + Send + Sync,
+    {
+        ExternModule::with_metadata(thread, value, Metadata::default())
+    }
+
+    /// Creates a module which documents its value and fields with `metadata`, making the
+    /// documentation available to the REPL and to completion.
+    ///
+    /// ```rust
+    /// #[macro_use]
+    /// extern crate gluon_vm;
+    /// extern crate gluon_base;
+    ///
+    /// use gluon_base::metadata::Metadata;
+    /// use gluon_vm::ExternModule;
+    /// use gluon_vm::thread::Thread;
+    ///
+    /// fn add(l: i32, r: i32) -> i32 {
+    ///     l + r
+    /// }
+    ///
+    /// fn load(vm: &Thread) -> gluon_vm::Result<ExternModule> {
+    ///     ExternModule::with_metadata(
+    ///         vm,
+    ///         record!(add => primitive!(2 add)),
+    ///         Metadata::with_comment("Arithmetic on integers")
+    ///             .field("add", Metadata::with_comment("Adds two integers")),
+    ///     )
+    /// }
+    /// # fn main() { }
+    /// ```
+    pub fn with_metadata<'vm, T>(
+        thread: &'vm Thread,
+        value: T,
+        metadata: Metadata,
+    ) -> Result<ExternModule>
     where
         T: VmType + api::Pushable<'vm> + Send + Sync,
     {
         Ok(ExternModule {
             value: value.marshal(thread)?,
             typ: T::make_forall_type(thread),
-            metadata: Metadata::default(),
+            metadata: metadata,
         })
     }
 }
@@ -172,6 +224,6 @@ impl ExternModule {
 
 /// Internal types and functions exposed to the main `gluon` crate
 pub mod internal {
-    pub use value::{ClosureDataDef, Value, ValuePrinter};
+    pub use value::{ClosureDataDef, Cloner, Value, ValuePrinter};
     pub use vm::Global;
 }
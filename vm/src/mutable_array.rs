@@ -0,0 +1,342 @@
+//! Growable, mutable arrays.
+//!
+//! `MutableArray a` holds arbitrary gluon values while `ByteBuffer` stores bytes unboxed, which
+//! makes it suitable for building binary data. Both types are updated in place, so building an
+//! array by repeatedly pushing to them is linear, unlike `std.array.append` which copies. Every
+//! operation runs in `IO` which keeps the mutation from being observable in pure code. `freeze`
+//! copies the current contents into an ordinary, immutable `Array`.
+use std::any::Any;
+use std::cmp;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::sync::{Mutex, MutexGuard};
+
+use base::types::{ArcType, Type};
+use {ExternModule, Result};
+use api::{Generic, Userdata, VmType, WithVM, IO};
+use api::generic::A;
+use gc::{Gc, GcPtr, Traverseable};
+use thread::ThreadInternal;
+use types::VmInt;
+use value::{Cloner, MoveUserdata, Value};
+use vm::Thread;
+
+pub struct MutableArray<T> {
+    values: Mutex<Vec<Value>>,
+    /// The thread whose heap the array is allocated in
+    thread: GcPtr<Thread>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Userdata for MutableArray<T>
+where
+    T: Any + Send + Sync,
+{
+    fn deep_clone(&self, deep_cloner: &mut Cloner) -> Result<GcPtr<Box<Userdata>>> {
+        let values = self.values.lock().unwrap();
+        let cloned_values = values
+            .iter()
+            .map(|&value| deep_cloner.deep_clone(value))
+            .collect::<Result<Vec<_>>>()?;
+        let data: Box<Userdata> = Box::new(MutableArray {
+            values: Mutex::new(cloned_values),
+            thread: unsafe { GcPtr::from_raw(deep_cloner.thread()) },
+            _marker: PhantomData::<A>,
+        });
+        deep_cloner.gc().alloc(MoveUserdata(data))
+    }
+
+    fn has_external_memory(&self) -> bool {
+        true
+    }
+
+    fn external_size(&self) -> usize {
+        self.values.lock().unwrap().capacity() * size_of::<Value>()
+    }
+}
+
+impl<T> fmt::Debug for MutableArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MutableArray({:?})", *self.values.lock().unwrap())
+    }
+}
+
+impl<T> Traverseable for MutableArray<T> {
+    fn traverse(&self, gc: &mut Gc) {
+        self.values.lock().unwrap().traverse(gc)
+    }
+}
+
+impl<T> VmType for MutableArray<T>
+where
+    T: VmType,
+    T::Type: Sized,
+{
+    type Type = MutableArray<T::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        let env = vm.global_env().get_env();
+        let symbol = env.find_type_info("MutableArray").unwrap().name.clone();
+        let ctor = Type::ident(symbol);
+        Type::app(ctor, collect![T::make_type(vm)])
+    }
+}
+
+impl<T> MutableArray<T> {
+    /// Clones `value` into the thread which owns the array so it can be stored in it
+    fn store(&self, value: Value) -> ::std::result::Result<Value, String> {
+        self.thread
+            .deep_clone_value(&self.thread, value)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// A growable buffer of unboxed bytes
+pub struct ByteBuffer {
+    bytes: Mutex<Vec<u8>>,
+    /// The thread whose heap the buffer is allocated in
+    thread: GcPtr<Thread>,
+}
+
+impl Userdata for ByteBuffer {
+    fn deep_clone(&self, deep_cloner: &mut Cloner) -> Result<GcPtr<Box<Userdata>>> {
+        let bytes = self.bytes.lock().unwrap().clone();
+        let data: Box<Userdata> = Box::new(ByteBuffer {
+            bytes: Mutex::new(bytes),
+            thread: unsafe { GcPtr::from_raw(deep_cloner.thread()) },
+        });
+        deep_cloner.gc().alloc(MoveUserdata(data))
+    }
+
+    fn has_external_memory(&self) -> bool {
+        true
+    }
+
+    fn external_size(&self) -> usize {
+        self.bytes.lock().unwrap().capacity()
+    }
+
+    fn is_mutable(&self) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for ByteBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ByteBuffer({:?})", *self.bytes.lock().unwrap())
+    }
+}
+
+impl Traverseable for ByteBuffer {
+    fn traverse(&self, _: &mut Gc) {}
+}
+
+impl VmType for ByteBuffer {
+    type Type = ByteBuffer;
+}
+
+fn out_of_range(index: VmInt, len: usize) -> String {
+    format!("Index {} is out of range for an array of length {}", index, len)
+}
+
+fn check_index(index: VmInt, len: usize) -> ::std::result::Result<usize, String> {
+    if 0 <= index && (index as usize) < len {
+        Ok(index as usize)
+    } else {
+        Err(out_of_range(index, len))
+    }
+}
+
+/// Checks that reserving `capacity` elements of `element_size` bytes stays within the memory
+/// limit of `vm`, returning the number of bytes to reserve
+fn reserve(vm: &Thread, capacity: VmInt, element_size: usize) -> Result<usize> {
+    let capacity = capacity.max(0) as usize;
+    vm.context()
+        .gc
+        .check_memory_limit(capacity.saturating_mul(element_size))?;
+    Ok(capacity)
+}
+
+/// Makes room for `additional` more elements in `vec`, which is owned by a value allocated in the
+/// heap of `thread`, and returns the locked vector. The bytes the vector grows by are checked
+/// against the memory limit before growing and are then counted as allocated memory.
+///
+/// The context of `thread` is locked before `vec` so the lock order is the same as when a
+/// collection traverses the vector.
+fn grow<'a, T>(
+    thread: &Thread,
+    vec: &'a Mutex<Vec<T>>,
+    additional: usize,
+) -> Result<MutexGuard<'a, Vec<T>>> {
+    let mut context = thread.context();
+    let mut vec = vec.lock().unwrap();
+    let len = vec.len();
+    let capacity = vec.capacity();
+    if capacity - len >= additional {
+        return Ok(vec);
+    }
+    let new_capacity = cmp::max(capacity.saturating_mul(2), len.saturating_add(additional));
+    context
+        .gc
+        .check_memory_limit((new_capacity - capacity).saturating_mul(size_of::<T>()))?;
+    vec.reserve_exact(new_capacity - len);
+    context
+        .gc
+        .add_external_memory((vec.capacity() - capacity) * size_of::<T>());
+    Ok(vec)
+}
+
+fn with_capacity(capacity: WithVM<VmInt>) -> IO<MutableArray<A>> {
+    let WithVM { vm, value: capacity } = capacity;
+    reserve(vm, capacity, size_of::<Value>())
+        .map(|capacity| MutableArray {
+            values: Mutex::new(Vec::with_capacity(capacity)),
+            thread: unsafe { GcPtr::from_raw(vm) },
+            _marker: PhantomData,
+        })
+        .into()
+}
+
+fn push(array: &MutableArray<A>, value: Generic<A>) -> IO<()> {
+    let value = match array.store(value.0) {
+        Ok(value) => value,
+        Err(err) => return IO::Exception(err),
+    };
+    grow(&array.thread, &array.values, 1)
+        .map(|mut values| values.push(value))
+        .map_err(|err| err.to_string())
+        .into()
+}
+
+fn pop(array: &MutableArray<A>) -> IO<Option<Generic<A>>> {
+    IO::Value(array.values.lock().unwrap().pop().map(Generic::from))
+}
+
+fn set(array: &MutableArray<A>, index: VmInt, value: Generic<A>) -> IO<()> {
+    let value = match array.store(value.0) {
+        Ok(value) => value,
+        Err(err) => return IO::Exception(err),
+    };
+    let mut values = array.values.lock().unwrap();
+    let len = values.len();
+    check_index(index, len)
+        .map(|index| values[index] = value)
+        .into()
+}
+
+fn get(array: &MutableArray<A>, index: VmInt) -> IO<Generic<A>> {
+    let values = array.values.lock().unwrap();
+    check_index(index, values.len())
+        .map(|index| Generic::from(values[index]))
+        .into()
+}
+
+fn len(array: &MutableArray<A>) -> IO<VmInt> {
+    IO::Value(array.values.lock().unwrap().len() as VmInt)
+}
+
+fn freeze(array: &MutableArray<A>) -> IO<Vec<Generic<A>>> {
+    IO::Value(
+        array
+            .values
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|&value| Generic::from(value))
+            .collect(),
+    )
+}
+
+#[doc(hidden)]
+pub mod byte_buffer {
+    use super::*;
+
+    pub fn with_capacity(capacity: WithVM<VmInt>) -> IO<ByteBuffer> {
+        let WithVM { vm, value: capacity } = capacity;
+        reserve(vm, capacity, 1)
+            .map(|capacity| ByteBuffer {
+                bytes: Mutex::new(Vec::with_capacity(capacity)),
+                thread: unsafe { GcPtr::from_raw(vm) },
+            })
+            .into()
+    }
+
+    pub fn push(buffer: &ByteBuffer, byte: u8) -> IO<()> {
+        grow(&buffer.thread, &buffer.bytes, 1)
+            .map(|mut bytes| bytes.push(byte))
+            .into()
+    }
+
+    pub fn extend(buffer: &ByteBuffer, bytes: &[u8]) -> IO<()> {
+        grow(&buffer.thread, &buffer.bytes, bytes.len())
+            .map(|mut buffer| buffer.extend_from_slice(bytes))
+            .into()
+    }
+
+    pub fn set(buffer: &ByteBuffer, index: VmInt, byte: u8) -> IO<()> {
+        let mut bytes = buffer.bytes.lock().unwrap();
+        let len = bytes.len();
+        check_index(index, len).map(|index| bytes[index] = byte).into()
+    }
+
+    pub fn get(buffer: &ByteBuffer, index: VmInt) -> IO<u8> {
+        let bytes = buffer.bytes.lock().unwrap();
+        check_index(index, bytes.len())
+            .map(|index| bytes[index])
+            .into()
+    }
+
+    pub fn len(buffer: &ByteBuffer) -> IO<VmInt> {
+        IO::Value(buffer.bytes.lock().unwrap().len() as VmInt)
+    }
+
+    pub fn freeze(buffer: &ByteBuffer) -> IO<Vec<u8>> {
+        IO::Value(buffer.bytes.lock().unwrap().clone())
+    }
+}
+
+mod std {
+    pub mod mutable_array {
+        pub use mutable_array as prim;
+    }
+    pub mod byte_buffer {
+        pub use mutable_array::byte_buffer as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    vm.register_type::<MutableArray<A>>("MutableArray", &["a"])?;
+    ExternModule::new(
+        vm,
+        record!{
+            with_capacity => primitive!(1 std::mutable_array::prim::with_capacity),
+            push => primitive!(2 std::mutable_array::prim::push),
+            pop => primitive!(1 std::mutable_array::prim::pop),
+            set => primitive!(3 std::mutable_array::prim::set),
+            get => primitive!(2 std::mutable_array::prim::get),
+            len => primitive!(1 std::mutable_array::prim::len),
+            freeze => primitive!(1 std::mutable_array::prim::freeze)
+        },
+    )
+}
+
+pub fn load_byte_buffer(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    vm.register_type::<ByteBuffer>("ByteBuffer", &[])?;
+    ExternModule::new(
+        vm,
+        record!{
+            with_capacity => primitive!(1 std::byte_buffer::prim::with_capacity),
+            push => primitive!(2 std::byte_buffer::prim::push),
+            extend => primitive!(2 std::byte_buffer::prim::extend),
+            set => primitive!(3 std::byte_buffer::prim::set),
+            get => primitive!(2 std::byte_buffer::prim::get),
+            len => primitive!(1 std::byte_buffer::prim::len),
+            freeze => primitive!(1 std::byte_buffer::prim::freeze)
+        },
+    )
+}
@@ -0,0 +1,158 @@
+//! Peephole optimization of the bytecode emitted by `compiler::Compiler`.
+//!
+//! Common sequences of instructions are fused into superinstructions (see the end of
+//! `types::Instruction`), reducing the number of instructions the virtual machine needs to
+//! dispatch, and adjacent `Pop` and `Slide` instructions are merged. Instructions are never fused
+//! across the target of a jump, as that would change where the jump lands.
+use compiler::CompiledFunction;
+use types::Instruction;
+use types::Instruction::*;
+use types::VmIndex;
+
+/// Tries to merge `next` into `previous`, the instruction before it
+fn fuse(previous: Instruction, next: Instruction) -> Option<Instruction> {
+    Some(match (previous, next) {
+        (Push(first), Push(second)) => PushPair {
+            first: first,
+            second: second,
+        },
+        (PushPair { first, second }, AddInt) => AddIntLocals {
+            lhs: first,
+            rhs: second,
+        },
+        (TestTag(tag), CJump(target)) => TestTagJump {
+            tag: tag,
+            target: target,
+        },
+        (GetOffset(first), GetOffset(second)) => GetOffsetPair {
+            first: first,
+            second: second,
+        },
+        (Pop(n), Pop(m)) => Pop(n + m),
+        (Slide(n), Slide(m)) => Slide(n + m),
+        // `Slide` keeps the top value but it is then popped as well
+        (Slide(n), Pop(m)) if m > 0 => Pop(n + m),
+        _ => return None,
+    })
+}
+
+fn jump_target(instruction: &mut Instruction) -> Option<&mut VmIndex> {
+    match *instruction {
+        Jump(ref mut target) | CJump(ref mut target) | TestTagJump { ref mut target, .. } => {
+            Some(target)
+        }
+        _ => None,
+    }
+}
+
+/// Runs the peephole optimizer on the instructions of `function`, updating jumps and the debug
+/// information to refer to the optimized instructions. Inner functions are not optimized.
+pub fn optimize(function: &mut CompiledFunction) {
+    let old_len = function.instructions.len();
+    let mut is_target = vec![false; old_len + 1];
+    for instruction in &mut function.instructions {
+        if let Some(&mut target) = jump_target(instruction) {
+            is_target[target as usize] = true;
+        }
+    }
+
+    let mut instructions: Vec<Instruction> = Vec::with_capacity(old_len);
+    // Maps the index of each old instruction to the index of the instruction it ended up in
+    let mut new_index = Vec::with_capacity(old_len + 1);
+    // Set to false whenever a jump target is passed which prevents it from being fused away
+    let mut can_fuse = false;
+    for (i, &instruction) in function.instructions.iter().enumerate() {
+        can_fuse = can_fuse && !is_target[i];
+        if let Pop(0) = instruction {
+            new_index.push(instructions.len());
+            continue;
+        }
+        let fused = if can_fuse {
+            instructions
+                .last()
+                .and_then(|&previous| fuse(previous, instruction))
+        } else {
+            None
+        };
+        match fused {
+            Some(fused) => {
+                *instructions.last_mut().unwrap() = fused;
+                new_index.push(instructions.len() - 1);
+            }
+            None => {
+                new_index.push(instructions.len());
+                instructions.push(instruction);
+            }
+        }
+        can_fuse = true;
+    }
+    new_index.push(instructions.len());
+
+    for instruction in &mut instructions {
+        if let Some(target) = jump_target(instruction) {
+            *target = new_index[*target as usize] as VmIndex;
+        }
+    }
+
+    function.instructions = instructions;
+    function.debug_info.source_map.remap(&new_index);
+    function.debug_info.local_map.remap(&new_index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base::symbol::Symbol;
+    use base::types::Type;
+
+    fn optimized(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut function = CompiledFunction::new(0, Symbol::from("test"), Type::hole(), "".into());
+        function.instructions = instructions;
+        optimize(&mut function);
+        function.instructions
+    }
+
+    #[test]
+    fn fuse_push_add() {
+        assert_eq!(
+            optimized(vec![Push(0), Push(1), AddInt, Push(2), Push(3)]),
+            vec![
+                AddIntLocals { lhs: 0, rhs: 1 },
+                PushPair {
+                    first: 2,
+                    second: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_pops() {
+        assert_eq!(
+            optimized(vec![Slide(1), Slide(2), Pop(1), Pop(0), Pop(3)]),
+            vec![Pop(7)]
+        );
+    }
+
+    #[test]
+    fn do_not_fuse_jump_targets() {
+        assert_eq!(
+            optimized(vec![
+                TestTag(1),
+                CJump(4),
+                Push(0),
+                Jump(5),
+                Push(1),
+                Push(2),
+            ]),
+            vec![
+                TestTagJump { tag: 1, target: 3 },
+                Push(0),
+                Jump(4),
+                Push(1),
+                Push(2),
+            ]
+        );
+    }
+}
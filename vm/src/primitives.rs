@@ -4,7 +4,7 @@ use std::string::String as StdString;
 use std::str::FromStr;
 
 use {Error, ExternModule, Variants};
-use api::{generic, primitive, Array, Generic, Getable, RuntimeResult, WithVM};
+use api::{generic, primitive, Array, Generic, Getable, RuntimeResult, TypedBytecode, WithVM};
 use api::generic::A;
 use gc::{DataDef, Gc, Traverseable, WriteOnly};
 use Result;
@@ -16,7 +16,15 @@ use types::VmInt;
 #[doc(hidden)]
 pub mod array {
     use super::*;
-    use thread::ThreadInternal;
+    use std::cmp::Ordering;
+    use std::ops::Range;
+
+    use futures::Async;
+
+    use api::FunctionRef;
+    use thread::{alloc, Context, ThreadInternal};
+    use types::VmIndex;
+    use value::ArrayDef;
 
     pub fn len(array: Array<generic::A>) -> VmInt {
         array.len() as VmInt
@@ -92,6 +100,321 @@ pub mod array {
             )
         }
     }
+
+    /// Allocates an array containing the elements in each of `parts`, in order
+    struct Concat<'b> {
+        parts: Vec<(&'b ValueArray, Range<usize>)>,
+    }
+
+    impl<'b> Concat<'b> {
+        fn repr(&self) -> Repr {
+            // Empty arrays may not have the correct representation so take it from the first
+            // array which contributes any elements
+            self.parts
+                .iter()
+                .find(|&&(_, ref range)| range.len() != 0)
+                .map_or(Repr::Unknown, |&(array, _)| array.repr())
+        }
+
+        fn len(&self) -> usize {
+            self.parts.iter().map(|&(_, ref range)| range.len()).sum()
+        }
+    }
+
+    impl<'b> Traverseable for Concat<'b> {
+        fn traverse(&self, gc: &mut Gc) {
+            for &(array, _) in &self.parts {
+                array.traverse(gc);
+            }
+        }
+    }
+
+    unsafe impl<'b> DataDef for Concat<'b> {
+        type Value = ValueArray;
+        fn size(&self) -> usize {
+            ValueArray::size_of(self.repr(), self.len())
+        }
+        fn initialize<'w>(self, mut result: WriteOnly<'w, ValueArray>) -> &'w mut ValueArray {
+            unsafe {
+                let result = &mut *result.as_mut_ptr();
+                result.set_repr(self.repr());
+                result.initialize(
+                    self.parts
+                        .iter()
+                        .flat_map(|&(array, ref range)| range.clone().map(move |i| array.get(i))),
+                );
+                result
+            }
+        }
+    }
+
+    /// Allocates an array containing `len` copies of `value`
+    struct Fill {
+        value: Value,
+        len: usize,
+    }
+
+    impl Fill {
+        fn repr(&self) -> Repr {
+            if self.len == 0 {
+                Repr::Unknown
+            } else {
+                Repr::from_value(self.value)
+            }
+        }
+    }
+
+    impl Traverseable for Fill {
+        fn traverse(&self, gc: &mut Gc) {
+            self.value.traverse(gc);
+        }
+    }
+
+    unsafe impl DataDef for Fill {
+        type Value = ValueArray;
+        fn size(&self) -> usize {
+            // Saturate so that absurd lengths are rejected by the memory limit instead of wrapping
+            ::std::mem::size_of::<ValueArray>()
+                .saturating_add(self.repr().size_of().saturating_mul(self.len))
+        }
+        fn initialize<'w>(self, mut result: WriteOnly<'w, ValueArray>) -> &'w mut ValueArray {
+            unsafe {
+                let result = &mut *result.as_mut_ptr();
+                result.set_repr(self.repr());
+                result.initialize(::std::iter::repeat(self.value).take(self.len));
+                result
+            }
+        }
+    }
+
+    fn alloc_array<'vm, T, D>(vm: &'vm Thread, def: D) -> RuntimeResult<Array<'vm, T>, Error>
+    where
+        D: DataDef<Value = ValueArray> + Traverseable,
+    {
+        let value = {
+            let mut context = vm.context();
+            match context.alloc(def) {
+                Ok(x) => x,
+                Err(err) => return RuntimeResult::Panic(err),
+            }
+        };
+        unsafe { RuntimeResult::Return(Getable::from_value(vm, Variants::new(&Value::Array(value)))) }
+    }
+
+    /// Calls `f` with `args`, leaving the result at the top of the stack where it stays rooted
+    fn call_rooted(vm: &Thread, f: Value, args: &[Value]) -> Result<()> {
+        let mut context = vm.context();
+        context.stack.push(f);
+        for &arg in args {
+            context.stack.push(arg);
+        }
+        match vm.call_function(context, args.len() as VmIndex)? {
+            Async::Ready(_) => Ok(()),
+            Async::NotReady => Err(Error::Message(
+                "Functions passed to `std.array` may not be asynchronous".into(),
+            )),
+        }
+    }
+
+    fn call(vm: &Thread, f: Value, args: &[Value]) -> Result<Value> {
+        call_rooted(vm, f, args)?;
+        Ok(vm.context().stack.pop())
+    }
+
+    fn call_compare(vm: &Thread, f: Value, args: &[Value]) -> Result<Ordering> {
+        let value = call(vm, f, args)?;
+        Ok(unsafe { Ordering::from_value(vm, Variants::new(&value)) })
+    }
+
+    fn check_range(array: &ValueArray, start: VmInt, end: VmInt) -> StdResult<Range<usize>, String> {
+        if 0 <= start && start <= end && end as usize <= array.len() {
+            Ok(start as usize..end as usize)
+        } else {
+            Err(format!(
+                "Range {}..{} is out of range for an array of length {}",
+                start,
+                end,
+                array.len()
+            ))
+        }
+    }
+
+    pub fn map<'vm>(
+        f: FunctionRef<'vm, fn(Generic<generic::A>) -> Generic<generic::B>>,
+        array: Array<'vm, Generic<generic::A>>,
+    ) -> RuntimeResult<Array<'vm, Generic<generic::B>>, Error> {
+        let vm = array.vm();
+        let f = f.value();
+        let len = array.len();
+        // The results are left on the stack until all of them have been computed so that they
+        // stay rooted while `f` is running
+        for i in 0..len {
+            if let Err(err) = call_rooted(vm, f, &[array.get_value_array().get(i)]) {
+                return RuntimeResult::Panic(err);
+            }
+        }
+        let result = {
+            let mut context = vm.context();
+            let result = {
+                let Context {
+                    ref mut gc,
+                    ref stack,
+                    ..
+                } = *context;
+                let values = &stack[stack.len() - len as VmIndex..];
+                alloc(gc, vm, stack, ArrayDef(values))
+            };
+            context.stack.pop_many(len);
+            result
+        };
+        match result {
+            Ok(value) => unsafe {
+                RuntimeResult::Return(Getable::from_value(vm, Variants::new(&Value::Array(value))))
+            },
+            Err(err) => RuntimeResult::Panic(err),
+        }
+    }
+
+    pub fn fold<'vm>(
+        f: FunctionRef<'vm, fn(Generic<generic::B>, Generic<generic::A>) -> Generic<generic::B>>,
+        initial: Generic<generic::B>,
+        array: Array<'vm, Generic<generic::A>>,
+    ) -> RuntimeResult<Generic<generic::B>, Error> {
+        let vm = array.vm();
+        let f = f.value();
+        let mut acc = initial.0;
+        for value in array.get_value_array().iter() {
+            acc = match call(vm, f, &[acc, value]) {
+                Ok(acc) => acc,
+                Err(err) => return RuntimeResult::Panic(err),
+            };
+        }
+        RuntimeResult::Return(Generic::from(acc))
+    }
+
+    pub fn slice<'vm>(
+        array: Array<'vm, Generic<generic::A>>,
+        start: VmInt,
+        end: VmInt,
+    ) -> RuntimeResult<Array<'vm, Generic<generic::A>>, Error> {
+        let range = match check_range(array.get_value_array(), start, end) {
+            Ok(range) => range,
+            Err(err) => return RuntimeResult::Panic(Error::Message(err)),
+        };
+        alloc_array(
+            array.vm(),
+            Concat {
+                parts: vec![(array.get_value_array(), range)],
+            },
+        )
+    }
+
+    pub fn fill<'vm>(
+        WithVM { vm, value: len }: WithVM<'vm, VmInt>,
+        value: Generic<generic::A>,
+    ) -> RuntimeResult<Array<'vm, Generic<generic::A>>, Error> {
+        if len < 0 {
+            return RuntimeResult::Panic(Error::Message(format!(
+                "Attempted to create an array with a negative length ({})",
+                len
+            )));
+        }
+        alloc_array(
+            vm,
+            Fill {
+                value: value.0,
+                len: len as usize,
+            },
+        )
+    }
+
+    pub fn copy<'vm>(
+        dest: Array<'vm, Generic<generic::A>>,
+        offset: VmInt,
+        src: Array<'vm, Generic<generic::A>>,
+    ) -> RuntimeResult<Array<'vm, Generic<generic::A>>, Error> {
+        let dest_array = dest.get_value_array();
+        let src_array = src.get_value_array();
+        let end = offset.saturating_add(src_array.len() as VmInt);
+        let range = match check_range(dest_array, offset, end) {
+            Ok(range) => range,
+            Err(err) => return RuntimeResult::Panic(Error::Message(err)),
+        };
+        alloc_array(
+            dest.vm(),
+            Concat {
+                parts: vec![
+                    (dest_array, 0..range.start),
+                    (src_array, 0..src_array.len()),
+                    (dest_array, range.end..dest_array.len()),
+                ],
+            },
+        )
+    }
+
+    pub fn concat<'vm>(
+        arrays: Array<'vm, Array<'vm, Generic<generic::A>>>,
+    ) -> RuntimeResult<Array<'vm, Generic<generic::A>>, Error> {
+        // The inner arrays are rooted by `arrays`
+        let inner: Vec<_> = arrays
+            .get_value_array()
+            .iter()
+            .map(|value| match value {
+                Value::Array(array) => array,
+                _ => ice!("Expected an array found {:?}", value),
+            })
+            .collect();
+        alloc_array(
+            arrays.vm(),
+            Concat {
+                parts: inner.iter().map(|array| (&**array, 0..array.len())).collect(),
+            },
+        )
+    }
+
+    pub fn sort_by<'vm>(
+        compare: FunctionRef<'vm, fn(Generic<generic::A>, Generic<generic::A>) -> Ordering>,
+        array: Array<'vm, Generic<generic::A>>,
+    ) -> RuntimeResult<Array<'vm, Generic<generic::A>>, Error> {
+        let vm = array.vm();
+        let compare = compare.value();
+        // The values are rooted by `array`
+        let mut values: Vec<Value> = array.get_value_array().iter().collect();
+        let mut error = None;
+        values.sort_by(|&l, &r| {
+            if error.is_some() {
+                return Ordering::Equal;
+            }
+            call_compare(vm, compare, &[l, r]).unwrap_or_else(|err| {
+                error = Some(err);
+                Ordering::Equal
+            })
+        });
+        if let Some(err) = error {
+            return RuntimeResult::Panic(err);
+        }
+        alloc_array(vm, ArrayDef(&values))
+    }
+
+    pub fn binary_search_by<'vm>(
+        compare: FunctionRef<'vm, fn(Generic<generic::A>) -> Ordering>,
+        array: Array<'vm, Generic<generic::A>>,
+    ) -> RuntimeResult<StdResult<VmInt, VmInt>, Error> {
+        let vm = array.vm();
+        let compare = compare.value();
+        let array = array.get_value_array();
+        let (mut low, mut high) = (0, array.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match call_compare(vm, compare, &[array.get(mid)]) {
+                Ok(Ordering::Less) => low = mid + 1,
+                Ok(Ordering::Greater) => high = mid,
+                Ok(Ordering::Equal) => return RuntimeResult::Return(Ok(mid as VmInt)),
+                Err(err) => return RuntimeResult::Panic(err),
+            }
+        }
+        RuntimeResult::Return(Err(low as VmInt))
+    }
 }
 
 mod string {
@@ -212,6 +535,150 @@ mod string {
     }
 }
 
+mod int {
+    use std::{cmp, mem};
+
+    use api::RuntimeResult;
+    use types::VmInt;
+
+    fn overflow(operation: &str, l: VmInt, r: VmInt) -> String {
+        format!("Integer overflow in `{} {} {}`", l, operation, r)
+    }
+
+    pub fn checked_add(l: VmInt, r: VmInt) -> Option<VmInt> {
+        l.checked_add(r)
+    }
+
+    pub fn checked_sub(l: VmInt, r: VmInt) -> Option<VmInt> {
+        l.checked_sub(r)
+    }
+
+    pub fn checked_mul(l: VmInt, r: VmInt) -> Option<VmInt> {
+        l.checked_mul(r)
+    }
+
+    pub fn checked_div(l: VmInt, r: VmInt) -> Option<VmInt> {
+        l.checked_div(r)
+    }
+
+    pub fn checked_rem(l: VmInt, r: VmInt) -> Option<VmInt> {
+        l.checked_rem(r)
+    }
+
+    pub fn checked_pow(base: VmInt, exp: VmInt) -> Option<VmInt> {
+        if exp < 0 {
+            return None;
+        }
+        let (mut base, mut exp) = (base, exp);
+        let mut result: VmInt = 1;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.checked_mul(base)?;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        Some(result)
+    }
+
+    pub fn checked_abs(i: VmInt) -> Option<VmInt> {
+        i.checked_abs()
+    }
+
+    pub fn pow(base: VmInt, exp: VmInt) -> RuntimeResult<VmInt, String> {
+        if exp < 0 {
+            return RuntimeResult::Panic(format!("Negative exponent {}", exp));
+        }
+        checked_pow(base, exp)
+            .ok_or_else(|| overflow("^", base, exp))
+            .into()
+    }
+
+    pub fn abs(i: VmInt) -> RuntimeResult<VmInt, String> {
+        i.checked_abs()
+            .ok_or_else(|| format!("Integer overflow in `abs {}`", i))
+            .into()
+    }
+
+    /// The remainder of `l / r`, taking the sign of `l`
+    pub fn rem(l: VmInt, r: VmInt) -> RuntimeResult<VmInt, &'static str> {
+        if r == 0 {
+            RuntimeResult::Panic("Attempted to calculate the remainder with a divisor of zero")
+        } else {
+            // `min_value % -1` is 0 but overflows in `%`
+            RuntimeResult::Return(l.wrapping_rem(r))
+        }
+    }
+
+    /// The modulo of `l / r`, taking the sign of `r`
+    pub fn modulo(l: VmInt, r: VmInt) -> RuntimeResult<VmInt, &'static str> {
+        if r == 0 {
+            return RuntimeResult::Panic(
+                "Attempted to calculate the modulo with a divisor of zero",
+            );
+        }
+        let m = l.wrapping_rem(r);
+        RuntimeResult::Return(if m != 0 && (m < 0) != (r < 0) { m + r } else { m })
+    }
+
+    pub fn min(l: VmInt, r: VmInt) -> VmInt {
+        cmp::min(l, r)
+    }
+
+    pub fn max(l: VmInt, r: VmInt) -> VmInt {
+        cmp::max(l, r)
+    }
+
+    pub fn shift_right_logical(l: VmInt, r: VmInt) -> RuntimeResult<VmInt, String> {
+        if 0 <= r && r < (mem::size_of::<VmInt>() * 8) as VmInt {
+            RuntimeResult::Return(((l as usize) >> r) as VmInt)
+        } else {
+            RuntimeResult::Panic(overflow(">>>", l, r))
+        }
+    }
+
+    pub fn leading_zeros(i: VmInt) -> VmInt {
+        i.leading_zeros() as VmInt
+    }
+
+    pub fn trailing_zeros(i: VmInt) -> VmInt {
+        i.trailing_zeros() as VmInt
+    }
+
+    pub fn count_zeros(i: VmInt) -> VmInt {
+        i.count_zeros() as VmInt
+    }
+
+    pub fn parse_radix(s: &str, radix: VmInt) -> RuntimeResult<Result<VmInt, ()>, String> {
+        if 2 <= radix && radix <= 36 {
+            RuntimeResult::Return(VmInt::from_str_radix(s, radix as u32).map_err(|_| ()))
+        } else {
+            RuntimeResult::Panic(format!("Radix must be between 2 and 36 but got {}", radix))
+        }
+    }
+
+    pub fn to_byte(i: VmInt) -> Option<u8> {
+        if 0 <= i && i <= 255 {
+            Some(i as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Converts an already rounded float to an `Int`, returning `None` if it is NaN or out of
+    /// range
+    pub fn float_to_int(f: f64) -> Option<VmInt> {
+        // `VmInt::max_value() as f64` rounds up to a power of two which is out of range
+        if f.is_nan() || f < VmInt::min_value() as f64 || f >= VmInt::max_value() as f64 {
+            None
+        } else {
+            Some(f.trunc() as VmInt)
+        }
+    }
+}
+
 fn parse<T>(s: &str) -> StdResult<T, ()>
 where
     T: FromStr,
@@ -333,6 +800,9 @@ pub fn load_float(thread: &Thread) -> Result<ExternModule> {
 #[allow(non_camel_case_types)]
 pub fn load_int(vm: &Thread) -> Result<ExternModule> {
     use self::std;
+    use types::Instruction::{BitAndInt, BitOrInt, BitXorInt, ByteToInt, IntToFloat, PushInt,
+                             ShiftLeftInt, ShiftRightInt};
+
     ExternModule::new(
         vm,
         record! {
@@ -346,12 +816,84 @@ pub fn load_int(vm: &Thread) -> Result<ExternModule> {
             from_le => primitive!(1 std::int::prim::from_le),
             to_be => primitive!(1 std::int::prim::to_be),
             to_le => primitive!(1 std::int::prim::to_le),
-            pow => primitive!(2 std::int::prim::pow),
-            abs => primitive!(1 std::int::prim::abs),
+            wrapping_add => primitive!(2 std::int::prim::wrapping_add),
+            wrapping_sub => primitive!(2 std::int::prim::wrapping_sub),
+            wrapping_mul => primitive!(2 std::int::prim::wrapping_mul),
+            saturating_add => primitive!(2 std::int::prim::saturating_add),
+            saturating_sub => primitive!(2 std::int::prim::saturating_sub),
+            saturating_mul => primitive!(2 std::int::prim::saturating_mul),
+            checked_add => named_primitive!(2, "std.int.prim.checked_add", int::checked_add),
+            checked_sub => named_primitive!(2, "std.int.prim.checked_sub", int::checked_sub),
+            checked_mul => named_primitive!(2, "std.int.prim.checked_mul", int::checked_mul),
+            checked_div => named_primitive!(2, "std.int.prim.checked_div", int::checked_div),
+            checked_rem => named_primitive!(2, "std.int.prim.checked_rem", int::checked_rem),
+            checked_pow => named_primitive!(2, "std.int.prim.checked_pow", int::checked_pow),
+            checked_abs => named_primitive!(1, "std.int.prim.checked_abs", int::checked_abs),
+            pow => named_primitive!(2, "std.int.prim.pow", int::pow),
+            abs => named_primitive!(1, "std.int.prim.abs", int::abs),
+            rem => named_primitive!(2, "std.int.prim.rem", int::rem),
+            modulo => named_primitive!(2, "std.int.prim.modulo", int::modulo),
+            min => named_primitive!(2, "std.int.prim.min", int::min),
+            max => named_primitive!(2, "std.int.prim.max", int::max),
             signum => primitive!(1 std::int::prim::signum),
             is_positive => primitive!(1 std::int::prim::is_positive),
             is_negative => primitive!(1 std::int::prim::is_negative),
-            parse => named_primitive!(1, "std.int.prim.parse", parse::<VmInt>)
+            bit_and => TypedBytecode::<fn(VmInt, VmInt) -> VmInt>::new(
+                "std.int.prim.bit_and",
+                2,
+                vec![BitAndInt]
+            ),
+            bit_or => TypedBytecode::<fn(VmInt, VmInt) -> VmInt>::new(
+                "std.int.prim.bit_or",
+                2,
+                vec![BitOrInt]
+            ),
+            bit_xor => TypedBytecode::<fn(VmInt, VmInt) -> VmInt>::new(
+                "std.int.prim.bit_xor",
+                2,
+                vec![BitXorInt]
+            ),
+            bit_not => TypedBytecode::<fn(VmInt) -> VmInt>::new(
+                "std.int.prim.bit_not",
+                1,
+                vec![PushInt(-1), BitXorInt]
+            ),
+            shift_left => TypedBytecode::<fn(VmInt, VmInt) -> VmInt>::new(
+                "std.int.prim.shift_left",
+                2,
+                vec![ShiftLeftInt]
+            ),
+            shift_right => TypedBytecode::<fn(VmInt, VmInt) -> VmInt>::new(
+                "std.int.prim.shift_right",
+                2,
+                vec![ShiftRightInt]
+            ),
+            shift_right_logical => named_primitive!(
+                2,
+                "std.int.prim.shift_right_logical",
+                int::shift_right_logical
+            ),
+            count_zeros => named_primitive!(1, "std.int.prim.count_zeros", int::count_zeros),
+            leading_zeros => named_primitive!(1, "std.int.prim.leading_zeros", int::leading_zeros),
+            trailing_zeros => named_primitive!(
+                1,
+                "std.int.prim.trailing_zeros",
+                int::trailing_zeros
+            ),
+            to_float => TypedBytecode::<fn(VmInt) -> f64>::new(
+                "std.int.prim.to_float",
+                1,
+                vec![IntToFloat]
+            ),
+            float_to_int => named_primitive!(1, "std.int.prim.float_to_int", int::float_to_int),
+            from_byte => TypedBytecode::<fn(u8) -> VmInt>::new(
+                "std.int.prim.from_byte",
+                1,
+                vec![ByteToInt]
+            ),
+            to_byte => named_primitive!(1, "std.int.prim.to_byte", int::to_byte),
+            parse => named_primitive!(1, "std.int.prim.parse", parse::<VmInt>),
+            parse_radix => named_primitive!(2, "std.int.prim.parse_radix", int::parse_radix)
         },
     )
 }
@@ -364,7 +906,15 @@ pub fn load_array(vm: &Thread) -> Result<ExternModule> {
         record! {
             len => primitive!(1 std::array::prim::len),
             index => primitive!(2 std::array::prim::index),
-            append => primitive!(2 std::array::prim::append)
+            append => primitive!(2 std::array::prim::append),
+            map => primitive!(2 std::array::prim::map),
+            fold => primitive!(3 std::array::prim::fold),
+            slice => primitive!(3 std::array::prim::slice),
+            fill => primitive!(2 std::array::prim::fill),
+            copy => primitive!(3 std::array::prim::copy),
+            concat => primitive!(1 std::array::prim::concat),
+            sort_by => primitive!(2 std::array::prim::sort_by),
+            binary_search_by => primitive!(2 std::array::prim::binary_search_by)
         },
     )
 }
@@ -5,10 +5,10 @@ use std::marker::PhantomData;
 
 use base::types::{ArcType, Type};
 use {ExternModule, Result};
-use gc::{Gc, GcPtr, Move, Traverseable};
+use gc::{Gc, GcPtr, Traverseable};
 use vm::Thread;
 use thread::ThreadInternal;
-use value::{Cloner, Value};
+use value::{Cloner, MoveUserdata, Value};
 use api::{Generic, RuntimeResult, Userdata, VmType, WithVM};
 use api::generic::A;
 
@@ -30,7 +30,7 @@ where
             thread: unsafe { GcPtr::from_raw(deep_cloner.thread()) },
             _marker: PhantomData::<A>,
         });
-        deep_cloner.gc().alloc(Move(data))
+        deep_cloner.gc().alloc(MoveUserdata(data))
     }
 }
 
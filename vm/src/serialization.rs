@@ -16,10 +16,11 @@ use base::types::ArcType;
 
 use array::Array;
 use gc::{DataDef, GcPtr, WriteOnly};
+use stack::Stack;
 use thread::{RootedThread, Thread, ThreadInternal};
 use types::VmIndex;
 use value::{BytecodeFunction, Callable, ClosureData, ExternFunction, PartialApplicationData,
-            PartialApplicationDataDef, Value};
+            PartialApplicationDataDef, Userdata, Value};
 
 #[derive(Clone)]
 pub struct DeSeed {
@@ -727,12 +728,100 @@ where
     }
 }
 
-pub fn serialize_userdata<S, T>(_: &T, _: S) -> Result<S::Ok, S::Error>
+impl SerializeState<SeSeed> for Box<Userdata> {
+    fn serialize_state<S>(&self, serializer: S, _seed: &SeSeed) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::{Error, Serialize};
+        match self.serialize_userdata() {
+            Some(Ok((name, bytes))) => (name, bytes).serialize(serializer),
+            Some(Err(err)) => Err(S::Error::custom(err)),
+            None => Err(S::Error::custom(format_args!(
+                "Userdata cannot be serialized: {:?}",
+                self
+            ))),
+        }
+    }
+}
+
+pub fn deserialize_userdata<'de, D>(
+    seed: &mut DeSeed,
+    deserializer: D,
+) -> Result<GcPtr<Box<Userdata>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use base::serialization::SharedSeed;
+
+    struct UserdataSeed<'a> {
+        state: &'a mut DeSeed,
+    }
+    impl<'a> AsMut<NodeMap> for UserdataSeed<'a> {
+        fn as_mut(&mut self) -> &mut NodeMap {
+            &mut self.state.gc_map
+        }
+    }
+
+    impl<'de, 'a> DeserializeState<'de, UserdataSeed<'a>> for GcPtr<Box<Userdata>> {
+        fn deserialize_state<D>(
+            seed: &mut UserdataSeed<'a>,
+            deserializer: D,
+        ) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let seed = &mut seed.state;
+            let (name, bytes) = <(String, Vec<u8>)>::deserialize(deserializer)?;
+            let value = seed.thread
+                .global_env()
+                .deserialize_userdata(&seed.thread, &name, &bytes)
+                .map_err(D::Error::custom)?;
+            match value {
+                Value::Userdata(data) => Ok(data),
+                _ => Err(D::Error::custom("Expected userdata")),
+            }
+        }
+    }
+
+    let mut seed = UserdataSeed { state: seed };
+
+    DeserializeSeed::deserialize(SharedSeed::new(&mut seed), deserializer)
+}
+
+/// Serializes the stack of `thread`, allowing it to be resumed later by `deserialize_thread`,
+/// possibly in another process.
+///
+/// The thread must be parked (see `ThreadInternal::is_parked`), for instance suspended at a call
+/// to `std.thread.yield` or not yet started, otherwise an error is returned. Every value reachable
+/// from its stack is serialized which fails if any of them is userdata which does not implement
+/// `SerializableUserdata`. Extern functions are serialized by name and must be loaded in the
+/// virtual machine which deserializes the thread.
+pub fn serialize_thread<S>(thread: &Thread, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
     use serde::ser::Error;
-    Err(S::Error::custom("Userdata cannot be serialized"))
+    if !thread.is_parked() {
+        return Err(S::Error::custom(
+            "Only parked threads can be serialized but the thread is running an extern function \
+             or waiting on a future",
+        ));
+    }
+    let context = thread.context();
+    context.stack.serialize_state(serializer, &SeSeed::new())
+}
+
+/// Deserializes a thread serialized by `serialize_thread` as a new child thread of `parent`. The
+/// returned thread continues where the serialized thread was suspended once it is resumed.
+pub fn deserialize_thread<'de, D>(parent: &Thread, deserializer: D) -> Result<RootedThread, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let thread = parent.new_thread().map_err(D::Error::custom)?;
+    let stack: Stack = DeSeed::new(&thread).deserialize(deserializer)?;
+    thread.context().stack = stack;
+    Ok(thread)
 }
 
 #[cfg(test)]
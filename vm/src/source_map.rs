@@ -32,6 +32,16 @@ impl SourceMap {
         self.map.push((instruction_index, current_line));
     }
 
+    /// Updates the instruction indexes after the instructions have been rewritten.
+    /// `new_index[i]` must be the new index of the instruction previously at index `i`.
+    pub fn remap(&mut self, new_index: &[usize]) {
+        for entry in &mut self.map {
+            entry.0 = new_index[entry.0];
+        }
+        // If several instructions were merged the line of the first instruction is kept
+        self.map.dedup_by_key(|entry| entry.0);
+    }
+
     /// Returns the line where the instruction at `instruction_index` were defined
     pub fn line(&self, instruction_index: usize) -> Option<Line> {
         // The line for `instruction_index` is at the last index still larger than
@@ -100,6 +110,15 @@ impl LocalMap {
         }
     }
 
+    /// Updates the instruction indexes after the instructions have been rewritten.
+    /// `new_index[i]` must be the new index of the instruction previously at index `i`.
+    pub fn remap(&mut self, new_index: &[usize]) {
+        for local in &mut self.map {
+            local.start = new_index[local.start];
+            local.end = new_index[local.end];
+        }
+    }
+
     /// Returns an iterator over the variables in scope at `instruction_index`
     pub fn locals(&self, instruction_index: usize) -> LocalIter {
         LocalIter {
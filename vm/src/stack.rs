@@ -132,6 +132,13 @@ impl Stack {
         }
     }
 
+    /// Removes all frames and values which were added after the stack contained `frames` frames
+    /// and `len` values
+    pub fn unwind(&mut self, frames: usize, len: VmIndex) {
+        self.frames.truncate(frames);
+        self.values.truncate(len as usize);
+    }
+
     /// Release a lock on the stack.
     ///
     /// Panics if the lock is not the top-most lock
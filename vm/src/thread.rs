@@ -8,7 +8,7 @@ use std::ops::{Add, Deref, DerefMut, Div, Mul, Sub};
 use std::string::String as StdString;
 use std::result::Result as StdResult;
 use std::sync::Arc;
-use std::sync::atomic::{self, AtomicBool};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 use std::usize;
 
 use futures::{Async, Future, Poll};
@@ -24,13 +24,16 @@ use {Error, Result, Variants};
 use macros::MacroEnv;
 use api::{Getable, Pushable, ValueRef, VmType};
 use compiler::UpvarInfo;
-use gc::{DataDef, Gc, GcPtr, Generation, Move};
+use dynamic::RecordRef;
+use gc::{DataDef, Gc, GcPtr, GcStats, Generation, Move};
+use heap_snapshot::HeapSnapshot;
 use source_map::LocalIter;
 use stack::{Frame, Lock, Stack, StackFrame, State};
 use types::*;
 use vm::{GlobalVmState, VmEnv};
 use value::{BytecodeFunction, Callable, ClosureData, ClosureDataDef, ClosureInitDef, Def,
-            ExternFunction, GcStr, PartialApplicationDataDef, RecordDef, Userdata, Value};
+            ExternFunction, GcStr, PartialApplicationDataDef, RecordDef, SerializableUserdata,
+            Userdata, Value};
 
 use value::Value::{Closure, Data, Float, Function, Int, PartialApplication, String};
 
@@ -292,6 +295,9 @@ pub struct Thread {
     child_threads: RwLock<Vec<GcPtr<Thread>>>,
     #[cfg_attr(feature = "serde_derive", serde(state))] context: Mutex<Context>,
     #[cfg_attr(feature = "serde_derive", serde(skip))] interrupt: AtomicBool,
+    /// The number of extern functions which this thread is currently in the middle of calling
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    extern_calls: AtomicUsize,
 }
 
 impl fmt::Debug for Thread {
@@ -408,6 +414,7 @@ impl RootedThread {
             rooted_values: RwLock::new(Vec::new()),
             child_threads: RwLock::new(Vec::new()),
             interrupt: AtomicBool::new(false),
+            extern_calls: AtomicUsize::new(0),
         };
         let mut gc = Gc::new(Generation::default(), usize::MAX);
         let vm = gc.alloc(Move(thread))
@@ -450,6 +457,7 @@ impl Thread {
             rooted_values: RwLock::new(Vec::new()),
             child_threads: RwLock::new(Vec::new()),
             interrupt: AtomicBool::new(false),
+            extern_calls: AtomicUsize::new(0),
         };
         // Enter the top level scope
         {
@@ -502,6 +510,23 @@ impl Thread {
     ///
     #[deprecated(since = "0.7.0", note = "Use `gluon::import::add_extern_module` instead")]
     pub fn define_global<'vm, T>(&'vm self, name: &str, value: T) -> Result<()>
+    where
+        T: Pushable<'vm> + VmType,
+    {
+        self.define_global_with_metadata(name, value, Metadata::default())
+    }
+
+    /// Creates a new global value at `name` which is documented by `metadata`.
+    /// Fails if a global called `name` already exists.
+    ///
+    /// Values which are meant to be imported as a module should be added with
+    /// `gluon::import::add_extern_module` and `ExternModule::with_metadata` instead.
+    pub fn define_global_with_metadata<'vm, T>(
+        &'vm self,
+        name: &str,
+        value: T,
+        metadata: Metadata,
+    ) -> Result<()>
     where
         T: Pushable<'vm> + VmType,
     {
@@ -513,7 +538,7 @@ impl Thread {
         self.set_global(
             Symbol::from(format!("@{}", name)),
             T::make_forall_type(self),
-            Metadata::default(),
+            metadata,
             value,
         )
     }
@@ -565,6 +590,21 @@ impl Thread {
         }
     }
 
+    /// Retrieves the global `name` as a `RecordRef` whose fields can be retrieved by name without
+    /// knowing the type of the whole record beforehand.
+    ///
+    /// # Errors
+    ///
+    /// if the global does not exist or is not a record.
+    pub fn get_global_record<'vm>(&'vm self, name: &str) -> Result<RecordRef<'vm, &'vm Thread>> {
+        let (value, typ) = {
+            let env = self.get_env();
+            let (value, typ) = env.get_binding(name)?;
+            (value, typ.into_owned())
+        };
+        RecordRef::new(self.root_value(value), typ)
+    }
+
     /// Retrieves type information about the type `name`. Types inside records can be accessed
     /// using dot notation (std.prelude.Option)
     pub fn find_type_info(&self, name: &str) -> Result<types::Alias<Symbol, ArcType>> {
@@ -582,6 +622,11 @@ impl Thread {
         self.global_env().register_type::<T>(name, args)
     }
 
+    /// Registers `T` so that values of it can be deserialized as part of values or threads
+    pub fn register_serializable_userdata<T: SerializableUserdata>(&self) -> Result<()> {
+        self.global_env().register_serializable_userdata::<T>()
+    }
+
     /// Locks and retrieves the global environment of the vm
     pub fn get_env<'b>(&'b self) -> RwLockReadGuard<'b, VmEnv> {
         self.global_env().get_env()
@@ -600,6 +645,52 @@ impl Thread {
         })
     }
 
+    /// Runs a minor garbage collection which only frees values that were allocated since the
+    /// last collection.
+    pub fn collect_minor(&self) {
+        let mut context = self.current_context();
+        self.with_roots(&mut context, |gc, roots| unsafe {
+            gc.collect_minor(roots);
+        })
+    }
+
+    /// Walks every value reachable from this thread and returns a snapshot of them which can be
+    /// used to find what is keeping memory alive (see the `heap_snapshot` module).
+    ///
+    /// The roots are the stack of this thread, its rooted values, its child threads and the
+    /// globals, interned strings and generation 0 threads of the vm.
+    pub fn heap_snapshot(&self) -> HeapSnapshot {
+        let mut walker = Gc::heap_walker();
+        let context = self.context.lock().unwrap();
+
+        // The context of this thread is already locked so `self` must not be traversed normally
+        walker.walk_root("thread", |gc| {
+            gc.mark(unsafe { GcPtr::from_raw(self) });
+        });
+        walker.walk_root("stack", |gc| context.stack.traverse(gc));
+        walker.walk_root("rooted values", |gc| {
+            self.rooted_values.read().unwrap().traverse(gc)
+        });
+        walker.walk_root("roots", |gc| self.roots.read().unwrap().traverse(gc));
+        walker.walk_root("child threads", |gc| {
+            self.child_threads.read().unwrap().traverse(gc)
+        });
+        self.global_state.walk_heap_roots(&mut walker);
+
+        walker.finish_walk()
+    }
+
+    /// Returns statistics about the garbage collector and the execution of this thread
+    pub fn stats(&self) -> ThreadStats {
+        let context = self.current_context();
+        ThreadStats {
+            gc: context.gc.stats(),
+            stack_depth: context.stack.get_frames().len(),
+            stack_size: context.stack.len() as usize,
+            instructions_executed: context.instructions_executed,
+        }
+    }
+
     /// Pushes a value to the top of the stack
     pub fn push<'vm, T>(&'vm self, v: T) -> Result<()>
     where
@@ -626,6 +717,11 @@ impl Thread {
         self.interrupt.load(atomic::Ordering::Relaxed)
     }
 
+    /// Clears the interrupt flag, allowing the thread to execute code again
+    pub fn clear_interrupt(&self) {
+        self.interrupt.store(false, atomic::Ordering::Relaxed)
+    }
+
     fn current_context(&self) -> OwnedContext {
         self.context()
     }
@@ -743,6 +839,15 @@ where
     fn deep_clone_value(&self, owner: &Thread, value: Value) -> Result<Value>;
 
     fn can_share_values_with(&self, gc: &mut Gc, other: &Thread) -> bool;
+
+    /// Returns the flag which is set when the thread is interrupted
+    fn interrupt_flag(&self) -> &AtomicBool;
+
+    /// Returns `true` if the thread is parked, that is, it is not in the middle of calling an
+    /// extern function (such as when it is the thread which is currently running) and it is not
+    /// waiting for a future to complete. The stack of a parked thread contains everything needed
+    /// to resume it.
+    fn is_parked(&self) -> bool;
 }
 
 impl ThreadInternal for Thread {
@@ -902,6 +1007,15 @@ impl ThreadInternal for Thread {
         }
         false
     }
+
+    fn interrupt_flag(&self) -> &AtomicBool {
+        &self.interrupt
+    }
+
+    fn is_parked(&self) -> bool {
+        self.extern_calls.load(atomic::Ordering::SeqCst) == 0
+            && self.current_context().poll_fns.is_empty()
+    }
 }
 
 pub type HookFn = Box<FnMut(&Thread, DebugInfo) -> Result<Async<()>> + Send + Sync>;
@@ -1043,6 +1157,29 @@ pub struct Context {
             Box<for<'vm> FnMut(&'vm Thread) -> Result<Async<OwnedContext<'vm>>> + Send>,
         ),
     >,
+
+    /// The number of instructions this thread has executed
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    instructions_executed: u64,
+}
+
+/// Statistics about a thread, returned by `Thread::stats`
+#[derive(Clone, Debug)]
+pub struct ThreadStats {
+    pub gc: GcStats,
+    /// The number of frames on the stack
+    pub stack_depth: usize,
+    /// The number of values on the stack
+    pub stack_size: usize,
+    pub instructions_executed: u64,
+}
+
+/// A position in the stack of a `Context` which the stack can later be unwound to
+#[derive(Clone, Copy, Debug)]
+pub struct StackMarker {
+    frames: usize,
+    values: VmIndex,
+    poll_fns: usize,
 }
 
 impl Context {
@@ -1057,6 +1194,7 @@ impl Context {
             },
             max_stack_size: VmIndex::max_value(),
             poll_fns: Vec::new(),
+            instructions_executed: 0,
         }
     }
 
@@ -1070,6 +1208,72 @@ impl Context {
         ).map(Value::Data)
     }
 
+    /// Pops the top `fields` values of the stack and pushes a variant with `tag` containing them
+    pub fn push_new_data(&mut self, thread: &Thread, tag: VmTag, fields: usize) -> Result<()> {
+        let fields = fields as VmIndex;
+        let value = if fields == 0 {
+            Value::Tag(tag)
+        } else {
+            let offset = self.stack.len() - fields;
+            Value::Data(alloc(
+                &mut self.gc,
+                thread,
+                &self.stack,
+                Def {
+                    tag: tag,
+                    elems: &self.stack[offset..],
+                },
+            )?)
+        };
+        for _ in 0..fields {
+            self.stack.pop();
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    /// Pops one value for each name in `fields` from the stack and pushes a record where the
+    /// fields are bound to those values in order
+    pub fn push_new_record(&mut self, thread: &Thread, fields: &[&str]) -> Result<()> {
+        let fields = fields
+            .iter()
+            .map(|field| thread.global_env().intern(field))
+            .collect::<Result<Vec<_>>>()?;
+        let len = fields.len() as VmIndex;
+        let offset = self.stack.len() - len;
+        let value = alloc(
+            &mut self.gc,
+            thread,
+            &self.stack,
+            RecordDef {
+                elems: &self.stack[offset..],
+                fields: &fields,
+            },
+        )?;
+        for _ in 0..len {
+            self.stack.pop();
+        }
+        self.stack.push(Value::Data(value));
+        Ok(())
+    }
+
+    /// Pops the top `len` values of the stack and pushes an array containing them
+    pub fn push_new_array(&mut self, thread: &Thread, len: usize) -> Result<()> {
+        let len = len as VmIndex;
+        let offset = self.stack.len() - len;
+        let value = alloc(
+            &mut self.gc,
+            thread,
+            &self.stack,
+            ::value::ArrayDef(&self.stack[offset..]),
+        )?;
+        for _ in 0..len {
+            self.stack.pop();
+        }
+        self.stack.push(Value::Array(value));
+        Ok(())
+    }
+
     pub fn alloc_with<D>(&mut self, thread: &Thread, data: D) -> Result<GcPtr<D::Value>>
     where
         D: DataDef + Traverseable,
@@ -1098,6 +1302,23 @@ impl Context {
         self.max_stack_size = limit;
     }
 
+    /// Returns a marker for the current position of the stack
+    pub fn stack_marker(&self) -> StackMarker {
+        StackMarker {
+            frames: self.stack.get_frames().len(),
+            values: self.stack.len(),
+            poll_fns: self.poll_fns.len(),
+        }
+    }
+
+    /// Removes every frame, value and pending future which were added after `marker` was
+    /// created. Used to restore a thread which were aborted in the middle of execution (for
+    /// instance by `Thread::interrupt`) so that it can be used again.
+    pub fn unwind(&mut self, marker: StackMarker) {
+        self.poll_fns.truncate(marker.poll_fns);
+        self.stack.unwind(marker.frames, marker.values);
+    }
+
     /// "Returns a future", letting the virtual machine know that `future` must be resolved to
     /// produce the actual value.
     ///
@@ -1317,7 +1538,9 @@ impl<'b> OwnedContext<'b> {
             // Necessary since we do not know what will happen during the function call
             let thread = self.thread;
             drop(self);
+            thread.extern_calls.fetch_add(1, atomic::Ordering::SeqCst);
             status = (function.function)(thread);
+            thread.extern_calls.fetch_sub(1, atomic::Ordering::SeqCst);
 
             if status == Status::Yield {
                 return Ok(Async::NotReady);
@@ -1431,6 +1654,7 @@ impl<'b> OwnedContext<'b> {
             gc: &mut context.gc,
             stack: StackFrame::current(&mut context.stack),
             hook: &mut context.hook,
+            instructions_executed: &mut context.instructions_executed,
         }
     }
 }
@@ -1440,6 +1664,7 @@ struct ExecuteContext<'b> {
     stack: StackFrame<'b>,
     gc: &'b mut Gc,
     hook: &'b mut Hook,
+    instructions_executed: &'b mut u64,
 }
 
 impl<'b> ExecuteContext<'b> {
@@ -1577,6 +1802,7 @@ impl<'b> ExecuteContext<'b> {
         }
         while let Some(&instr) = instructions.get(index) {
             debug_instruction(&self.stack, index, instr);
+            *self.instructions_executed += 1;
 
             if self.hook.flags.contains(HookFlags::LINE_FLAG) {
                 if let Some(ref mut hook) = self.hook.function {
@@ -1734,7 +1960,10 @@ impl<'b> ExecuteContext<'b> {
                     let field = function.strings[i as usize];
                     match self.stack.pop() {
                         Data(data) => {
-                            let v = data.get_field(field).expect("ICE: Field does not exist");
+                            let v = function
+                                .field_caches
+                                .get_field(index, &data, field)
+                                .expect("ICE: Field does not exist");
                             self.stack.push(*v);
                         }
                         x => return Err(Error::Message(format!("GetField on {:?}", x))),
@@ -1836,6 +2065,9 @@ impl<'b> ExecuteContext<'b> {
                                     *var = self.stack.pop();
                                 }
                             }
+                            // A collection may have run since the closure were allocated so it
+                            // may need to be remembered
+                            self.gc.write_barrier(closure);
                             self.stack.pop(); //Remove the closure
                         }
                         x => ice!("Expected closure, got {:?}", x),
@@ -1845,10 +2077,72 @@ impl<'b> ExecuteContext<'b> {
                     let v = self.stack.get_upvar(i).clone();
                     self.stack.push(v);
                 }
-                AddInt => binop_int(self.thread, &mut self.stack, VmInt::add),
-                SubtractInt => binop_int(self.thread, &mut self.stack, VmInt::sub),
-                MultiplyInt => binop_int(self.thread, &mut self.stack, VmInt::mul),
-                DivideInt => binop_int(self.thread, &mut self.stack, VmInt::div),
+                PushPair { first, second } => {
+                    let v = self.stack[first];
+                    self.stack.push(v);
+                    let v = self.stack[second];
+                    self.stack.push(v);
+                }
+                AddIntLocals { lhs, rhs } => {
+                    let l = self.stack[lhs];
+                    // `rhs` may refer to the slot that `Push(lhs)` would have pushed to
+                    let r = if rhs == self.stack.len() {
+                        l
+                    } else {
+                        self.stack[rhs]
+                    };
+                    match (l, r) {
+                        (Int(l), Int(r)) => match l.checked_add(r) {
+                            Some(result) => self.stack.push(Int(result)),
+                            None => return Err(int_error("+", l, r)),
+                        },
+                        (l, r) => {
+                            return Err(Error::Message(format!(
+                                "AddIntLocals on {:?} and {:?}",
+                                l,
+                                r
+                            )))
+                        }
+                    }
+                }
+                TestTagJump { tag, target } => {
+                    let data_tag = match self.stack.top() {
+                        Data(ref data) => data.tag(),
+                        Value::Tag(tag) => tag,
+                        _ => {
+                            return Err(Error::Message(
+                                "Op TestTagJump called on non data type".to_string(),
+                            ))
+                        }
+                    };
+                    if data_tag == tag {
+                        index = target as usize;
+                        continue;
+                    }
+                }
+                GetOffsetPair { first, second } => match self.stack.pop() {
+                    Data(data) => match data.fields[first as usize] {
+                        Data(inner) => {
+                            let v = inner.fields[second as usize];
+                            self.stack.push(v);
+                        }
+                        x => return Err(Error::Message(format!("GetOffset on {:?}", x))),
+                    },
+                    x => return Err(Error::Message(format!("GetOffset on {:?}", x))),
+                },
+
+                AddInt => {
+                    checked_binop_int(self.thread, &mut self.stack, "+", VmInt::checked_add)?
+                }
+                SubtractInt => {
+                    checked_binop_int(self.thread, &mut self.stack, "-", VmInt::checked_sub)?
+                }
+                MultiplyInt => {
+                    checked_binop_int(self.thread, &mut self.stack, "*", VmInt::checked_mul)?
+                }
+                DivideInt => {
+                    checked_binop_int(self.thread, &mut self.stack, "/", VmInt::checked_div)?
+                }
                 IntLT => binop_bool(self.thread, &mut self.stack, |l: VmInt, r| l < r),
                 IntEQ => binop_bool(self.thread, &mut self.stack, |l: VmInt, r| l == r),
 
@@ -1865,6 +2159,24 @@ impl<'b> ExecuteContext<'b> {
                 DivideFloat => binop_f64(self.thread, &mut self.stack, f64::div),
                 FloatLT => binop_bool(self.thread, &mut self.stack, |l: f64, r| l < r),
                 FloatEQ => binop_bool(self.thread, &mut self.stack, |l: f64, r| l == r),
+
+                BitAndInt => binop(self.thread, &mut self.stack, |l: VmInt, r| Int(l & r)),
+                BitOrInt => binop(self.thread, &mut self.stack, |l: VmInt, r| Int(l | r)),
+                BitXorInt => binop(self.thread, &mut self.stack, |l: VmInt, r| Int(l ^ r)),
+                ShiftLeftInt => {
+                    checked_binop_int(self.thread, &mut self.stack, "<<", checked_shl)?
+                }
+                ShiftRightInt => {
+                    checked_binop_int(self.thread, &mut self.stack, ">>", checked_shr)?
+                }
+                IntToFloat => match self.stack.pop() {
+                    Int(i) => self.stack.push(Float(i as f64)),
+                    x => return Err(Error::Message(format!("IntToFloat on {:?}", x))),
+                },
+                ByteToInt => match self.stack.pop() {
+                    Value::Byte(b) => self.stack.push(Int(b as VmInt)),
+                    x => return Err(Error::Message(format!("ByteToInt on {:?}", x))),
+                },
             }
             index += 1;
         }
@@ -1913,13 +2225,48 @@ impl<'b> ExecuteContext<'b> {
     }
 }
 
+/// Integer arithmetic is checked, raising an error instead of wrapping around or panicking
 #[inline]
-fn binop_int<'b, F, T>(vm: &'b Thread, stack: &mut StackFrame<'b>, f: F)
-where
-    F: FnOnce(T, T) -> VmInt,
-    T: Getable<'b> + fmt::Debug,
-{
-    binop(vm, stack, |l, r| Value::Int(f(l, r)))
+fn checked_binop_int<'b>(
+    vm: &'b Thread,
+    stack: &mut StackFrame<'b>,
+    operation: &'static str,
+    f: fn(VmInt, VmInt) -> Option<VmInt>,
+) -> Result<()> {
+    let (l, r) = {
+        let r = stack.get_variants(stack.len() - 1).unwrap();
+        let l = stack.get_variants(stack.len() - 2).unwrap();
+        (VmInt::from_value(vm, l), VmInt::from_value(vm, r))
+    };
+    let result = f(l, r).ok_or_else(|| int_error(operation, l, r))?;
+    stack.pop();
+    stack.pop();
+    stack.stack.push(Value::Int(result));
+    Ok(())
+}
+
+fn shift_amount(r: VmInt) -> Option<u32> {
+    if 0 <= r && r < (mem::size_of::<VmInt>() * 8) as VmInt {
+        Some(r as u32)
+    } else {
+        None
+    }
+}
+
+fn checked_shl(l: VmInt, r: VmInt) -> Option<VmInt> {
+    shift_amount(r).map(|r| l << r)
+}
+
+fn checked_shr(l: VmInt, r: VmInt) -> Option<VmInt> {
+    shift_amount(r).map(|r| l >> r)
+}
+
+fn int_error(operation: &str, l: VmInt, r: VmInt) -> Error {
+    if operation == "/" && r == 0 {
+        Error::DivideByZero
+    } else {
+        Error::Overflow(format!("{} {} {}", l, operation, r))
+    }
 }
 
 #[inline]
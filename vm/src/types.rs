@@ -94,6 +94,18 @@ pub enum Instruction {
     /// Fills the previously allocated closure with `n` upvariables.
     CloseClosure(VmIndex),
 
+    // Superinstructions which are created by the peephole optimizer (see `vm::peephole`) by
+    // fusing a sequence of instructions into a single instruction.
+    /// `Push(first); Push(second)`
+    PushPair { first: VmIndex, second: VmIndex },
+    /// `Push(lhs); Push(rhs); AddInt`
+    AddIntLocals { lhs: VmIndex, rhs: VmIndex },
+    /// `TestTag(tag); CJump(target)`. Jumps to `target` if the value at the top of the stack is
+    /// tagged with `tag`, the value is left on the stack in either case.
+    TestTagJump { tag: VmTag, target: VmIndex },
+    /// `GetOffset(first); GetOffset(second)`
+    GetOffsetPair { first: VmIndex, second: VmIndex },
+
     AddInt,
     SubtractInt,
     MultiplyInt,
@@ -114,6 +126,19 @@ pub enum Instruction {
     DivideFloat,
     FloatLT,
     FloatEQ,
+
+    BitAndInt,
+    BitOrInt,
+    BitXorInt,
+    /// Shifts are checked, shifting by a negative amount or by more than the width of an `Int`
+    /// is an error
+    ShiftLeftInt,
+    /// Arithmetic (sign extending) shift
+    ShiftRightInt,
+    /// Converts the `Int` at the top of the stack to a `Float`
+    IntToFloat,
+    /// Converts the `Byte` at the top of the stack to an `Int`
+    ByteToInt,
 }
 
 
@@ -140,9 +165,14 @@ impl Instruction {
             NewClosure { .. } => 1,
             CloseClosure(_) => -1,
             PushUpVar(_) => 1,
+            PushPair { .. } => 2,
+            AddIntLocals { .. } => 1,
+            TestTagJump { .. } | GetOffsetPair { .. } => 0,
             AddInt | SubtractInt | MultiplyInt | DivideInt | IntLT | IntEQ | AddFloat | AddByte
             | SubtractByte | MultiplyByte | DivideByte | ByteLT | ByteEQ | SubtractFloat
             | MultiplyFloat | DivideFloat | FloatLT | FloatEQ => -1,
+            BitAndInt | BitOrInt | BitXorInt | ShiftLeftInt | ShiftRightInt => -1,
+            IntToFloat | ByteToInt => 0,
         }
     }
 }
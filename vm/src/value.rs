@@ -2,6 +2,7 @@ use std::collections::hash_map::Entry;
 use std::fmt;
 use std::mem::size_of;
 use std::result::Result as StdResult;
+use std::string::String as StdString;
 
 use itertools::Itertools;
 
@@ -15,7 +16,9 @@ use base::types::pretty_print::ident as pretty_ident;
 
 use interner::InternedStr;
 use compiler::DebugInfo;
-use gc::{DataDef, Gc, GcPtr, Generation, Move, Traverseable, WriteOnly};
+use inline_cache::FieldCaches;
+use gc::{DataDef, ExternalSizeFn, Finalize, Gc, GcPtr, Generation, Move, SweepFn, TraverseFn,
+         Traverseable, WeakRef, WriteOnly};
 use array::Array;
 use thread::{Status, Thread};
 use {Error, Result, Variants};
@@ -28,6 +31,161 @@ pub trait Userdata: ::mopa::Any + Traverseable + fmt::Debug + Send + Sync {
         let _ = deep_cloner;
         Err(Error::Message("Userdata cannot be cloned".into()))
     }
+
+    /// Returns `true` if the value may be changed to refer to other garbage collected values after
+    /// it has been allocated, in which case it is scanned on every minor collection. Types which
+    /// never refer to garbage collected values (or never change which values they refer to) should
+    /// return `false` to avoid the cost of the scan.
+    fn is_mutable(&self) -> bool {
+        true
+    }
+
+    /// Returns `true` if the value owns memory outside of the garbage collected heap, which is
+    /// counted against the memory limit through `external_size`
+    fn has_external_memory(&self) -> bool {
+        false
+    }
+
+    /// Returns the number of bytes that the value currently owns outside of the garbage collected
+    /// heap. The bytes are counted against the memory limit until the value is freed. If the value
+    /// grows after it has been allocated the added bytes must first be checked against the limit
+    /// and reported through `Gc::add_external_memory`. Only called if `has_external_memory`
+    /// returns `true`.
+    fn external_size(&self) -> usize {
+        0
+    }
+
+    /// Returns `true` if `finalize` should be called once the value becomes unreachable
+    fn has_finalizer(&self) -> bool {
+        false
+    }
+
+    /// Called once the garbage collector has found that the value is unreachable but before any
+    /// value is freed. Only called if `has_finalizer` returns `true`.
+    fn finalize(&self) {}
+
+    /// Returns `true` if the value is a weak reference which needs to be notified through
+    /// `clear_dead` after each collection
+    fn is_weak(&self) -> bool {
+        false
+    }
+
+    /// See `WeakRef::clear_dead`. Only called if `is_weak` returns `true`.
+    fn clear_dead(&self, gc: &Gc) {
+        let _ = gc;
+    }
+
+    /// Returns the name and the serialized bytes of the value. Types implementing
+    /// `SerializableUserdata` opt in to serialization by overriding this method to call
+    /// `SerializableUserdata::to_serialized`. Returns `None` if the value cannot be serialized.
+    fn serialize_userdata(&self) -> Option<StdResult<(&'static str, Vec<u8>), StdString>> {
+        None
+    }
+}
+
+/// Userdata which can be serialized, which allows values and suspended threads containing it to
+/// be serialized as well.
+///
+/// Besides implementing this trait, a type needs to forward `Userdata::serialize_userdata` to
+/// `to_serialized` and be registered with `Thread::register_serializable_userdata` in the virtual
+/// machine which deserializes it. The `serializable_userdata!` macro implements both `Userdata`
+/// and this trait with the forwarding in place.
+pub trait SerializableUserdata: Userdata + Sized {
+    /// The name which identifies the type in serialized data. Must be unique among the registered
+    /// types.
+    fn name() -> &'static str;
+
+    fn to_bytes(&self) -> StdResult<Vec<u8>, StdString>;
+
+    fn from_bytes(bytes: &[u8]) -> StdResult<Self, StdString>;
+
+    fn to_serialized(&self) -> StdResult<(&'static str, Vec<u8>), StdString> {
+        self.to_bytes().map(|bytes| (Self::name(), bytes))
+    }
+}
+
+unsafe fn traverse_userdata(ptr: *const (), gc: &mut Gc) {
+    (*(ptr as *const Box<Userdata>)).traverse(gc)
+}
+
+unsafe fn finalize_userdata(ptr: *const (), _: &Gc) {
+    (*(ptr as *const Box<Userdata>)).finalize()
+}
+
+unsafe fn clear_dead_userdata(ptr: *const (), gc: &Gc) {
+    (*(ptr as *const Box<Userdata>)).clear_dead(gc)
+}
+
+unsafe fn userdata_external_size(ptr: *const ()) -> usize {
+    (*(ptr as *const Box<Userdata>)).external_size()
+}
+
+/// `DataDef` for userdata which is only marked as mutable (see `MoveMutable`) if
+/// `Userdata::is_mutable` returns `true`. Userdata with a finalizer, which is a weak reference or
+/// which owns external memory is registered with the garbage collector as it is allocated.
+pub struct MoveUserdata(pub Box<Userdata>);
+
+unsafe impl DataDef for MoveUserdata {
+    type Value = Box<Userdata>;
+    fn size(&self) -> usize {
+        size_of::<Box<Userdata>>()
+    }
+    fn initialize(self, result: WriteOnly<Box<Userdata>>) -> &mut Box<Userdata> {
+        result.write(self.0)
+    }
+    fn mutable_traverse(&self) -> Option<TraverseFn> {
+        if self.0.is_mutable() {
+            Some(traverse_userdata)
+        } else {
+            None
+        }
+    }
+    fn external_size(&self) -> usize {
+        if self.0.has_external_memory() {
+            self.0.external_size()
+        } else {
+            0
+        }
+    }
+    fn external_size_fn(&self) -> Option<ExternalSizeFn> {
+        if self.0.has_external_memory() {
+            Some(userdata_external_size)
+        } else {
+            None
+        }
+    }
+    fn finalizer(&self) -> Option<SweepFn> {
+        if self.0.has_finalizer() {
+            Some(finalize_userdata)
+        } else {
+            None
+        }
+    }
+    fn weak_ref(&self) -> Option<SweepFn> {
+        if self.0.is_weak() {
+            Some(clear_dead_userdata)
+        } else {
+            None
+        }
+    }
+}
+
+impl Traverseable for MoveUserdata {
+    fn traverse(&self, gc: &mut Gc) {
+        self.0.traverse(gc)
+    }
+}
+
+impl Finalize for Box<Userdata> {
+    fn finalize(&self) {
+        (**self).finalize()
+    }
+}
+
+impl WeakRef for Box<Userdata> {
+    fn clear_dead(&self, gc: &Gc) {
+        (**self).clear_dead(gc)
+    }
 }
 
 impl PartialEq for Userdata {
@@ -115,6 +273,10 @@ pub struct BytecodeFunction {
     #[cfg_attr(feature = "serde_derive", serde(state))] pub strings: Vec<InternedStr>,
     #[cfg_attr(feature = "serde_derive", serde(state))] pub records: Vec<Vec<InternedStr>>,
     #[cfg_attr(feature = "serde_derive", serde(state))] pub debug_info: DebugInfo,
+    /// Inline caches for the `GetField` instructions in `instructions`. Deserialized functions
+    /// start without any caches and look up every field by name.
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub field_caches: FieldCaches,
 }
 
 impl Traverseable for BytecodeFunction {
@@ -322,11 +484,10 @@ pub enum Value {
         #[cfg_attr(feature = "serde_derive", serde(serialize_state))]
         GcPtr<PartialApplicationData>,
     ),
-    // TODO Implement serializing of userdata
-    #[cfg_attr(feature = "serde_derive", serde(skip_deserializing))]
     Userdata(
         #[cfg_attr(feature = "serde_derive",
-                   serde(serialize_with = "::serialization::serialize_userdata"))]
+                   serde(deserialize_state_with = "::serialization::deserialize_userdata"))]
+        #[cfg_attr(feature = "serde_derive", serde(serialize_state))]
         GcPtr<Box<Userdata>>,
     ),
     #[cfg_attr(feature = "serde_derive", serde(skip_deserializing))]
@@ -348,6 +509,30 @@ impl Value {
             Value::Tag(_) | Value::Byte(_) | Int(_) | Float(_) => Generation::default(),
         }
     }
+
+    /// Returns `true` if `self` points to a value allocated by a garbage collector
+    pub fn is_gc_ptr(self) -> bool {
+        match self {
+            Value::Tag(_) | Value::Byte(_) | Int(_) | Float(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Returns `false` if `self` points to a value which is about to be freed by `gc`
+    /// (see `Gc::is_alive`)
+    pub fn is_alive(self, gc: &Gc) -> bool {
+        match self {
+            String(p) => gc.is_alive(p.into_inner()),
+            Value::Data(p) => gc.is_alive(p),
+            Function(p) => gc.is_alive(p),
+            Closure(p) => gc.is_alive(p),
+            Value::Array(p) => gc.is_alive(p),
+            PartialApplication(p) => gc.is_alive(p),
+            Value::Userdata(p) => gc.is_alive(p),
+            Value::Thread(p) => gc.is_alive(p),
+            Value::Tag(_) | Value::Byte(_) | Int(_) | Float(_) => true,
+        }
+    }
 }
 
 #[derive(PartialEq, Copy, Clone, PartialOrd)]
@@ -847,7 +1032,8 @@ macro_rules! impl_repr {
         }
         )*
         impl Repr {
-            fn size_of(self) -> usize {
+            /// The number of bytes used by each element of an array with this representation
+            pub fn size_of(self) -> usize {
                 use std::mem::size_of;
                 match self {
                     $(
@@ -871,7 +1057,8 @@ impl_repr! {
 }
 
 impl Repr {
-    fn from_value(value: Value) -> Repr {
+    /// The representation used by arrays whose elements are like `value`
+    pub fn from_value(value: Value) -> Repr {
         match value {
             Value::Byte(_) => Repr::Byte,
             Value::Int(_) => Repr::Int,
@@ -20,11 +20,16 @@ use interner::{InternedStr, Interner};
 use gc::{Gc, GcPtr, Generation, Move, Traverseable};
 use compiler::{CompiledFunction, CompiledModule, CompilerEnv, Variable};
 use api::IO;
+use fixed_int::{I32, Word};
+#[cfg(target_pointer_width = "64")]
+use fixed_int::U64;
 use lazy::Lazy;
+use inline_cache::FieldCaches;
 
-use value::{BytecodeFunction, ClosureData};
+use thread::ThreadInternal;
+use value::{BytecodeFunction, ClosureData, MoveUserdata};
 
-pub use value::{ClosureDataDef, Userdata};
+pub use value::{ClosureDataDef, SerializableUserdata, Userdata};
 pub use value::Value; //FIXME Value should not be exposed
 pub use thread::{Root, RootStr, RootedThread, RootedValue, Status, Thread};
 
@@ -86,6 +91,7 @@ fn new_bytecode_function(
         name: id,
         args: args,
         max_stack_size: max_stack_size,
+        field_caches: FieldCaches::new(&instructions),
         instructions: instructions,
         inner_functions: fs?,
         strings: strings,
@@ -142,6 +148,19 @@ pub struct GlobalVmState {
 
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     event_loop: Option<::std::panic::AssertUnwindSafe<::tokio_core::reactor::Remote>>,
+
+    /// Deserializers for the userdata types registered through `register_serializable_userdata`
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    userdata_deserializers: RwLock<FnvMap<StdString, DeserializeUserdata>>,
+}
+
+type DeserializeUserdata = fn(&Thread, &[u8]) -> Result<Value>;
+
+fn deserialize_userdata<T: SerializableUserdata>(thread: &Thread, bytes: &[u8]) -> Result<Value> {
+    let data: Box<Userdata> = Box::new(T::from_bytes(bytes).map_err(Error::Message)?);
+    let mut context = thread.context();
+    let userdata = context.gc.alloc(MoveUserdata(data))?;
+    Ok(Value::Userdata(userdata))
 }
 
 impl Traverseable for GlobalVmState {
@@ -373,6 +392,7 @@ impl GlobalVmState {
             type_cache: TypeCache::new(),
             generation_0_threads: RwLock::new(Vec::new()),
             event_loop: event_loop.map(::std::panic::AssertUnwindSafe),
+            userdata_deserializers: RwLock::new(FnvMap::default()),
         };
         vm.add_types().unwrap();
         vm
@@ -414,6 +434,10 @@ impl GlobalVmState {
         self.register_type::<Lazy<Generic<A>>>("Lazy", &["a"])
             .unwrap();
         self.register_type::<Thread>("Thread", &[]).unwrap();
+        self.register_type::<I32>("I32", &[]).unwrap();
+        #[cfg(target_pointer_width = "64")]
+        self.register_type::<U64>("U64", &[]).unwrap();
+        self.register_type::<Word>("Word", &[]).unwrap();
         Ok(())
     }
 
@@ -487,6 +511,34 @@ impl GlobalVmState {
         g
     }
 
+    /// Registers `T` so that values of it can be deserialized, see `SerializableUserdata`
+    pub fn register_serializable_userdata<T: SerializableUserdata>(&self) -> Result<()> {
+        let mut deserializers = self.userdata_deserializers.write().unwrap();
+        if deserializers.contains_key(T::name()) {
+            return Err(Error::Message(format!(
+                "Userdata `{}` is already registered for serialization",
+                T::name()
+            )));
+        }
+        deserializers.insert(T::name().into(), deserialize_userdata::<T>);
+        Ok(())
+    }
+
+    /// Deserializes the userdata registered as `name` from `bytes`, allocating it in `thread`
+    pub fn deserialize_userdata(&self, thread: &Thread, name: &str, bytes: &[u8]) -> Result<Value> {
+        let deserialize = *self.userdata_deserializers
+            .read()
+            .unwrap()
+            .get(name)
+            .ok_or_else(|| {
+                Error::Message(format!(
+                    "Userdata `{}` has not been registered for serialization",
+                    name
+                ))
+            })?;
+        deserialize(thread, bytes)
+    }
+
     /// Registers a new type called `name`
     pub fn register_type<T: ?Sized + Any>(&self, name: &str, args: &[&str]) -> Result<ArcType> {
         let mut env = self.env.write().unwrap();
@@ -529,4 +581,21 @@ impl GlobalVmState {
     pub fn get_env<'b>(&'b self) -> RwLockReadGuard<'b, VmEnv> {
         self.env.read().unwrap()
     }
+
+    /// Adds the globals, interned strings and generation 0 threads as roots to `walker` (see
+    /// `Gc::heap_walker`)
+    pub fn walk_heap_roots(&self, walker: &mut Gc) {
+        let env = self.env.read().unwrap();
+        let mut globals: Vec<_> = env.globals.iter().collect();
+        globals.sort_by(|l, r| l.0.cmp(r.0));
+        for (name, global) in globals {
+            walker.walk_root(&format!("global {}", name), |gc| global.traverse(gc));
+        }
+        walker.walk_root("interned strings", |gc| {
+            self.interner.read().unwrap().traverse(gc)
+        });
+        walker.walk_root("generation 0 threads", |gc| {
+            self.generation_0_threads.read().unwrap().traverse(gc)
+        });
+    }
 }
@@ -0,0 +1,124 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Mutex;
+use std::marker::PhantomData;
+
+use base::types::{ArcType, Type};
+use {ExternModule, Result};
+use gc::{Gc, GcPtr, Traverseable};
+use vm::Thread;
+use thread::ThreadInternal;
+use value::{Cloner, MoveUserdata, Value};
+use api::{Generic, Userdata, VmType, WithVM};
+use api::generic::A;
+
+/// A reference to a value which does not keep the value alive.
+///
+/// Only values which are owned by the garbage collector that allocated the weak reference can be
+/// collected, values from parent threads are kept alive for as long as the weak reference is.
+pub struct Weak<T> {
+    value: Mutex<Option<Value>>,
+    /// `false` if `value` is kept alive by this reference
+    weak: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Weak<T> {
+    fn new(value: Value, gc: &Gc) -> Weak<T> {
+        Weak {
+            value: Mutex::new(Some(value)),
+            weak: value.is_gc_ptr() && value.generation() == gc.generation(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Userdata for Weak<T>
+where
+    T: Any + Send + Sync,
+{
+    fn deep_clone(&self, deep_cloner: &mut Cloner) -> Result<GcPtr<Box<Userdata>>> {
+        let value = *self.value.lock().unwrap();
+        let data: Box<Userdata> = match value {
+            Some(value) => {
+                let cloned_value = deep_cloner.deep_clone(value)?;
+                Box::new(Weak::<A>::new(cloned_value, deep_cloner.gc()))
+            }
+            None => Box::new(Weak::<A> {
+                value: Mutex::new(None),
+                weak: false,
+                _marker: PhantomData,
+            }),
+        };
+        deep_cloner.gc().alloc(MoveUserdata(data))
+    }
+
+    fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    fn clear_dead(&self, gc: &Gc) {
+        if !self.weak {
+            return;
+        }
+        let mut value = self.value.lock().unwrap();
+        if value.map_or(false, |value| !value.is_alive(gc)) {
+            *value = None;
+        }
+    }
+}
+
+impl<T> fmt::Debug for Weak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Weak({:?})", *self.value.lock().unwrap())
+    }
+}
+
+impl<T> Traverseable for Weak<T> {
+    fn traverse(&self, gc: &mut Gc) {
+        if !self.weak {
+            self.value.lock().unwrap().traverse(gc)
+        }
+    }
+}
+
+impl<T> VmType for Weak<T>
+where
+    T: VmType,
+    T::Type: Sized,
+{
+    type Type = Weak<T::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        let env = vm.global_env().get_env();
+        let symbol = env.find_type_info("Weak").unwrap().name.clone();
+        let ctor = Type::ident(symbol);
+        Type::app(ctor, collect![T::make_type(vm)])
+    }
+}
+
+fn make_weak(a: WithVM<Generic<A>>) -> Weak<A> {
+    let context = a.vm.context();
+    Weak::new(a.value.0, &context.gc)
+}
+
+fn get(weak: &Weak<A>) -> Option<Generic<A>> {
+    weak.value.lock().unwrap().map(Generic::from)
+}
+
+mod std {
+    pub use weak;
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    let _ = vm.register_type::<Weak<A>>("Weak", &["a"]);
+    ExternModule::new(
+        vm,
+        record!{
+            weak => named_primitive!(1, "std.weak.weak", std::weak::make_weak),
+            get => named_primitive!(1, "std.weak.get", std::weak::get),
+        },
+    )
+}